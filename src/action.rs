@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
-#[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
+use crate::{
+    app::{CardInfo, ReviseCardDetails},
+    check::BrokenLink,
+    store::ID,
+    usecase::{CardSummary, Deck, Draft, EditOutcome},
+};
+
+#[derive(Debug, Clone, PartialEq, Display, Serialize, Deserialize)]
 pub enum Action {
     Tick,
     Render,
@@ -12,4 +19,31 @@ pub enum Action {
     ClearScreen,
     Error(String),
     Help,
+    // Answers from the store worker. They carry data and can't be bound to keys.
+    #[serde(skip)]
+    DecksLoaded(Vec<Deck>),
+    #[serde(skip)]
+    CardsLoaded {
+        generation: u64,
+        cards: Vec<CardSummary>,
+    },
+    #[serde(skip)]
+    CardInfoLoaded(Box<CardInfo>),
+    /// The card whose info was asked for is gone, another process removed it.
+    #[serde(skip)]
+    CardRemoved(ID),
+    #[serde(skip)]
+    ReviseCardLoaded(ReviseCardDetails),
+    #[serde(skip)]
+    EditorClosed(Result<EditOutcome, String>),
+    #[serde(skip)]
+    DraftsLoaded(Vec<Draft>),
+    /// The editor closed on a bulk edit, this is the summary of the changes to confirm.
+    #[serde(skip)]
+    BulkEditReady(Vec<String>),
+    /// The opener of a card's source is done, or has been started for URLs.
+    #[serde(skip)]
+    SourceOpened(Result<(), String>),
+    #[serde(skip)]
+    LinksChecked(Result<Vec<BrokenLink>, String>),
 }
@@ -1,23 +1,47 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
 use color_eyre::Result;
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
-    prelude::Rect,
+    prelude::{Position, Rect},
+    text::{Line, Text},
     widgets::{ListState, TableState},
 };
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::{debug, info, trace};
 use tui_input::{backend::crossterm::EventHandler, Input};
 
 use crate::{
     action::Action,
+    check::BrokenLink,
     components::{home::Home, Component},
-    config::Config,
+    config::{key_event_to_string, Config},
+    errors,
+    form::{self, CardForm, FormEvent},
+    frontmatter,
+    source::{Excerpt, Source},
     store::{SqliteStore, ID},
+    template,
     tui::{Event, Tui},
-    usecase::{Card, CardSummary, Deck, Review, Usecase},
+    usecase::{Card, CardSummary, Deck, Draft, EditOutcome, Review, Usecase},
+    worker::{CardList, Request, StoreWorker},
 };
 
+/// Number of fixed entries (Review, Suspended, All Collection) above the decks in the sidebar.
+const FIXED_LISTS: usize = 3;
+
+/// Two clicks on the same card within this interval open the review popup.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// How long a toast stays in the footer.
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ReviseCardDetails {
     pub id: ID,
     pub next_dates: Vec<(&'static str, f32)>,
@@ -25,7 +49,15 @@ pub struct ReviseCardDetails {
 
 pub struct AppState {
     pub decks: Vec<Deck>,
+    /// Every card of `card_list`, use [`AppState::visible_cards`] for what the table shows.
     pub cards: Vec<CardSummary>,
+    /// Indices into `cards` that match the search, in table order. Row `n` of the table and
+    /// `cards_table_state` refer to `cards[visible[n]]`.
+    visible: Vec<usize>,
+    /// The list the cards were loaded from, follows the sidebar selection.
+    pub card_list: CardList,
+    /// True until the store worker answers the latest request for `card_list`.
+    pub loading_cards: bool,
     pub focused: Focused,
     pub card_info: Option<CardInfo>,
     pub cards_table_state: TableState,
@@ -35,6 +67,50 @@ pub struct AppState {
     pub revise_card: Option<ReviseCardDetails>,
     pub confirm_delete_deck: Option<ID>,
     pub confirm_delete_card: Option<ID>,
+    /// Draft offered for recovery, the others wait in [`App`]'s queue.
+    pub recover_draft: Option<Draft>,
+    /// First line of the description shown in the detail pane.
+    pub description_scroll: u16,
+    /// First review shown in the revlog table.
+    pub revlog_scroll: usize,
+    /// Whether the sidebar is shown when the terminal is wide enough for it.
+    pub show_sidebar: bool,
+    /// Whether the card info and description panes are shown below the card table.
+    pub show_details: bool,
+    /// The description takes the whole screen.
+    pub zoom_description: bool,
+    /// Last rendered description and its source, Markdown is only rendered when it changes.
+    pub rendered_description: Option<(String, Text<'static>)>,
+    /// Last highlighted source excerpt, highlighting only runs when it changes.
+    pub rendered_excerpt: Option<(Excerpt, Vec<Line<'static>>)>,
+    /// Where things were drawn in the last frame, written while rendering.
+    pub areas: ScreenAreas,
+    /// Short message shown in the footer, and when it was shown.
+    pub toast: Option<(String, Instant)>,
+    /// The add or edit form, shown over the table.
+    pub card_form: Option<CardForm>,
+    /// The quick-capture line in the footer while a card is being typed into it.
+    pub quick_capture: Option<Input>,
+    /// Cards marked for a bulk edit.
+    pub marked: HashSet<ID>,
+    /// Summary of a bulk edit waiting to be confirmed.
+    pub bulk_summary: Option<Vec<String>>,
+    /// Result of the last link check while its popup is open.
+    pub broken_links: Option<Vec<BrokenLink>>,
+    pub broken_links_state: TableState,
+}
+
+/// Screen areas of the last frame, used to map mouse events to what was drawn there. Areas of
+/// widgets that were not drawn are empty.
+#[derive(Debug, Default)]
+pub struct ScreenAreas {
+    pub sidebar: Rect,
+    /// Rows of the card table, without borders and header.
+    pub cards_table: Rect,
+    pub description: Rect,
+    pub revlog: Rect,
+    /// One line per rating of the revise popup, the first one is rating 1.
+    pub rating_buttons: Vec<Rect>,
 }
 
 #[derive(PartialEq, Eq)]
@@ -43,9 +119,12 @@ pub enum Focused {
     Cards,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct CardInfo {
     pub card: Card,
     pub reviews: Vec<Review>,
+    /// Lines of the card's source file, read again each time the card is selected.
+    pub excerpt: Option<Excerpt>,
 }
 
 impl Default for AppState {
@@ -53,6 +132,9 @@ impl Default for AppState {
         Self {
             decks: Vec::new(),
             cards: Vec::new(),
+            visible: Vec::new(),
+            card_list: CardList::Review,
+            loading_cards: true,
             focused: Focused::Cards,
             card_info: None,
             cards_table_state: TableState::default().with_selected(Some(0)),
@@ -62,6 +144,103 @@ impl Default for AppState {
             revise_card: None,
             confirm_delete_deck: None,
             confirm_delete_card: None,
+            recover_draft: None,
+            toast: None,
+            card_form: None,
+            quick_capture: None,
+            marked: HashSet::new(),
+            bulk_summary: None,
+            broken_links: None,
+            broken_links_state: TableState::default(),
+            description_scroll: 0,
+            revlog_scroll: 0,
+            show_sidebar: true,
+            show_details: true,
+            zoom_description: false,
+            rendered_description: None,
+            rendered_excerpt: None,
+            areas: ScreenAreas::default(),
+        }
+    }
+}
+
+impl AppState {
+    /// Cards shown in the table, i.e. those matching the search.
+    pub fn visible_cards(&self) -> impl Iterator<Item = &CardSummary> {
+        self.visible.iter().map(|&ind| &self.cards[ind])
+    }
+
+    pub fn selected_card(&self) -> Option<&CardSummary> {
+        self.cards_table_state
+            .selected()
+            .and_then(|row| self.visible.get(row))
+            .map(|&ind| &self.cards[ind])
+    }
+
+    /// Replaces the cards, keeping the selected card selected if it is still visible.
+    fn set_cards(&mut self, cards: Vec<CardSummary>) {
+        let selected = self.selected_card().map(|card| card.id);
+        self.cards = cards;
+        self.apply_search(selected);
+    }
+
+    /// Recomputes the visible rows after the search changed.
+    fn refresh_search(&mut self) {
+        let selected = self.selected_card().map(|card| card.id);
+        self.apply_search(selected);
+    }
+
+    fn clear_search(&mut self) {
+        self.cards_table_input.reset();
+        self.cards_table_searching = false;
+        self.refresh_search();
+    }
+
+    fn apply_search(&mut self, selected: Option<ID>) {
+        let query = self.cards_table_input.value();
+        self.visible = self
+            .cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.title.contains(query))
+            .map(|(ind, _)| ind)
+            .collect();
+        let row = selected.and_then(|id| self.visible_cards().position(|card| card.id == id));
+        if row.is_some() {
+            self.cards_table_state.select(row);
+        }
+        clamp_selection(&mut self.cards_table_state, self.visible.len());
+    }
+
+    fn selected_deck(&self) -> Option<&Deck> {
+        self.decks_list_state
+            .selected()
+            .and_then(|ind| ind.checked_sub(FIXED_LISTS))
+            .and_then(|ind| self.decks.get(ind))
+    }
+
+    fn list_at(&self, ind: usize) -> Option<CardList> {
+        match ind {
+            0 => Some(CardList::Review),
+            1 => Some(CardList::Suspended),
+            2 => Some(CardList::All),
+            _ => self
+                .decks
+                .get(ind - FIXED_LISTS)
+                .map(|deck| CardList::Deck(deck.id)),
+        }
+    }
+
+    fn index_of(&self, list: CardList) -> Option<usize> {
+        match list {
+            CardList::Review => Some(0),
+            CardList::Suspended => Some(1),
+            CardList::All => Some(2),
+            CardList::Deck(id) => self
+                .decks
+                .iter()
+                .position(|deck| deck.id == id)
+                .map(|ind| ind + FIXED_LISTS),
         }
     }
 }
@@ -77,8 +256,25 @@ pub struct App {
     last_tick_key_events: Vec<KeyEvent>,
     action_tx: mpsc::UnboundedSender<Action>,
     action_rx: mpsc::UnboundedReceiver<Action>,
-    usecase: Usecase<SqliteStore>,
+    store: StoreWorker,
     state: AppState,
+    /// Set whenever something visible may have changed; the next loop iteration redraws.
+    needs_render: bool,
+    last_render: Instant,
+    /// Minute of the last frame, relative dates are redrawn when it rolls over.
+    last_render_minute: i64,
+    /// Incremented for every card list request, older answers are dropped.
+    cards_generation: u64,
+    /// Card whose info was last requested, `None` forces a new request.
+    card_info_request: Option<ID>,
+    /// The terminal is handed to the editor until the store worker reports back.
+    editing: bool,
+    /// Time and card of the last click on the card table, to detect double clicks.
+    last_click: Option<(Instant, ID)>,
+    /// Drafts left over from earlier sessions, offered one after the other.
+    drafts: Vec<Draft>,
+    /// Template for new cards given on the command line.
+    template: Option<String>,
 }
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -88,62 +284,68 @@ pub enum Mode {
 }
 
 impl App {
-    pub fn new(tick_rate: f64, frame_rate: f64, usecase: Usecase<SqliteStore>) -> Result<Self> {
+    pub fn new(
+        tick_rate: Option<f64>,
+        frame_rate: Option<f64>,
+        usecase: Usecase<SqliteStore>,
+    ) -> Result<Self> {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let config = Config::new()?;
+        let template = usecase.template().map(str::to_string);
         Ok(Self {
-            tick_rate,
-            frame_rate,
+            tick_rate: tick_rate.unwrap_or(config.config.tick_rate),
+            frame_rate: frame_rate.unwrap_or(config.config.frame_rate),
             components: vec![
                 Box::new(Home::new()),
                 // Box::new(FpsCounter::default())
             ],
             should_quit: false,
             should_suspend: false,
-            config: Config::new()?,
+            config,
             mode: Mode::Home,
             last_tick_key_events: Vec::new(),
+            store: StoreWorker::spawn(usecase, action_tx.clone()),
             action_tx,
             action_rx,
-            usecase,
             state: AppState::default(),
+            needs_render: true,
+            last_render: Instant::now(),
+            last_render_minute: 0,
+            cards_generation: 0,
+            card_info_request: None,
+            editing: false,
+            last_click: None,
+            drafts: Vec::new(),
+            template,
         })
     }
 
-    pub fn get_cards_in_deck(&self, ind: usize, decks: &Vec<Deck>) -> Vec<CardSummary> {
-        if ind == 0 {
-            return self.usecase.list_card_summaries(None, false, false);
-        }
-
-        if ind == 1 {
-            return self.usecase.list_card_summaries(None, true, true);
-        }
-
-        if ind == 2 {
-            return self.usecase.list_card_summaries(None, true, false);
-        }
-
-        let deck = decks.get(ind - 3).expect("There is atleast one deck");
-        return self
-            .usecase
-            .list_card_summaries(Some(deck.id), false, false);
+    /// Opens the form for a new card, filled in from a template if there is one.
+    fn open_card_form(&mut self) {
+        let deck = self.state.selected_deck().map(|deck| deck.name.clone());
+        let deck = deck.as_deref();
+        let template = template::load(self.template.as_deref(), deck)
+            .map_err(|err| err.to_string())
+            .and_then(|text| text.map(|text| frontmatter::parse(&text)).transpose());
+        let form = match template {
+            Ok(Some((fm, body))) => CardForm::from_template(fm, &body, &self.state.decks),
+            Ok(None) => CardForm::add(deck, &self.state.decks),
+            Err(err) => {
+                let form = CardForm::add(deck, &self.state.decks);
+                self.toast(&format!("Template not used: {err}"));
+                form
+            }
+        };
+        self.state.card_form = Some(form);
     }
 
     pub async fn run(&mut self) -> Result<()> {
         let mut tui = Tui::new()?
-            // .mouse(true) // uncomment this line to enable mouse support
-            .tick_rate(self.tick_rate)
-            .frame_rate(self.frame_rate);
+            .mouse(self.config.config.mouse)
+            .tick_rate(self.tick_rate);
 
-        self.state.decks = self.usecase.list_decks();
-        self.state.cards = self.get_cards_in_deck(0, &self.state.decks);
-
-        if let Some(selected_row) = self.state.cards_table_state.selected() {
-            if let Some(card) = self.state.cards.get(selected_row) {
-                let card = self.usecase.get_card(card.id);
-                let reviews = self.usecase.get_reviews(card.id);
-                self.state.card_info = Some(CardInfo { card, reviews });
-            }
-        }
+        self.reload()?;
+        self.store.send(Request::LoadDrafts)?;
 
         tui.enter()?;
 
@@ -161,11 +363,16 @@ impl App {
         loop {
             self.handle_events(&mut tui).await?;
             self.handle_actions(&mut tui)?;
+            if self.needs_render
+                && !self.editing
+                && self.last_render.elapsed() >= self.frame_interval()
+            {
+                self.render(&mut tui)?;
+            }
             if self.should_suspend {
                 tui.suspend()?;
                 action_tx.send(Action::Resume)?;
                 action_tx.send(Action::ClearScreen)?;
-                // tui.mouse(true);
                 tui.enter()?;
             } else if self.should_quit {
                 tui.stop()?;
@@ -176,10 +383,159 @@ impl App {
         Ok(())
     }
 
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.frame_rate)
+    }
+
+    /// Requests the decks and the current card list from the store worker.
+    fn reload(&mut self) -> Result<()> {
+        self.store.send(Request::LoadDecks)?;
+        self.load_cards()
+    }
+
+    fn load_cards(&mut self) -> Result<()> {
+        self.cards_generation += 1;
+        self.state.loading_cards = true;
+        // The selected card may have changed even if its id did not.
+        self.card_info_request = None;
+        self.store.send(Request::LoadCards {
+            list: self.state.card_list,
+            generation: self.cards_generation,
+        })
+    }
+
+    /// Selects an entry in the sidebar and loads its cards.
+    fn select_list(&mut self, ind: usize) -> Result<()> {
+        let Some(list) = self.state.list_at(ind) else {
+            return Ok(());
+        };
+        self.state.decks_list_state.select(Some(ind));
+        if list != self.state.card_list {
+            self.state.card_list = list;
+            self.load_cards()?;
+        }
+        Ok(())
+    }
+
+    /// Requests the info of the selected card unless it is already shown or on its way.
+    fn sync_card_info(&mut self) -> Result<()> {
+        let selected = self.state.selected_card().map(|card| card.id);
+        if selected.is_none() {
+            self.state.card_info = None;
+        }
+        if selected != self.card_info_request {
+            self.card_info_request = selected;
+            if let Some(id) = selected {
+                self.store.send(Request::LoadCardInfo(id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the terminal to the editor, which the store worker runs for `request`.
+    fn open_editor(&mut self, tui: &mut Tui, request: Request) -> Result<()> {
+        tui.exit()?;
+        self.editing = true;
+        self.store.send(request)
+    }
+
+    /// Keys of the broken links popup: suspend or edit the card of the selected problem.
+    fn handle_broken_links_key(&mut self, tui: &mut Tui, key: KeyEvent) -> Result<()> {
+        let Some(broken) = self.state.broken_links.as_mut() else {
+            return Ok(());
+        };
+        let list_state = &mut self.state.broken_links_state;
+        let selected = list_state.selected().and_then(|ind| broken.get(ind));
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => list_state.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => list_state.select_previous(),
+            KeyCode::Char('s') => {
+                if let Some(id) = selected.map(|link| link.card_id) {
+                    broken.retain(|link| link.card_id != id);
+                    if broken.is_empty() {
+                        self.state.broken_links = None;
+                    }
+                    self.store.send(Request::SuspendCard(id))?;
+                    self.toast("Card suspended");
+                    self.load_cards()?;
+                }
+            }
+            // The check runs again once the editor is closed.
+            KeyCode::Char('e') => {
+                if let Some(id) = selected.map(|link| link.card_id) {
+                    self.open_editor(tui, Request::EditCard(id))?;
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.state.broken_links = None,
+            _ => {}
+        }
+        if let Some(broken) = self.state.broken_links.as_ref() {
+            clamp_selection(&mut self.state.broken_links_state, broken.len());
+        }
+        Ok(())
+    }
+
+    /// Opens the source of the selected card. Files take over the terminal like the editor.
+    fn open_source(&mut self, tui: &mut Tui) -> Result<()> {
+        let selected = self.state.selected_card().map(|card| card.id);
+        let Some(info) = self
+            .state
+            .card_info
+            .as_ref()
+            .filter(|info| Some(info.card.id) == selected)
+        else {
+            return Ok(());
+        };
+        if info.card.source.is_empty() {
+            self.toast("This card has no source");
+            return Ok(());
+        }
+        let source = match Source::parse(&info.card.source) {
+            Ok(source) => source,
+            Err(err) => {
+                self.toast(&err);
+                return Ok(());
+            }
+        };
+        let openers = self.config.config.openers.clone();
+        if source.is_file() {
+            tui.exit()?;
+            self.editing = true;
+        }
+        self.store.send(Request::OpenSource { source, openers })
+    }
+
+    fn toast(&mut self, message: &str) {
+        self.state.toast = Some((message.to_string(), Instant::now()));
+    }
+
+    /// Offers the next draft for recovery, if any.
+    fn next_draft(&mut self) {
+        self.state.recover_draft = (!self.drafts.is_empty()).then(|| self.drafts.remove(0));
+    }
+
     async fn handle_events(&mut self, tui: &mut Tui) -> Result<()> {
-        let Some(event) = tui.next_event().await else {
+        // A pending redraw that was throttled by the frame rate must not wait for the next input.
+        let render_deadline =
+            (self.needs_render && !self.editing).then(|| self.last_render + self.frame_interval());
+        let event = tokio::select! {
+            event = tui.next_event() => event,
+            Some(action) = self.action_rx.recv() => return self.handle_action(action, tui),
+            _ = sleep_until(render_deadline) => return Ok(()),
+        };
+        let Some(event) = event else {
             return Ok(());
         };
+        let is_mouse_move = matches!(
+            event,
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Moved,
+                ..
+            })
+        );
+        if !matches!(event, Event::Tick) && !is_mouse_move {
+            self.needs_render = true;
+        }
         let action_tx = self.action_tx.clone();
         match event {
             Event::Quit => action_tx.send(Action::Quit)?,
@@ -187,6 +543,7 @@ impl App {
             Event::Render => action_tx.send(Action::Render)?,
             Event::Resize(x, y) => action_tx.send(Action::Resize(x, y))?,
             Event::Key(key) => self.handle_key_event(key, tui)?,
+            Event::Mouse(mouse) if !is_mouse_move => self.handle_mouse_event(mouse)?,
             _ => {}
         }
         for component in self.components.iter_mut() {
@@ -202,11 +559,68 @@ impl App {
         let Some(keymap) = self.config.keybindings.get(&self.mode) else {
             return Ok(());
         };
-        match keymap.get(&vec![key]) {
+        let key_name = format!("Key: {}", key_event_to_string(&key));
+        trace!("{key_name}");
+        errors::record_event(key_name);
+        // The recovery prompt is modal.
+        if let Some(draft) = &self.state.recover_draft {
+            match key.code {
+                KeyCode::Char('y') => {
+                    let request = Request::RecoverDraft(draft.clone());
+                    self.state.recover_draft = None;
+                    self.open_editor(tui, request)?;
+                }
+                KeyCode::Char('n') => {
+                    self.store.send(Request::DiscardDraft(draft.path.clone()))?;
+                    self.next_draft();
+                }
+                // Kept for the next start.
+                KeyCode::Esc => self.next_draft(),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.state.bulk_summary.is_some() {
+            match key.code {
+                KeyCode::Char('y') => {
+                    self.state.bulk_summary = None;
+                    self.state.marked.clear();
+                    self.store.send(Request::ApplyBulkEdit)?;
+                    self.toast("Bulk edit applied");
+                    self.reload()?;
+                }
+                KeyCode::Char('e') => {
+                    self.state.bulk_summary = None;
+                    self.open_editor(tui, Request::EditBulkAgain)?;
+                }
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.state.bulk_summary = None;
+                    self.store.send(Request::DiscardBulkEdit)?;
+                    self.toast("Cancelled");
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.state.broken_links.is_some() {
+            return self.handle_broken_links_key(tui, key);
+        }
+        // Forms take every key, typed text must not trigger bindings such as `q` for quit.
+        if self.state.card_form.is_some() || self.state.quick_capture.is_some() {
+            return self.handle_form_key(key);
+        }
+        // Typed search text must not trigger bindings such as `q` for quit.
+        let keymap_key = if self.state.cards_table_searching {
+            None
+        } else {
+            keymap.get(&vec![key])
+        };
+        match keymap_key {
             Some(action) => {
                 info!("Got action: {action:?}");
                 action_tx.send(action.clone())?;
             }
+            _ if self.state.cards_table_searching => {}
             _ => {
                 // If the key was not handled as a single key action,
                 // then consider it for multi-key combinations.
@@ -220,56 +634,31 @@ impl App {
             }
         }
 
+        // The sidebar may have been hidden by a resize.
+        if self.state.focused == Focused::Sidebar && self.state.areas.sidebar.is_empty() {
+            self.state.focused = Focused::Cards;
+        }
+
         if self.state.focused == Focused::Cards {
             if self.state.cards_table_searching {
                 match key.code {
-                    KeyCode::Enter | KeyCode::Esc => {
+                    KeyCode::Enter => {
                         self.state.cards_table_searching = false;
                     }
+                    KeyCode::Esc => {
+                        self.state.clear_search();
+                    }
                     _ => {
                         self.state
                             .cards_table_input
                             .handle_event(&crossterm::event::Event::Key(key));
+                        self.state.refresh_search();
                     }
                 }
             } else if self.state.revise_card.is_some() {
-                let card_id = self.state.revise_card.as_ref().unwrap().id;
                 match key.code {
-                    KeyCode::Char('1') => {
-                        self.usecase.revise_card(card_id, 1);
-                        self.state.revise_card = None;
-
-                        self.state.cards = self.get_cards_in_deck(
-                            self.state.decks_list_state.selected().unwrap(),
-                            &self.state.decks,
-                        );
-                    }
-                    KeyCode::Char('2') => {
-                        self.usecase.revise_card(card_id, 2);
-                        self.state.revise_card = None;
-
-                        self.state.cards = self.get_cards_in_deck(
-                            self.state.decks_list_state.selected().unwrap(),
-                            &self.state.decks,
-                        );
-                    }
-                    KeyCode::Char('3') => {
-                        self.usecase.revise_card(card_id, 3);
-                        self.state.revise_card = None;
-
-                        self.state.cards = self.get_cards_in_deck(
-                            self.state.decks_list_state.selected().unwrap(),
-                            &self.state.decks,
-                        );
-                    }
-                    KeyCode::Char('4') => {
-                        self.usecase.revise_card(card_id, 4);
-                        self.state.revise_card = None;
-
-                        self.state.cards = self.get_cards_in_deck(
-                            self.state.decks_list_state.selected().unwrap(),
-                            &self.state.decks,
-                        );
+                    KeyCode::Char(n @ '1'..='4') => {
+                        self.rate_card(n.to_digit(10).unwrap() as usize)?;
                     }
                     KeyCode::Esc => {
                         self.state.revise_card = None;
@@ -279,145 +668,140 @@ impl App {
             } else {
                 match key.code {
                     KeyCode::Char(n @ '1'..='9') => {
-                        n.to_digit(10).map(|n| {
-                            if (n as usize) > self.state.decks.len() {
-                                return;
+                        if let Some(n) = n.to_digit(10) {
+                            if (n as usize) <= self.state.decks.len() {
+                                self.select_list(n as usize + FIXED_LISTS - 1)?;
                             }
-                            self.state.decks_list_state.select(Some(n as usize + 2));
-                            self.state.cards = self.get_cards_in_deck(
-                                self.state.decks_list_state.selected().unwrap(),
-                                &self.state.decks,
-                            );
-                        });
-                    }
-                    KeyCode::Tab | KeyCode::Char('h') => {
+                        }
+                    }
+                    KeyCode::Tab | KeyCode::Char('h') if !self.state.areas.sidebar.is_empty() => {
                         self.state.focused = Focused::Sidebar;
                     }
+                    KeyCode::Char('b') => {
+                        self.state.show_sidebar = !self.state.show_sidebar;
+                    }
+                    KeyCode::Char('i') => {
+                        self.state.show_details = !self.state.show_details;
+                    }
+                    KeyCode::Char('z') => {
+                        self.state.zoom_description = !self.state.zoom_description;
+                    }
+                    KeyCode::Esc if self.state.zoom_description => {
+                        self.state.zoom_description = false;
+                    }
+                    KeyCode::Char('J') | KeyCode::PageDown => {
+                        self.state.description_scroll =
+                            self.state.description_scroll.saturating_add(1);
+                    }
+                    KeyCode::Char('K') | KeyCode::PageUp => {
+                        self.state.description_scroll =
+                            self.state.description_scroll.saturating_sub(1);
+                    }
+                    KeyCode::Char(']') => {
+                        self.state.revlog_scroll = self.state.revlog_scroll.saturating_add(1);
+                    }
+                    KeyCode::Char('[') => {
+                        self.state.revlog_scroll = self.state.revlog_scroll.saturating_sub(1);
+                    }
                     KeyCode::Char('k') => {
                         self.state.cards_table_state.select_previous();
                     }
                     KeyCode::Char('j') => {
                         self.state.cards_table_state.select_next();
+                        let len = self.state.visible.len();
+                        clamp_selection(&mut self.state.cards_table_state, len);
                     }
                     KeyCode::Char('/') => {
                         self.state.cards_table_searching = true;
                     }
+                    KeyCode::Char('c') => {
+                        self.state.clear_search();
+                    }
                     KeyCode::Char('d') => {
-                        if let Some(selected_row) = self.state.cards_table_state.selected() {
-                            if let Some(card) = self.state.cards.get(selected_row) {
-                                self.state.confirm_delete_card = Some(card.id);
-                            }
+                        if let Some(card) = self.state.selected_card() {
+                            self.state.confirm_delete_card = Some(card.id);
                         }
                     }
                     KeyCode::Char('y') => {
-                        if let Some(card_id) = self.state.confirm_delete_card {
-                            self.usecase.remove_card(card_id);
-                            self.state.decks = self.usecase.list_decks();
-                            self.state.cards = self.get_cards_in_deck(
-                                self.state.decks_list_state.selected().unwrap(),
-                                &self.state.decks,
-                            );
-                            self.state.confirm_delete_card = None;
+                        if let Some(card_id) = self.state.confirm_delete_card.take() {
+                            self.store.send(Request::RemoveCard(card_id))?;
+                            self.reload()?;
                         }
                     }
                     KeyCode::Char('n') | KeyCode::Esc => {
                         self.state.confirm_delete_card = None;
                     }
-
-                    KeyCode::Char('e') => {
-                        if let Some(selected_row) = self.state.cards_table_state.selected() {
-                            if let Some(card) = self.state.cards.get(selected_row) {
-                                tui.exit().unwrap();
-                                self.usecase.edit_card(card.id);
-                                tui.enter()?;
-                                tui.terminal.clear().unwrap();
-                                self.state.decks = self.usecase.list_decks();
-                                self.state.cards = self.get_cards_in_deck(
-                                    self.state.decks_list_state.selected().unwrap(),
-                                    &self.state.decks,
-                                );
+                    KeyCode::Char('F') => {
+                        // The form needs the whole card, which comes with its info.
+                        let selected = self.state.selected_card().map(|card| card.id);
+                        if let Some(info) = self.state.card_info.as_ref() {
+                            if Some(info.card.id) == selected {
+                                let form = CardForm::edit(&info.card, &self.state.decks);
+                                self.state.card_form = Some(form);
                             }
                         }
                     }
-
+                    KeyCode::Char('o') => self.open_source(tui)?,
+                    KeyCode::Char('L') => self.store.send(Request::CheckLinks)?,
+                    KeyCode::Char('v') => {
+                        if let Some(id) = self.state.selected_card().map(|card| card.id) {
+                            if !self.state.marked.remove(&id) {
+                                self.state.marked.insert(id);
+                            }
+                            self.state.cards_table_state.select_next();
+                            let len = self.state.visible.len();
+                            clamp_selection(&mut self.state.cards_table_state, len);
+                        }
+                    }
+                    KeyCode::Char('V') => {
+                        // Without marks the whole list is edited.
+                        let ids: Vec<_> = if self.state.marked.is_empty() {
+                            self.state.visible_cards().map(|card| card.id).collect()
+                        } else {
+                            let mut ids: Vec<_> = self.state.marked.iter().copied().collect();
+                            ids.sort();
+                            ids
+                        };
+                        if !ids.is_empty() {
+                            self.open_editor(tui, Request::BulkEdit(ids))?;
+                        }
+                    }
+                    KeyCode::Char('e') => {
+                        if let Some(card) = self.state.selected_card() {
+                            let request = Request::EditCard(card.id);
+                            self.open_editor(tui, request)?;
+                        }
+                    }
                     _ => {}
                 }
 
-                let is_suspended = self.state.decks_list_state.selected().unwrap() == 1;
-
-                if is_suspended {
-                    match key.code {
-                        KeyCode::Char('s') => {
-                            // If in supended list, unsuspend the cards.
-                            if let Some(selected_row) = self.state.cards_table_state.selected() {
-                                if let Some(card) = self.state.cards.get(selected_row) {
-                                    self.usecase.unsuspend_card(card.id);
-                                    self.state.cards = self.get_cards_in_deck(
-                                        self.state.decks_list_state.selected().unwrap(),
-                                        &self.state.decks,
-                                    );
-                                }
-                            }
+                if self.state.card_list == CardList::Suspended {
+                    if let KeyCode::Char('s') = key.code {
+                        // If in supended list, unsuspend the cards.
+                        if let Some(card) = self.state.selected_card() {
+                            self.store.send(Request::UnsuspendCard(card.id))?;
+                            self.load_cards()?;
                         }
-                        _ => {}
                     }
                 } else {
                     match key.code {
+                        KeyCode::Char('f') => {
+                            self.open_card_form();
+                        }
                         KeyCode::Char('a') => {
-                            tui.exit().unwrap();
-                            let current_deck = if self.state.decks_list_state.selected().unwrap() >= 3 {
-                                let deck_index = self.state.decks_list_state.selected().unwrap() - 3;
-                                Some(self.state.decks[deck_index].name.as_str())
-                            } else {
-                                None
-                            };
-                            self.usecase.add_card(current_deck);
-                            tui.enter()?;
-                            tui.terminal.clear().unwrap();
-                            self.state.decks = self.usecase.list_decks();
-                            self.state.cards = self.get_cards_in_deck(
-                                self.state.decks_list_state.selected().unwrap(),
-                                &self.state.decks,
-                            );
+                            let deck = self.state.selected_deck().map(|deck| deck.name.clone());
+                            self.open_editor(tui, Request::AddCard { deck })?;
                         }
-
-                        KeyCode::Char('e') => {
-                            if let Some(selected_row) = self.state.cards_table_state.selected() {
-                                if let Some(card) = self.state.cards.get(selected_row) {
-                                    tui.exit().unwrap();
-                                    self.usecase.edit_card(card.id);
-                                    tui.enter()?;
-                                    tui.terminal.clear().unwrap();
-                                    self.state.decks = self.usecase.list_decks();
-                                    self.state.cards = self.get_cards_in_deck(
-                                        self.state.decks_list_state.selected().unwrap(),
-                                        &self.state.decks,
-                                    );
-                                }
-                            }
+                        KeyCode::Char('+') => {
+                            self.state.quick_capture = Some(Input::default());
                         }
 
-                        KeyCode::Char('r') => {
-                            if let Some(selected_row) = self.state.cards_table_state.selected() {
-                                if let Some(card) = self.state.cards.get(selected_row) {
-                                    self.state.revise_card = Some(ReviseCardDetails {
-                                        id: card.id,
-                                        next_dates: self.usecase.get_next_dates(card),
-                                    });
-                                }
-                            }
-                        }
+                        KeyCode::Char('r') => self.review_selected_card()?,
 
                         KeyCode::Char('s') => {
-                            // If in supended list, unsuspend the cards.
-                            if let Some(selected_row) = self.state.cards_table_state.selected() {
-                                if let Some(card) = self.state.cards.get(selected_row) {
-                                    self.usecase.suspend_card(card.id);
-                                    self.state.cards = self.get_cards_in_deck(
-                                        self.state.decks_list_state.selected().unwrap(),
-                                        &self.state.decks,
-                                    );
-                                }
+                            if let Some(card) = self.state.selected_card() {
+                                self.store.send(Request::SuspendCard(card.id))?;
+                                self.load_cards()?;
                             }
                         }
 
@@ -426,40 +810,31 @@ impl App {
                 }
             }
         } else if self.state.focused == Focused::Sidebar {
+            let selected = self.state.decks_list_state.selected().unwrap_or(0);
             match key.code {
                 KeyCode::Tab | KeyCode::Char('l') => {
                     self.state.focused = Focused::Cards;
                 }
+                KeyCode::Char('b') => {
+                    self.state.show_sidebar = false;
+                    self.state.focused = Focused::Cards;
+                }
                 KeyCode::Char('k') => {
-                    self.state.decks_list_state.select_previous();
-                    self.state.cards = self.get_cards_in_deck(
-                        self.state.decks_list_state.selected().unwrap(),
-                        &self.state.decks,
-                    );
+                    self.select_list(selected.saturating_sub(1))?;
                 }
                 KeyCode::Char('j') => {
-                    self.state.decks_list_state.select_next();
-                    let selected = self.state.decks_list_state.selected().unwrap().clamp(0, &self.state.decks.len() + 2);
-                    self.state.cards = self.get_cards_in_deck(
-                        selected,
-                        &self.state.decks,
-                    );
+                    self.select_list(selected + 1)?;
                 }
                 KeyCode::Char('d') => {
-                    if self.state.decks_list_state.selected().unwrap() >= 3 {
-                        let deck_index = self.state.decks_list_state.selected().unwrap() - 3;
-                        if let Some(deck) = self.state.decks.get(deck_index) {
-                            self.state.confirm_delete_deck = Some(deck.id);
-                        }
+                    if let Some(deck) = self.state.selected_deck() {
+                        self.state.confirm_delete_deck = Some(deck.id);
                     }
                 }
                 KeyCode::Char('y') => {
-                    if let Some(deck_id) = self.state.confirm_delete_deck {
-                        self.usecase.delete_deck(deck_id);
-                        self.state.decks = self.usecase.list_decks();
-                        self.state.decks_list_state.select(Some(0));
-                        self.state.cards = self.get_cards_in_deck(0, &self.state.decks);
-                        self.state.confirm_delete_deck = None;
+                    if let Some(deck_id) = self.state.confirm_delete_deck.take() {
+                        self.store.send(Request::DeleteDeck(deck_id))?;
+                        self.select_list(0)?;
+                        self.reload()?;
                     }
                 }
                 KeyCode::Char('n') | KeyCode::Esc => {
@@ -469,40 +844,293 @@ impl App {
             }
         }
 
-        if let Some(selected_row) = self.state.cards_table_state.selected() {
-            if let Some(card) = self.state.cards.get(selected_row) {
-                let card = self.usecase.get_card(card.id);
-                let reviews = self.usecase.get_reviews(card.id);
-                self.state.card_info = Some(CardInfo { card, reviews });
+        self.sync_card_info()
+    }
+
+    /// Keys for the card form or the quick-capture line, whichever is open.
+    fn handle_form_key(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some(input) = self.state.quick_capture.as_mut() {
+            match key.code {
+                KeyCode::Enter => {
+                    let text = input.value().to_string();
+                    let deck = self.state.selected_deck().map(|deck| deck.name.as_str());
+                    match form::parse_quick_capture(&text, deck) {
+                        Some(fm) => {
+                            let deck = fm.deck.clone();
+                            self.store.send(Request::SaveCard {
+                                fm,
+                                body: String::new(),
+                            })?;
+                            self.state.quick_capture = None;
+                            self.toast(&format!("Added to {deck}"));
+                            self.reload()?;
+                        }
+                        None => self.toast("A card needs a title"),
+                    }
+                }
+                KeyCode::Esc => {
+                    self.state.quick_capture = None;
+                    self.toast("Cancelled");
+                }
+                _ => {
+                    input.handle_event(&crossterm::event::Event::Key(key));
+                }
             }
+            return Ok(());
         }
 
+        let Some(form) = self.state.card_form.as_mut() else {
+            return Ok(());
+        };
+        match form.handle_key(key) {
+            Some(FormEvent::Submit(fm, body)) => {
+                self.state.card_form = None;
+                self.store.send(Request::SaveCard { fm, body })?;
+                self.reload()?;
+            }
+            Some(FormEvent::Cancel) => {
+                self.state.card_form = None;
+                self.toast("Cancelled");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<()> {
+        let pos = Position::new(mouse.column, mouse.row);
+        let areas = &self.state.areas;
+        let left_click = mouse.kind == MouseEventKind::Down(MouseButton::Left);
+
+        // Popups are modal, only their own buttons react.
+        if self.state.revise_card.is_some() {
+            let button = areas.rating_buttons.iter().position(|b| b.contains(pos));
+            if let (true, Some(ind)) = (left_click, button) {
+                self.rate_card(ind + 1)?;
+            }
+            return Ok(());
+        }
+        if self.state.confirm_delete_card.is_some()
+            || self.state.confirm_delete_deck.is_some()
+            || self.state.recover_draft.is_some()
+            || self.state.card_form.is_some()
+            || self.state.bulk_summary.is_some()
+            || self.state.broken_links.is_some()
+        {
+            return Ok(());
+        }
+
+        let in_sidebar = areas.sidebar.contains(pos);
+        let in_table = areas.cards_table.contains(pos);
+        let in_description = areas.description.contains(pos);
+        let in_revlog = areas.revlog.contains(pos);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if in_sidebar => {
+                self.state.focused = Focused::Sidebar;
+                // Skip the top border.
+                let line = (pos.y - self.state.areas.sidebar.y).checked_sub(1);
+                if let Some(line) = line {
+                    self.select_list(self.state.decks_list_state.offset() + line as usize)?;
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) if in_table => {
+                self.state.focused = Focused::Cards;
+                let row = self.state.cards_table_state.offset()
+                    + (pos.y - self.state.areas.cards_table.y) as usize;
+                if row < self.state.visible.len() {
+                    self.state.cards_table_state.select(Some(row));
+                    let id = self.state.visible_cards().nth(row).map(|card| card.id);
+                    let now = Instant::now();
+                    let double_click = matches!(
+                        self.last_click,
+                        Some((at, last)) if Some(last) == id && now - at <= DOUBLE_CLICK_INTERVAL
+                    );
+                    if double_click {
+                        self.last_click = None;
+                        if self.state.card_list != CardList::Suspended {
+                            self.review_selected_card()?;
+                        }
+                    } else {
+                        self.last_click = id.map(|id| (now, id));
+                    }
+                }
+            }
+            MouseEventKind::ScrollDown if in_description => {
+                self.state.description_scroll = self.state.description_scroll.saturating_add(1);
+            }
+            MouseEventKind::ScrollUp if in_description => {
+                self.state.description_scroll = self.state.description_scroll.saturating_sub(1);
+            }
+            MouseEventKind::ScrollDown if in_revlog => {
+                self.state.revlog_scroll = self.state.revlog_scroll.saturating_add(1);
+            }
+            MouseEventKind::ScrollUp if in_revlog => {
+                self.state.revlog_scroll = self.state.revlog_scroll.saturating_sub(1);
+            }
+            MouseEventKind::ScrollDown if in_table => {
+                self.state.cards_table_state.select_next();
+                let len = self.state.visible.len();
+                clamp_selection(&mut self.state.cards_table_state, len);
+            }
+            MouseEventKind::ScrollUp if in_table => {
+                self.state.cards_table_state.select_previous();
+            }
+            MouseEventKind::ScrollDown if in_sidebar => {
+                let selected = self.state.decks_list_state.selected().unwrap_or(0);
+                self.select_list(selected + 1)?;
+            }
+            MouseEventKind::ScrollUp if in_sidebar => {
+                let selected = self.state.decks_list_state.selected().unwrap_or(0);
+                self.select_list(selected.saturating_sub(1))?;
+            }
+            _ => {}
+        }
+        self.sync_card_info()
+    }
+
+    /// Opens the revise popup for the selected card once the next dates are loaded.
+    fn review_selected_card(&mut self) -> Result<()> {
+        if let Some(card) = self.state.selected_card() {
+            self.store.send(Request::LoadNextDates(card.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Rates the card of the revise popup and closes it.
+    fn rate_card(&mut self, rating: usize) -> Result<()> {
+        if let Some(revise_card) = self.state.revise_card.take() {
+            self.store.send(Request::ReviseCard {
+                id: revise_card.id,
+                rating,
+            })?;
+            self.load_cards()?;
+        }
         Ok(())
     }
 
     fn handle_actions(&mut self, tui: &mut Tui) -> Result<()> {
         while let Ok(action) = self.action_rx.try_recv() {
-            if action != Action::Tick && action != Action::Render {
-                debug!("{action:?}");
-            }
-            match action {
-                Action::Tick => {
-                    self.last_tick_key_events.drain(..);
-                }
-                Action::Quit => self.should_quit = true,
-                Action::Suspend => self.should_suspend = true,
-                Action::Resume => self.should_suspend = false,
-                Action::ClearScreen => tui.terminal.clear()?,
-                Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
-                Action::Render => self.render(tui)?,
-                _ => {}
+            self.handle_action(action, tui)?;
+        }
+        Ok(())
+    }
+
+    fn handle_action(&mut self, action: Action, tui: &mut Tui) -> Result<()> {
+        if action != Action::Tick && action != Action::Render {
+            debug!("{action}");
+            errors::record_event(action.to_string());
+        }
+        for component in self.components.iter_mut() {
+            if let Some(action) = component.update(action.clone())? {
+                self.action_tx.send(action)?
+            };
+        }
+        match action {
+            Action::Tick => {
+                self.last_tick_key_events.drain(..);
+                if Utc::now().timestamp() / 60 != self.last_render_minute {
+                    self.needs_render = true;
+                }
+                if let Some((_, shown_at)) = &self.state.toast {
+                    if shown_at.elapsed() >= TOAST_DURATION {
+                        self.state.toast = None;
+                        self.needs_render = true;
+                    }
+                }
+                return Ok(());
             }
-            for component in self.components.iter_mut() {
-                if let Some(action) = component.update(action.clone())? {
-                    self.action_tx.send(action)?
-                };
+            Action::Quit => self.should_quit = true,
+            Action::Suspend => self.should_suspend = true,
+            Action::Resume => self.should_suspend = false,
+            Action::ClearScreen => tui.terminal.clear()?,
+            Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
+            Action::DecksLoaded(decks) => {
+                self.state.decks = decks;
+                // Deck positions shift when decks are added or removed, follow the shown list.
+                match self.state.index_of(self.state.card_list) {
+                    Some(ind) => self.state.decks_list_state.select(Some(ind)),
+                    None => self.select_list(0)?,
+                }
+            }
+            Action::CardsLoaded { generation, cards } if generation == self.cards_generation => {
+                self.state.set_cards(cards);
+                self.state.loading_cards = false;
+                self.sync_card_info()?;
+            }
+            Action::CardInfoLoaded(info) if self.card_info_request == Some(info.card.id) => {
+                let shown = self.state.card_info.as_ref().map(|info| info.card.id);
+                if shown != Some(info.card.id) {
+                    self.state.description_scroll = 0;
+                    self.state.revlog_scroll = 0;
+                }
+                self.state.card_info = Some(*info);
+            }
+            Action::CardRemoved(id) if self.card_info_request == Some(id) => {
+                self.state.card_info = None;
+                self.load_cards()?;
+            }
+            Action::ReviseCardLoaded(details) => {
+                self.state.revise_card = Some(details);
+            }
+            Action::EditorClosed(result) => {
+                self.editing = false;
+                tui.enter()?;
+                tui.terminal.clear()?;
+                self.reload()?;
+                if self.state.recover_draft.is_none() {
+                    self.next_draft();
+                }
+                if self.state.broken_links.is_some() {
+                    self.store.send(Request::CheckLinks)?;
+                }
+                match result {
+                    Ok(EditOutcome::Saved) => {}
+                    Ok(EditOutcome::SavedWithWarnings(warnings)) => {
+                        self.toast(&format!("Saved, {}", warnings.join(", ")))
+                    }
+                    Ok(EditOutcome::Cancelled) => self.toast("Cancelled"),
+                    Ok(EditOutcome::DraftKept) => {
+                        self.toast("Cancelled, your changes were kept as a draft")
+                    }
+                    Err(err) => self.toast(&format!("Edit failed: {err}")),
+                }
             }
+            Action::SourceOpened(result) => {
+                if self.editing {
+                    self.editing = false;
+                    tui.enter()?;
+                    tui.terminal.clear()?;
+                }
+                if let Err(err) = result {
+                    self.toast(&err);
+                }
+            }
+            Action::LinksChecked(result) => match result {
+                Ok(broken) if broken.is_empty() => {
+                    self.state.broken_links = None;
+                    self.toast("No broken links");
+                }
+                Ok(broken) => {
+                    let len = broken.len();
+                    self.state.broken_links = Some(broken);
+                    clamp_selection(&mut self.state.broken_links_state, len);
+                }
+                Err(err) => self.toast(&err),
+            },
+            Action::BulkEditReady(summary) => {
+                self.editing = false;
+                tui.enter()?;
+                tui.terminal.clear()?;
+                self.state.bulk_summary = Some(summary);
+            }
+            Action::DraftsLoaded(drafts) => {
+                self.drafts = drafts;
+                self.next_draft();
+            }
+            _ => {}
         }
+        // Anything but a tick may have changed what is on screen.
+        self.needs_render = true;
         Ok(())
     }
 
@@ -513,6 +1141,9 @@ impl App {
     }
 
     fn render(&mut self, tui: &mut Tui) -> Result<()> {
+        self.needs_render = false;
+        self.last_render = Instant::now();
+        self.last_render_minute = Utc::now().timestamp() / 60;
         tui.draw(|frame| {
             for component in self.components.iter_mut() {
                 if let Err(err) = component.draw(&mut self.state, frame, frame.area()) {
@@ -525,3 +1156,67 @@ impl App {
         Ok(())
     }
 }
+
+/// Keeps the selected row inside a list of `len` rows.
+fn clamp_selection(state: &mut TableState, len: usize) {
+    match state.selected() {
+        Some(row) if row >= len => state.select(Some(len.saturating_sub(1))),
+        None if len > 0 => state.select(Some(0)),
+        _ => {}
+    }
+}
+
+/// Waits until `deadline`, or forever if there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn card(id: ID, title: &str) -> CardSummary {
+        CardSummary {
+            id,
+            deck: "deck".to_string(),
+            title: title.to_string(),
+            next_show_date: Utc::now(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn search(state: &mut AppState, query: &str) {
+        state.cards_table_input = Input::new(query.to_string());
+        state.refresh_search();
+    }
+
+    #[test]
+    fn test_selection_indexes_visible_cards() {
+        let mut state = AppState::default();
+        state.set_cards(vec![card(1, "rust"), card(2, "paper"), card(3, "rustc")]);
+        search(&mut state, "rust");
+        state.cards_table_state.select(Some(1));
+        assert_eq!(state.selected_card().map(|c| c.id), Some(3));
+
+        state.clear_search();
+        assert_eq!(state.selected_card().map(|c| c.id), Some(3));
+        assert_eq!(state.visible_cards().count(), 3);
+    }
+
+    #[test]
+    fn test_search_persists_across_card_lists() {
+        let mut state = AppState::default();
+        search(&mut state, "rust");
+        state.set_cards(vec![card(1, "paper"), card(2, "rust")]);
+        assert_eq!(state.selected_card().map(|c| c.id), Some(2));
+
+        state.set_cards(vec![card(3, "paper")]);
+        assert_eq!(state.visible_cards().count(), 0);
+        assert_eq!(state.selected_card(), None);
+    }
+}
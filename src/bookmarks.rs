@@ -0,0 +1,234 @@
+//! Reads the bookmark export of browsers, the Netscape bookmark file format. Folders are `<H3>`
+//! headings followed by a `<DL>` list of their links and subfolders:
+//!
+//! ```html
+//! <DL><p>
+//!     <DT><H3 ADD_DATE="1700000000">Reading</H3>
+//!     <DL><p>
+//!         <DT><A HREF="https://raft.github.io" ADD_DATE="1700000100">Raft</A>
+//!     </DL><p>
+//! </DL><p>
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use crate::source::Source;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    /// Names of the folders the link is in, outermost first.
+    pub folders: Vec<String>,
+    pub title: String,
+    pub url: String,
+    /// `ADD_DATE` of the link.
+    pub added: Option<DateTime<Utc>>,
+}
+
+/// The links of a bookmark export in the order they appear. Links that are not URLs, such as
+/// bookmarklets and Firefox `place:` queries, are skipped.
+pub fn parse(html: &str) -> Vec<Bookmark> {
+    // Tag and attribute names are matched in upper case, the offsets are the same.
+    let upper = html.to_ascii_uppercase();
+    let mut bookmarks = Vec::new();
+    // One entry per open `<DL>`, the folder name if it belongs to one.
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut folder = None;
+    let mut pos = 0;
+    while let Some(start) = upper[pos..].find('<').map(|ind| pos + ind) {
+        let Some(end) = upper[start..].find('>').map(|ind| start + ind) else {
+            break;
+        };
+        pos = end + 1;
+        let tag = &upper[start + 1..end];
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        match name {
+            "H3" => {
+                let (text, next) = text_until(html, &upper, pos, "</H3");
+                folder = Some(text);
+                pos = next;
+            }
+            "DL" => folders.push(folder.take()),
+            "/DL" => {
+                folders.pop();
+            }
+            "A" => {
+                // A folder without a list of its own is empty.
+                folder = None;
+                let (title, next) = text_until(html, &upper, pos, "</A");
+                pos = next;
+                let tag = &html[start + 1..end];
+                let Some(url) = attribute(tag, "HREF") else {
+                    continue;
+                };
+                if !matches!(Source::parse(&url), Ok(Source::Url(_))) {
+                    continue;
+                }
+                bookmarks.push(Bookmark {
+                    folders: folders.iter().flatten().cloned().collect(),
+                    title,
+                    url,
+                    added: attribute(tag, "ADD_DATE").and_then(|date| timestamp(&date)),
+                });
+            }
+            _ => {}
+        }
+    }
+    bookmarks
+}
+
+/// Whether the bookmark is in `folder`, a folder name or a path such as `Toolbar/Reading`.
+/// Returns the folders from the matching one on, e.g. `Reading/Rust`.
+pub fn in_folder<'a>(bookmark: &'a Bookmark, folder: &str) -> Option<&'a [String]> {
+    let wanted: Vec<_> = folder
+        .split('/')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if wanted.is_empty() {
+        return None;
+    }
+    bookmark
+        .folders
+        .windows(wanted.len())
+        .position(|names| {
+            names
+                .iter()
+                .zip(&wanted)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+        })
+        .map(|ind| &bookmark.folders[ind..])
+}
+
+/// The text from `pos` up to the closing tag, without markup and with entities decoded.
+fn text_until(html: &str, upper: &str, pos: usize, close: &str) -> (String, usize) {
+    let end = upper[pos..].find(close).map_or(html.len(), |ind| pos + ind);
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html[pos..end].chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    (decode(text.trim()), end)
+}
+
+/// The value of a double-quoted attribute of a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let upper = tag.to_ascii_uppercase();
+    let start = upper.find(&format!(" {name}=\""))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(decode(&tag[start..start + len]))
+}
+
+/// Seconds since the epoch. Some browsers write milliseconds or microseconds instead.
+fn timestamp(text: &str) -> Option<DateTime<Utc>> {
+    let value: i64 = text.trim().parse().ok()?;
+    let (secs, micros) = match value {
+        v if v > 100_000_000_000_000 => (v / 1_000_000, v % 1_000_000),
+        v if v > 100_000_000_000 => (v / 1_000, v % 1_000 * 1_000),
+        v => (v, 0),
+    };
+    DateTime::from_timestamp(secs, micros as u32 * 1_000)
+}
+
+/// Decodes the entities browsers write: `&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;` and numeric
+/// ones. Unknown entities are kept as they are.
+fn decode(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><H3>Reading</H3>
+        <DL><p>
+            <DT><A HREF="https://raft.github.io/?a=1&amp;b=2" ADD_DATE="1700000100">Raft &amp; Paxos</A>
+            <DT><H3>Rust</H3>
+            <DL><p>
+                <DT><A href="https://doc.rust-lang.org/nomicon/" add_date="1700000200000">The <b>Rustonomicon</b></A>
+            </DL><p>
+        </DL><p>
+        <DT><A HREF="javascript:void(0)">Bookmarklet</A>
+    </DL><p>
+    <DT><A HREF="https://example.com">Example</A>
+</DL><p>
+"#;
+
+    #[test]
+    fn test_parse() {
+        let bookmarks = parse(EXPORT);
+        assert_eq!(bookmarks.len(), 3);
+        assert_eq!(
+            bookmarks[0],
+            Bookmark {
+                folders: vec!["Bookmarks bar".to_string(), "Reading".to_string()],
+                title: "Raft & Paxos".to_string(),
+                url: "https://raft.github.io/?a=1&b=2".to_string(),
+                added: DateTime::from_timestamp(1_700_000_100, 0),
+            }
+        );
+        assert_eq!(bookmarks[1].title, "The Rustonomicon");
+        assert_eq!(
+            bookmarks[1].added,
+            DateTime::from_timestamp(1_700_000_200, 0)
+        );
+        assert_eq!(bookmarks[2].folders, Vec::<String>::new());
+        assert_eq!(bookmarks[2].added, None);
+
+        assert_eq!(
+            in_folder(&bookmarks[1], "reading"),
+            Some(&["Reading".to_string(), "Rust".to_string()][..])
+        );
+        assert!(in_folder(&bookmarks[1], "Bookmarks bar/Rust").is_none());
+        assert!(in_folder(&bookmarks[2], "Reading").is_none());
+    }
+}
@@ -0,0 +1,254 @@
+//! Finds cards whose source or description links to files that are gone, line ranges whose
+//! content changed, headings that were renamed, and cards that share a source.
+
+use std::{collections::HashMap, fmt, fs};
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::{
+    source::{self, Source},
+    store::ID,
+    usecase::Card,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub card_id: ID,
+    pub title: String,
+    /// The source or description link as written on the card.
+    pub link: String,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    MissingFile,
+    /// The file has fewer lines than the range.
+    MissingLines {
+        len: usize,
+    },
+    /// The file has no heading with the title of the source.
+    MissingHeading,
+    /// The lines of the range changed since the source was set. `to` is where the old content
+    /// is now, if it is still in the file.
+    Moved {
+        to: Option<(usize, usize)>,
+    },
+    /// Other cards with the same source.
+    Duplicate(Vec<ID>),
+}
+
+impl Problem {
+    /// Whether the card points at something that is no longer there, duplicates are not.
+    pub fn is_broken(&self) -> bool {
+        !matches!(self, Problem::Duplicate(_))
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingFile => write!(f, "file not found"),
+            Problem::MissingLines { len } => {
+                write!(f, "lines not found, the file ends at line {len}")
+            }
+            Problem::MissingHeading => write!(f, "heading not found"),
+            Problem::Moved {
+                to: Some((start, end)),
+            } => write!(f, "lines moved to {start}-{end}"),
+            Problem::Moved { to: None } => write!(f, "lines changed"),
+            Problem::Duplicate(ids) => {
+                let ids: Vec<_> = ids.iter().map(ID::to_string).collect();
+                write!(f, "same source as card {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+/// Checks the sources and description links of `cards`.
+pub fn check(cards: &[Card]) -> Vec<BrokenLink> {
+    let mut broken = Vec::new();
+    let mut by_source: HashMap<String, Vec<ID>> = HashMap::new();
+    for card in cards {
+        let report = |link: &str, problem| BrokenLink {
+            card_id: card.id,
+            title: card.title.clone(),
+            link: link.to_string(),
+            problem,
+        };
+        if let Ok(source) = Source::parse(&card.source) {
+            if let Some(problem) = check_source(&source, &card.source_hash) {
+                broken.push(report(&card.source, problem));
+            }
+            by_source
+                .entry(source.to_string())
+                .or_default()
+                .push(card.id);
+        }
+        for link in desc_links(&card.desc) {
+            let Ok(source) = Source::parse(&link) else {
+                continue;
+            };
+            if let Some(problem) = check_source(&source, "") {
+                broken.push(report(&link, problem));
+            }
+        }
+    }
+
+    for card in cards {
+        let Ok(source) = Source::parse(&card.source) else {
+            continue;
+        };
+        let ids = &by_source[&source.to_string()];
+        if ids.len() > 1 {
+            let others = ids.iter().copied().filter(|&id| id != card.id).collect();
+            broken.push(BrokenLink {
+                card_id: card.id,
+                title: card.title.clone(),
+                link: card.source.clone(),
+                problem: Problem::Duplicate(others),
+            });
+        }
+    }
+    broken.sort_by_key(|link| link.card_id);
+    broken
+}
+
+/// Checks one file link, `hash` is the content of its lines when it was set, if known.
+fn check_source(source: &Source, hash: &str) -> Option<Problem> {
+    let Source::File {
+        path,
+        lines,
+        heading,
+    } = source
+    else {
+        return None;
+    };
+    let Ok(text) = fs::read_to_string(path) else {
+        // Binary files such as PDFs still exist.
+        return (!path.exists()).then_some(Problem::MissingFile);
+    };
+    if let Some(heading) = heading {
+        return source::find_section(&text, heading)
+            .is_none()
+            .then_some(Problem::MissingHeading);
+    }
+    let (start, end) = (*lines)?;
+    let file_lines: Vec<_> = text.lines().collect();
+    if end > file_lines.len() {
+        return Some(Problem::MissingLines {
+            len: file_lines.len(),
+        });
+    }
+    if hash.is_empty() || source::hash_lines(&file_lines[start - 1..end]) == hash {
+        return None;
+    }
+    // Look for the old content elsewhere, e.g. after lines were added above it.
+    let len = end - start + 1;
+    let to = file_lines
+        .windows(len)
+        .position(|window| source::hash_lines(window) == hash)
+        .map(|ind| (ind + 1, ind + len));
+    Some(Problem::Moved { to })
+}
+
+/// Local file links in a Markdown description: link targets without a scheme, `file://` links
+/// and bare `file://` URLs in the text.
+fn desc_links(desc: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    for event in Parser::new(desc) {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                let dest = dest_url.split('#').next().unwrap_or_default();
+                if !dest.is_empty() && (dest.starts_with("file://") || !has_scheme(dest)) {
+                    links.push(dest.to_string());
+                }
+            }
+            Event::Text(text) => links.extend(
+                text.split_whitespace()
+                    .filter(|word| word.starts_with("file://"))
+                    .map(str::to_string),
+            ),
+            _ => {}
+        }
+    }
+    links
+}
+
+/// Whether `link` starts with a URL scheme such as `https:` or `mailto:`. One letter is a
+/// Windows drive, and `notes.md:12` is a file with a line.
+fn has_scheme(link: &str) -> bool {
+    link.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-".contains(c))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: ID, source: &str, source_hash: &str, desc: &str) -> Card {
+        Card {
+            id,
+            deck_id: 1,
+            deck: "rust".to_string(),
+            title: format!("card {id}"),
+            desc: desc.to_string(),
+            tags: Vec::new(),
+            next_show_date: Default::default(),
+            created_at: Default::default(),
+            suspended: false,
+            fields: Vec::new(),
+            source: source.to_string(),
+            source_hash: source_hash.to_string(),
+            note_id: String::new(),
+            uuid: String::new(),
+            modified_at: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_desc_links() {
+        let desc = "See [notes](notes/raft.md#log), [site](https://raft.github.io), \
+                    [code](src/main.rs:12) and file:///tmp/a.md\n";
+        assert_eq!(
+            desc_links(desc),
+            vec!["notes/raft.md", "src/main.rs:12", "file:///tmp/a.md"]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        let hash = source::hash_lines(&["b", "c"]);
+        // Two lines were added above the range.
+        let source = format!("{}:2-3", path.display());
+        fs::write(&path, "x\ny\na\nb\nc\n").unwrap();
+
+        let cards = [
+            card(1, &source, &hash, ""),
+            card(2, &source, "", "[gone](/nonexistent/file.md)"),
+            card(3, "https://raft.github.io", "", ""),
+            card(4, &format!("{}#Log", path.display()), "", ""),
+        ];
+        let problems: Vec<_> = check(&cards)
+            .into_iter()
+            .map(|broken| (broken.card_id, broken.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (1, Problem::Moved { to: Some((4, 5)) }),
+                (1, Problem::Duplicate(vec![2])),
+                (2, Problem::MissingFile),
+                (2, Problem::Duplicate(vec![1])),
+                (4, Problem::MissingHeading),
+            ]
+        );
+    }
+}
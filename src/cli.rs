@@ -0,0 +1,199 @@
+//! Commands that run without the TUI.
+
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use colored::*;
+
+use crate::{
+    check::BrokenLink,
+    store::{SqliteStore, ID},
+    sync,
+    usecase::{SyncReport, Usecase},
+    vault,
+};
+
+/// How often watch mode looks for changed notes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `check-links`: prints the problems, then offers to suspend or edit each card that has one.
+/// Exits with status 1 if there are problems and stdin is not a terminal, for scripts.
+pub fn check_links(usecase: &Usecase<SqliteStore>) -> Result<()> {
+    let broken = usecase.check_links()?;
+    if broken.is_empty() {
+        println!("No broken links");
+        return Ok(());
+    }
+    for link in &broken {
+        print_broken(link);
+    }
+    println!("\n{} problems", broken.len());
+    if !io::stdin().is_terminal() {
+        std::process::exit(1);
+    }
+
+    let mut ids: Vec<ID> = broken.iter().map(|link| link.card_id).collect();
+    ids.dedup();
+    let mut lines = io::stdin().lock().lines();
+    for id in ids {
+        let title = &broken.iter().find(|link| link.card_id == id).unwrap().title;
+        print!(
+            "{} {title}: [s]uspend, [e]dit, [n]ext, [q]uit? ",
+            id.to_string().yellow()
+        );
+        io::stdout().flush()?;
+        let Some(answer) = lines.next().transpose()? else {
+            break;
+        };
+        match answer.trim() {
+            "s" => usecase.suspend_card(id),
+            "e" => {
+                usecase.edit_card(id)?;
+            }
+            "q" => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `vault sync`: makes cards of the notes in `dir`, then again whenever a note changes if
+/// `watch` is set.
+pub fn vault_sync(
+    usecase: &Usecase<SqliteStore>,
+    dir: &Path,
+    tag: Option<&str>,
+    watch: bool,
+) -> Result<()> {
+    let sync = usecase.sync_vault(dir, tag)?;
+    print_sync(&sync, "note deleted");
+    if !watch {
+        if sync.is_empty() {
+            println!("Everything is up to date");
+        }
+        return Ok(());
+    }
+
+    println!(
+        "Watching {} for changes, press Ctrl-C to stop",
+        dir.display()
+    );
+    // Polling is plenty for notes, and the ids written back are picked up as one more change.
+    let mut last = vault::fingerprint(dir);
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let current = vault::fingerprint(dir);
+        if current != last {
+            print_sync(&usecase.sync_vault(dir, tag)?, "note deleted");
+            last = vault::fingerprint(dir);
+        }
+    }
+}
+
+/// `import headings`: makes a card of every heading of `level` in the Markdown file.
+pub fn import_headings(
+    usecase: &Usecase<SqliteStore>,
+    file: &Path,
+    level: usize,
+    deck: Option<&str>,
+) -> Result<()> {
+    let report = usecase.import_headings(file, level, deck)?;
+    print_sync(&report, "heading gone");
+    if report.is_empty() {
+        println!("Everything is up to date");
+    }
+    Ok(())
+}
+
+/// `import bookmarks`: makes a card of every link in `folders` of a browser's bookmark export.
+pub fn import_bookmarks(
+    usecase: &Usecase<SqliteStore>,
+    file: &Path,
+    folders: &[String],
+) -> Result<()> {
+    let report = usecase.import_bookmarks(file, folders)?;
+    print_sync(&report, "link removed");
+    if report.is_empty() {
+        println!("No new links");
+    }
+    Ok(())
+}
+
+/// `sync`: merges the cards and reviews with the other machines syncing through `target`, a
+/// folder or the URL of a sync server.
+pub fn sync(usecase: &Usecase<SqliteStore>, target: &str, token: Option<&str>) -> Result<()> {
+    let report = if target.starts_with("http://") || target.starts_with("https://") {
+        let Some(token) = token else {
+            return Err(eyre!(
+                "pass --token or set REVISE_SYNC_TOKEN, `evise sync-server add-user` makes one"
+            ));
+        };
+        usecase.sync_with_server(target, token)?
+    } else {
+        let dir = Path::new(target);
+        if !dir.is_dir() {
+            return Err(eyre!("{} is not a folder", dir.display()));
+        }
+        usecase.sync(dir)?
+    };
+    print_merge(&report);
+    Ok(())
+}
+
+/// Prints what a sync with other machines did.
+pub fn print_merge(report: &sync::Report) {
+    for title in &report.added {
+        println!("{} {title}", "+".green());
+    }
+    for title in &report.updated {
+        println!("{} {title}", "~".yellow());
+    }
+    for title in &report.deleted {
+        println!("{} {title}", "-".red());
+    }
+    for conflict in &report.conflicts {
+        println!("{} {conflict}", "conflict:".yellow().bold());
+    }
+    for warning in &report.warnings {
+        println!("{} {warning}", "!".red());
+    }
+    println!(
+        "{} reviews received, {} changes sent",
+        report.reviews, report.sent
+    );
+}
+
+/// Prints what a sync or import did, `gone` says why cards were suspended.
+fn print_sync(sync: &SyncReport, gone: &str) {
+    for title in &sync.added {
+        println!("{} {title}", "+".green());
+    }
+    for title in &sync.updated {
+        println!("{} {title}", "~".yellow());
+    }
+    for title in &sync.suspended {
+        println!("{} {title} ({gone}, card suspended)", "-".red());
+    }
+    for warning in &sync.warnings {
+        println!("{} {warning}", "!".red());
+    }
+}
+
+fn print_broken(link: &BrokenLink) {
+    let problem = if link.problem.is_broken() {
+        link.problem.to_string().red()
+    } else {
+        link.problem.to_string().yellow()
+    };
+    println!(
+        "{} {}\n    {}: {problem}",
+        link.card_id.to_string().yellow(),
+        link.title.bold(),
+        link.link.dimmed(),
+    );
+}
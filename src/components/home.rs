@@ -9,9 +9,12 @@ use tui_input::Input;
 use super::Component;
 use crate::{
     action::Action,
-    app::{AppState, CardInfo, Focused},
+    app::{AppState, CardInfo, Focused, ScreenAreas},
+    check::BrokenLink,
     config::Config,
-    usecase::CardSummary,
+    form::{CardForm, Field},
+    highlight::Highlighter,
+    markdown,
     utils::date_to_relative_string,
 };
 
@@ -26,7 +29,7 @@ impl Home {
         Self {
             command_tx: None,
             config: Config::default(),
-            components: vec![Box::new(ReviseTable {})],
+            components: vec![Box::new(ReviseTable { highlighter: None })],
         }
     }
 }
@@ -40,6 +43,9 @@ impl Component for Home {
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        for component in self.components.iter_mut() {
+            component.register_config_handler(config.clone())?;
+        }
         self.config = config;
         Ok(())
     }
@@ -58,6 +64,8 @@ impl Component for Home {
     }
 
     fn draw(&mut self, app_state: &mut AppState, frame: &mut Frame, area: Rect) -> Result<()> {
+        app_state.areas = ScreenAreas::default();
+
         let a1: [Rect; 3] = Layout::vertical(vec![
             Constraint::Length(1),
             Constraint::Min(0),
@@ -65,116 +73,298 @@ impl Component for Home {
         ])
         .areas(area);
 
-        let a2: [Rect; 2] =
-            Layout::horizontal(vec![Constraint::Length(30), Constraint::Min(0)]).areas(a1[1]);
-
         frame.render_widget(
             Paragraph::new(" REVISE 0.0.1 ".fg(Color::Black).bg(Color::Yellow).bold())
                 .alignment(Alignment::Center),
             a1[0],
         );
 
-        self.components[0].draw(app_state, frame, a2[1])?;
+        let zoomed = app_state.zoom_description && app_state.card_info.is_some();
+        match sidebar_width(a1[1].width).filter(|_| app_state.show_sidebar && !zoomed) {
+            Some(width) => {
+                let a2: [Rect; 2] =
+                    Layout::horizontal(vec![Constraint::Length(width), Constraint::Min(0)])
+                        .areas(a1[1]);
+                self.components[0].draw(app_state, frame, a2[1])?;
 
-        let mut sidebar = DeckSidebar::default();
-        sidebar.draw(app_state, frame, a2[0])?;
+                let mut sidebar = DeckSidebar::default();
+                sidebar.draw(app_state, frame, a2[0])?;
+            }
+            None => self.components[0].draw(app_state, frame, a1[1])?,
+        }
 
-        let mut kb = Keybindings::default();
-        kb.draw(app_state, frame, a1[2])?;
+        if let Some(form) = app_state.card_form.as_ref() {
+            render_card_form(form, frame, a1[1]);
+        }
+
+        let mut footer = a1[2];
+        if let Some((message, _)) = &app_state.toast {
+            let toast = Line::from(format!(" {message} ").fg(Color::Black).bg(Color::Yellow));
+            let [toast_area, rest] =
+                Layout::horizontal([Constraint::Length(toast.width() as u16), Constraint::Min(0)])
+                    .areas(footer);
+            frame.render_widget(toast, toast_area);
+            footer = rest;
+        }
+
+        if let Some(input) = app_state.quick_capture.as_ref() {
+            render_quick_capture(input, frame, footer);
+        } else {
+            let mut kb = Keybindings::default();
+            kb.draw(app_state, frame, footer)?;
+        }
 
         Ok(())
     }
 }
 
-struct ReviseTable {}
-
-impl Component for ReviseTable {
-    fn draw(&mut self, app_state: &mut AppState, frame: &mut Frame, area: Rect) -> Result<()> {
-        let has_card_info = app_state.card_info.is_some();
+/// Width of the labels in front of the card form's fields.
+const FORM_LABEL_WIDTH: u16 = 7;
+
+fn render_card_form(form: &CardForm, frame: &mut Frame, area: Rect) {
+    let title = if form.id.is_some() {
+        "|Edit card|"
+    } else {
+        "|Add card|"
+    };
+    let block = Block::bordered()
+        .title(title)
+        .padding(Padding::horizontal(1))
+        .border_style(Style::new().yellow());
+    let area = center(area, Constraint::Percentage(80), Constraint::Percentage(80));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let [title_area, deck_area, tags_area, source_area, body_label_area, body_area, status_area] =
+        Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
 
-        let a1: [Rect; 2] =
-            Layout::vertical(vec![Constraint::Fill(2), Constraint::Fill(1)]).areas(area);
-        let a2: [Rect; 2] =
-            Layout::horizontal(vec![Constraint::Fill(1), Constraint::Fill(1)]).areas(a1[1]);
+    let label_style = |field: Field| {
+        if form.focused == field {
+            Style::new().yellow().bold()
+        } else {
+            Style::new().fg(OFF_WHITE)
+        }
+    };
+    let fields = [
+        (Field::Title, "Title", &form.title, title_area),
+        (Field::Deck, "Deck", &form.deck, deck_area),
+        (Field::Tags, "Tags", &form.tags, tags_area),
+        (Field::Source, "Source", &form.source, source_area),
+    ];
+    for (field, label, input, area) in fields {
+        let [label_area, input_area] =
+            Layout::horizontal([Constraint::Length(FORM_LABEL_WIDTH), Constraint::Min(0)])
+                .areas(area);
+        frame.render_widget(Span::styled(label, label_style(field)), label_area);
+
+        let scroll = input.visual_scroll(input_area.width.saturating_sub(1) as usize);
+        let mut line = Line::from(input.value().chars().skip(scroll).collect::<String>());
+        if field == Field::Deck && form.focused == Field::Deck {
+            if let Some(rest) = form.deck_completion() {
+                line.push_span(rest.fg(OFF_WHITE));
+            }
+        }
+        if field == Field::Tags && input.value().is_empty() && form.focused != Field::Tags {
+            line = Line::from("comma separated".fg(OFF_WHITE));
+        }
+        if field == Field::Source && input.value().is_empty() && form.focused != Field::Source {
+            line = Line::from("file:line, https://… or app://…".fg(OFF_WHITE));
+        }
+        frame.render_widget(line, input_area);
+        if form.focused == field {
+            let x = input_area.x + (input.visual_cursor() - scroll) as u16;
+            frame.set_cursor_position(Position::new(x.min(input_area.right()), input_area.y));
+        }
+    }
 
-        let cards_table_area = if has_card_info { a1[0] } else { area };
+    frame.render_widget(
+        Line::from(vec![
+            Span::styled("Body ", label_style(Field::Body)),
+            "─"
+                .repeat(body_label_area.width.saturating_sub(5) as usize)
+                .fg(OFF_WHITE),
+        ]),
+        body_label_area,
+    );
 
-        render_card_table(app_state, frame, cards_table_area)?;
+    // Scrolls just enough to keep the cursor visible.
+    let body = &form.body;
+    let top = body
+        .row
+        .saturating_sub(body_area.height.saturating_sub(1) as usize);
+    let left = body
+        .col
+        .saturating_sub(body_area.width.saturating_sub(1) as usize);
+    let lines: Vec<Line> = body
+        .lines()
+        .iter()
+        .skip(top)
+        .map(|line| Line::from(line.chars().skip(left).collect::<String>()))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), body_area);
+    if form.focused == Field::Body && !body_area.is_empty() {
+        frame.set_cursor_position(Position::new(
+            body_area.x + (body.col - left) as u16,
+            body_area.y + (body.row - top) as u16,
+        ));
+    }
 
-        if !has_card_info {
-            return Ok(());
+    let status = match &form.error {
+        Some(error) => Line::from(error.as_str().red()),
+        None => {
+            Line::from("Tab: next field, complete deck  Ctrl-s: save  Esc: cancel".fg(OFF_WHITE))
         }
+    };
+    frame.render_widget(status, status_area);
+}
 
-        let CardInfo { card, reviews } = app_state.card_info.as_ref().unwrap();
+fn render_quick_capture(input: &Input, frame: &mut Frame, area: Rect) {
+    let prompt = " new card: ";
+    let hint = "  #tag @deck, Enter: add, Esc: cancel";
+    let width = area.width.saturating_sub((prompt.len() + 1) as u16) as usize;
+    let scroll = input.visual_scroll(width);
+    let mut line = Line::from(vec![
+        prompt.yellow(),
+        Span::from(input.value().chars().skip(scroll).collect::<String>()),
+    ]);
+    if input.value().is_empty() {
+        line.push_span(hint.fg(OFF_WHITE));
+    }
+    frame.render_widget(line, area);
+    let x = area.x + (prompt.len() + input.visual_cursor() - scroll) as u16;
+    frame.set_cursor_position(Position::new(x.min(area.right()), area.y));
+}
 
-        let block = Block::bordered()
-            .title("|Card Info|")
-            .padding(Padding::uniform(1))
-            .border_style(Style::default().fg(OFF_WHITE));
-        let card_info_area = block.inner(a2[0]);
-        frame.render_widget(block, a2[0]);
+/// Below this width the sidebar is hidden.
+const SIDEBAR_MIN_WIDTH: u16 = 80;
+/// From this width on the sidebar gets its full width.
+const WIDE_WIDTH: u16 = 120;
+/// Below this width the card info and description panes are stacked instead of side by side.
+const DETAILS_SIDE_BY_SIDE_MIN_WIDTH: u16 = 90;
+/// Below this height only the description is shown under the card table.
+const DETAILS_MIN_HEIGHT: u16 = 24;
+
+fn sidebar_width(width: u16) -> Option<u16> {
+    match width {
+        w if w >= WIDE_WIDTH => Some(30),
+        w if w >= SIDEBAR_MIN_WIDTH => Some(22),
+        _ => None,
+    }
+}
 
-        let [upper_area, lower_area] =
-            Layout::vertical(vec![Constraint::Length(4), Constraint::Fill(1)])
-                .areas(card_info_area);
+/// Where the card table and the detail panes go, depending on the available space.
+#[derive(Default)]
+struct Panes {
+    table: Rect,
+    info: Option<Rect>,
+    description: Option<Rect>,
+    /// Excerpt of the card's source file, only for cards that have one.
+    preview: Option<Rect>,
+}
 
-        let info_table = Table::new(
-            vec![
-                Row::new(vec![
-                    Cell::from("Name").style(Style::default().fg(Color::Cyan)),
-                    Cell::from(card.title.clone()),
-                ]),
-                Row::new(vec![
-                    Cell::from("Due Date").style(Style::default().fg(Color::Cyan)),
-                    Cell::from(date_to_relative_string(card.next_show_date)),
-                ]),
-                Row::new(vec![
-                    Cell::from("Created At").style(Style::default().fg(Color::Cyan)),
-                    Cell::from(date_to_relative_string(card.created_at)),
-                ]),
-            ],
-            vec![Constraint::Length(15), Constraint::Length(30)],
-        );
+impl Panes {
+    fn new(area: Rect, app_state: &AppState) -> Self {
+        let Some(info) = app_state.card_info.as_ref() else {
+            return Panes {
+                table: area,
+                ..Default::default()
+            };
+        };
+        let has_preview = info.excerpt.is_some();
+        if app_state.zoom_description {
+            return Panes {
+                description: Some(area),
+                ..Default::default()
+            };
+        }
+        if !app_state.show_details {
+            return Panes {
+                table: area,
+                ..Default::default()
+            };
+        }
 
-        frame.render_widget(info_table, upper_area);
+        if area.height < DETAILS_MIN_HEIGHT {
+            let [table, description] =
+                Layout::vertical(vec![Constraint::Fill(1), Constraint::Fill(1)]).areas(area);
+            return Panes {
+                table,
+                description: Some(description),
+                ..Default::default()
+            };
+        }
 
-        let rows = reviews.iter().enumerate().map(|(ind, r)| {
-            Row::new(vec![
-                Cell::from((ind + 1).to_string()),
-                Cell::from(date_to_relative_string(r.review_time)),
-                Cell::from(r.interval.to_string()),
-                Cell::from(format!("{:.2}", r.stability)),
-                Cell::from(format!("{:.2}", r.difficulty)),
-            ])
-        });
+        if area.width < DETAILS_SIDE_BY_SIDE_MIN_WIDTH {
+            let mut constraints = vec![
+                Constraint::Fill(2),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ];
+            if has_preview {
+                constraints.push(Constraint::Fill(1));
+            }
+            let areas = Layout::vertical(constraints).split(area);
+            return Panes {
+                table: areas[0],
+                info: Some(areas[1]),
+                description: Some(areas[2]),
+                preview: areas.get(3).copied(),
+            };
+        }
 
-        let revlog_table = Table::new(
-            rows,
-            vec![
-                Constraint::Length(3),
-                Constraint::Length(25),
-                Constraint::Length(8),
-                Constraint::Length(10),
-                Constraint::Length(10),
-            ],
-        )
-        .header(
-            Row::new(vec![
-                Cell::from("No."),
-                Cell::from("Date"),
-                Cell::from("Interval"),
-                Cell::from("Stability"),
-                Cell::from("Difficulty"),
-            ])
-            .style(Style::new().fg(Color::Cyan)),
-        )
-        .block(
-            Block::new()
-                .title("Previous Revisions:")
-                .style(Style::new().fg(Color::White)),
-        );
+        let [table, details] =
+            Layout::vertical(vec![Constraint::Fill(2), Constraint::Fill(1)]).areas(area);
+        let columns = if has_preview { 3 } else { 2 };
+        let areas = Layout::horizontal(vec![Constraint::Fill(1); columns]).split(details);
+        Panes {
+            table,
+            info: Some(areas[0]),
+            description: Some(areas[1]),
+            preview: areas.get(2).copied(),
+        }
+    }
+}
+
+struct ReviseTable {
+    highlighter: Option<Highlighter>,
+}
+
+impl Component for ReviseTable {
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.highlighter = Some(Highlighter::new(&config.config.code_theme));
+        Ok(())
+    }
 
-        frame.render_widget(revlog_table, lower_area);
+    fn draw(&mut self, app_state: &mut AppState, frame: &mut Frame, area: Rect) -> Result<()> {
+        let panes = Panes::new(area, app_state);
+
+        if !panes.table.is_empty() {
+            render_card_table(app_state, frame, panes.table)?;
+        }
+        if let Some(info_area) = panes.info {
+            render_card_info(app_state, frame, info_area);
+        }
+        if let Some(description_area) = panes.description {
+            render_description(
+                app_state,
+                frame,
+                description_area,
+                self.highlighter.as_ref(),
+            );
+        }
+        if let Some(preview_area) = panes.preview {
+            render_preview(app_state, frame, preview_area, self.highlighter.as_ref());
+        }
 
         // Render revise card prompt
         if let Some(revise_card) = app_state.revise_card.as_ref() {
@@ -192,19 +382,22 @@ impl Component for ReviseTable {
                     ));
                 });
 
-            text.push_line(format!("[q] skip"));
-
-            let revise_text = Paragraph::new(text)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(format!("|Revise card|"))
-                        .padding(Padding::horizontal(2))
-                        .style(Style::default().fg(Color::Yellow)),
-                )
-                .alignment(Alignment::Left);
+            text.push_line("[Esc] skip".to_string());
 
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title("|Revise card|".to_string())
+                .padding(Padding::horizontal(2))
+                .style(Style::default().fg(Color::Yellow));
             let area = center(area, Constraint::Length(50), Constraint::Length(9));
+            let inner = block.inner(area);
+            // The text starts with an empty line.
+            app_state.areas.rating_buttons = (1..=revise_card.next_dates.len() as u16)
+                .map(|line| Rect::new(inner.x, inner.y + line, inner.width, 1))
+                .filter(|button| inner.contains(button.as_position()))
+                .collect();
+
+            let revise_text = Paragraph::new(text).block(block).alignment(Alignment::Left);
 
             frame.render_widget(Clear, area);
             frame.render_widget(revise_text, area);
@@ -256,24 +449,387 @@ impl Component for ReviseTable {
             frame.render_widget(confirm_text, area);
         }
 
-        let card_desc = Paragraph::new(card.desc.to_string()).style(Style::new().fg(Color::White));
-        frame.render_widget(
-            card_desc.block(
-                Block::bordered()
-                    .title("|Description|")
-                    .padding(Padding::uniform(1))
-                    .style(Style::default().fg(OFF_WHITE)),
-            ),
-            a2[1],
-        );
+        if let Some(summary) = app_state.bulk_summary.as_ref() {
+            let (totals, changes) = summary.split_first().unwrap();
+            let height = (changes.len() as u16 + 6).min(area.height);
+            // Lines that don't fit are dropped, the totals still count them.
+            let mut lines: Vec<Line> = vec![Line::from(totals.clone().bold()), Line::from("")];
+            lines.extend(changes.iter().map(|change| {
+                let style = match change.chars().next() {
+                    Some('+') => Style::new().green(),
+                    Some('-') => Style::new().red(),
+                    _ => Style::new(),
+                };
+                Line::styled(change.clone(), style)
+            }));
+            lines.truncate(height.saturating_sub(4) as usize);
+            lines.push(Line::from(""));
+            lines.push(Line::from("[y] Apply  [e] Edit again  [n] Cancel".yellow()));
+
+            let summary_text = Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("|Bulk Edit|")
+                    .padding(Padding::horizontal(2))
+                    .border_style(Style::default().fg(Color::Yellow)),
+            );
+            let area = center(area, Constraint::Percentage(70), Constraint::Length(height));
+
+            frame.render_widget(Clear, area);
+            frame.render_widget(summary_text, area);
+        }
+
+        if let Some(broken) = app_state.broken_links.as_ref() {
+            render_broken_links(broken, &mut app_state.broken_links_state, frame, area);
+        }
+
+        if let Some(draft) = app_state.recover_draft.as_ref() {
+            let title = if draft.title.is_empty() {
+                "Untitled card".to_string()
+            } else {
+                draft.title.clone()
+            };
+            let saved_at = format!("from {}", draft.saved_at.format("%Y-%m-%d %H:%M"));
+            let text = Text::from(vec![
+                Line::from("An edit was not saved last time:"),
+                Line::from(title.bold()),
+                Line::from(saved_at.fg(OFF_WHITE)),
+                Line::from(""),
+                Line::from("[y] Recover  [n] Discard  [Esc] Later".yellow()),
+            ]);
+
+            let recover_text = Paragraph::new(text)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("|Unsaved Draft|")
+                        .padding(Padding::horizontal(2))
+                        .border_style(Style::default().fg(Color::Yellow)),
+                )
+                .alignment(Alignment::Center);
+
+            let area = center(area, Constraint::Length(50), Constraint::Length(7));
+
+            frame.render_widget(Clear, area);
+            frame.render_widget(recover_text, area);
+        }
 
         Ok(())
     }
 }
 
+fn render_card_info(app_state: &mut AppState, frame: &mut Frame, area: Rect) {
+    let Some(CardInfo { card, reviews, .. }) = app_state.card_info.as_ref() else {
+        return;
+    };
+
+    let block = Block::bordered()
+        .title("|Card Info|")
+        .padding(Padding::horizontal(1))
+        .border_style(Style::default().fg(OFF_WHITE));
+    let card_info_area = block.inner(area);
+    frame.render_widget(block, area);
+
+    // Long titles wrap instead of being cut off.
+    let value_width = card_info_area.width.saturating_sub(LABEL_WIDTH + 1).max(1);
+    let title = wrap_line(&card.title, value_width as usize);
+    let title_height = title.lines.len().max(1) as u16;
+
+    let [upper_area, lower_area] = Layout::vertical(vec![
+        Constraint::Length(
+            title_height + 4 + u16::from(!card.source.is_empty()) + card.fields.len() as u16,
+        ),
+        Constraint::Fill(1),
+    ])
+    .areas(card_info_area);
+
+    let mut rows = vec![
+        Row::new(vec![
+            Cell::from("Name").style(Style::default().fg(Color::Cyan)),
+            Cell::from(title),
+        ])
+        .height(title_height),
+        Row::new(vec![
+            Cell::from("Due Date").style(Style::default().fg(Color::Cyan)),
+            Cell::from(date_to_relative_string(card.next_show_date)),
+        ]),
+        Row::new(vec![
+            Cell::from("Created At").style(Style::default().fg(Color::Cyan)),
+            Cell::from(date_to_relative_string(card.created_at)),
+        ]),
+        Row::new(vec![
+            Cell::from("Tags").style(Style::default().fg(Color::Cyan)),
+            Cell::from(card.tags.join(", ")),
+        ]),
+    ];
+    if !card.source.is_empty() {
+        rows.push(Row::new(vec![
+            Cell::from("Source").style(Style::default().fg(Color::Cyan)),
+            Cell::from(card.source.as_str()),
+        ]));
+    }
+    // Custom fields from templates follow the built-in ones.
+    rows.extend(card.fields.iter().map(|(key, value)| {
+        Row::new(vec![
+            Cell::from(key.as_str()).style(Style::default().fg(Color::Cyan)),
+            Cell::from(value.as_str()),
+        ])
+    }));
+    let info_table = Table::new(rows, [Constraint::Length(LABEL_WIDTH), Constraint::Fill(1)]);
+
+    frame.render_widget(info_table, upper_area);
+
+    // Header and title take two lines.
+    let visible_reviews = lower_area.height.saturating_sub(2) as usize;
+    let max_scroll = reviews.len().saturating_sub(visible_reviews);
+    app_state.revlog_scroll = app_state.revlog_scroll.min(max_scroll);
+    app_state.areas.revlog = lower_area;
+
+    let rows = reviews
+        .iter()
+        .enumerate()
+        .skip(app_state.revlog_scroll)
+        .map(|(ind, r)| {
+            Row::new(vec![
+                Cell::from((ind + 1).to_string()),
+                Cell::from(date_to_relative_string(r.review_time)),
+                Cell::from(r.interval.to_string()),
+                Cell::from(format!("{:.2}", r.stability)),
+                Cell::from(format!("{:.2}", r.difficulty)),
+            ])
+        });
+
+    let title = if max_scroll > 0 {
+        format!(
+            "Previous Revisions ({}-{} of {}):",
+            app_state.revlog_scroll + 1,
+            (app_state.revlog_scroll + visible_reviews).min(reviews.len()),
+            reviews.len()
+        )
+    } else {
+        "Previous Revisions:".to_string()
+    };
+
+    // Narrow panes get abbreviated headers so the date still fits.
+    let header = if lower_area.width < 60 {
+        ["No.", "Date", "Ivl", "Stab", "Diff"]
+    } else {
+        ["No.", "Date", "Interval", "Stability", "Difficulty"]
+    };
+    let widths = header.map(|name| match name {
+        "Date" => Constraint::Fill(1),
+        name => Constraint::Length(name.len() as u16),
+    });
+
+    let revlog_table = Table::new(rows, widths)
+        .header(
+            header
+                .into_iter()
+                .map(Cell::from)
+                .collect::<Row>()
+                .style(Style::new().fg(Color::Cyan)),
+        )
+        .block(
+            Block::new()
+                .title(title)
+                .style(Style::new().fg(Color::White)),
+        );
+
+    frame.render_widget(revlog_table, lower_area);
+}
+
+/// Width of the labels in the card info table.
+const LABEL_WIDTH: u16 = 11;
+
+fn render_description(
+    app_state: &mut AppState,
+    frame: &mut Frame,
+    area: Rect,
+    highlighter: Option<&Highlighter>,
+) {
+    let Some(CardInfo { card, .. }) = app_state.card_info.as_ref() else {
+        return;
+    };
+
+    let mut block = Block::bordered()
+        .title("|Description|")
+        .padding(Padding::uniform(1))
+        .style(Style::default().fg(OFF_WHITE));
+    if app_state.zoom_description {
+        block = block.title_bottom("|z/Esc: back|");
+    }
+    let inner = block.inner(area);
+    app_state.areas.description = area;
+
+    let rendered = match app_state.rendered_description.take() {
+        Some((desc, text)) if desc == card.desc => (desc, text),
+        _ => (card.desc.clone(), markdown::render(&card.desc, highlighter)),
+    };
+    let card_desc = Paragraph::new(rendered.1.clone())
+        .style(Style::new().fg(Color::White))
+        .wrap(Wrap { trim: false });
+
+    // Stop scrolling once the last line is at the bottom.
+    let lines = card_desc.line_count(inner.width) as u16;
+    let max_scroll = lines.saturating_sub(inner.height);
+    app_state.description_scroll = app_state.description_scroll.min(max_scroll);
+    app_state.rendered_description = Some(rendered);
+    if max_scroll > 0 {
+        block = block.title_top(
+            Line::from(format!(
+                "|{}/{}|",
+                app_state.description_scroll + inner.height.min(lines),
+                lines
+            ))
+            .right_aligned(),
+        );
+    }
+
+    frame.render_widget(
+        card_desc
+            .scroll((app_state.description_scroll, 0))
+            .block(block),
+        area,
+    );
+}
+
+/// Popup of the problems found by the link check, one row per problem.
+fn render_broken_links(
+    broken: &[BrokenLink],
+    table_state: &mut TableState,
+    frame: &mut Frame,
+    area: Rect,
+) {
+    let rows = broken.iter().map(|link| {
+        let style = if link.problem.is_broken() {
+            Style::new().red()
+        } else {
+            Style::new().yellow()
+        };
+        Row::new(vec![
+            Cell::from(link.card_id.to_string()),
+            Cell::from(link.title.clone()),
+            Cell::from(link.link.clone()).fg(OFF_WHITE),
+            Cell::from(link.problem.to_string()).style(style),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(5),
+            Constraint::Fill(2),
+            Constraint::Fill(2),
+            Constraint::Fill(2),
+        ],
+    )
+    .header(
+        ["Card", "Title", "Link", "Problem"]
+            .into_iter()
+            .map(Cell::from)
+            .collect::<Row>()
+            .style(Style::new().fg(Color::Cyan)),
+    )
+    .row_highlight_style(Style::new().reversed())
+    .block(
+        Block::bordered()
+            .title(format!("|Broken Links ({})|", broken.len()))
+            .title_bottom("|s: suspend  e: edit  Esc: close|")
+            .padding(Padding::horizontal(1))
+            .border_style(Style::new().red()),
+    );
+
+    let height = (broken.len() as u16 + 3).min(area.height);
+    let area = center(area, Constraint::Percentage(85), Constraint::Length(height));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, table_state);
+}
+
+/// Shows the lines of the card's source file, with line numbers and highlighting.
+fn render_preview(
+    app_state: &mut AppState,
+    frame: &mut Frame,
+    area: Rect,
+    highlighter: Option<&Highlighter>,
+) {
+    let Some(excerpt) = app_state
+        .card_info
+        .as_ref()
+        .and_then(|info| info.excerpt.clone())
+    else {
+        return;
+    };
+
+    let block = Block::bordered()
+        .title("|Source|")
+        .padding(Padding::horizontal(1))
+        .style(Style::default().fg(OFF_WHITE));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rendered = match app_state.rendered_excerpt.take() {
+        Some((rendered, lines)) if rendered == excerpt => lines,
+        _ => {
+            let code = excerpt.lines.join("\n");
+            highlighter
+                .and_then(|highlighter| highlighter.highlight(&excerpt.language, &code))
+                .unwrap_or_else(|| excerpt.lines.iter().cloned().map(Line::from).collect())
+        }
+    };
+
+    let last_line = excerpt.first_line + excerpt.lines.len();
+    let gutter = last_line.to_string().len();
+    let mut text = Text::default();
+    if let Some(warning) = &excerpt.warning {
+        text.push_line(Line::from(format!("⚠ {warning}").yellow()));
+    }
+    for (ind, line) in rendered.iter().enumerate() {
+        let mut line = line.clone();
+        let number = format!("{:>gutter$} ", excerpt.first_line + ind);
+        line.spans
+            .insert(0, Span::styled(number, Style::new().fg(OFF_WHITE)));
+        text.push_line(line);
+    }
+    // Code keeps its layout, long lines are cut rather than wrapped.
+    frame.render_widget(
+        Paragraph::new(text).style(Style::new().fg(Color::White)),
+        inner,
+    );
+    app_state.rendered_excerpt = Some((excerpt, rendered));
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking at spaces where possible.
+fn wrap_line(text: &str, width: usize) -> Text<'static> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let len = line.chars().count();
+        let word_len = word.chars().count();
+        if len > 0 && len + 1 + word_len > width {
+            lines.push(Line::from(std::mem::take(&mut line)));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        // Words longer than a line are split.
+        while line.chars().count() > width {
+            let rest = line.split_off(line.char_indices().nth(width).unwrap().0);
+            lines.push(Line::from(std::mem::replace(&mut line, rest)));
+        }
+    }
+    lines.push(Line::from(line));
+    Text::from(lines)
+}
+
 fn render_card_table(app_state: &mut AppState, frame: &mut Frame, area: Rect) -> Result<()> {
-    if app_state.cards.is_empty() {
-        let empty_message = " No cards available. Press 'a' to add a new card. ";
+    let searching =
+        app_state.cards_table_searching || !app_state.cards_table_input.value().is_empty();
+    if app_state.cards.is_empty() && !searching {
+        let empty_message = if app_state.loading_cards {
+            " Loading cards... "
+        } else {
+            " No cards available. Press 'a' to add a new card. "
+        };
         frame.render_widget(
             Paragraph::new(empty_message)
                 .alignment(Alignment::Center)
@@ -287,15 +843,16 @@ fn render_card_table(app_state: &mut AppState, frame: &mut Frame, area: Rect) ->
         return Ok(());
     }
 
-    let header = ["Title", "Due Date", "Deck", "Id"]
-        .into_iter()
-        .map(Cell::from)
-        .collect::<Row>()
-        .style(Style::default().fg(Color::Cyan))
-        .height(1);
-
+    // The previous list stays on screen until the new one arrives.
+    let title = if app_state.loading_cards {
+        "|Cards (loading...)|".to_string()
+    } else if !app_state.marked.is_empty() {
+        format!("|Cards ({} marked)|", app_state.marked.len())
+    } else {
+        "|Cards|".to_string()
+    };
     let mut block = Block::bordered()
-        .title("|Cards|")
+        .title(title)
         .padding(Padding::horizontal(1))
         .border_style(Style::default().fg(OFF_WHITE));
 
@@ -303,38 +860,61 @@ fn render_card_table(app_state: &mut AppState, frame: &mut Frame, area: Rect) ->
         block = block.border_style(Style::default().yellow().bold())
     }
 
-    if app_state.cards_table_searching || !app_state.cards_table_input.value().is_empty() {
+    if searching {
         block = block.title_bottom(get_input_line(&app_state.cards_table_input));
     }
 
-    let cards_iter: Box<dyn Iterator<Item = &CardSummary>> =
-        if app_state.cards_table_input.value().is_empty() {
-            Box::new(app_state.cards.iter())
-        } else {
-            Box::new(
-                app_state
-                    .cards
-                    .iter()
-                    .filter(|c| c.title.contains(app_state.cards_table_input.value())),
-            )
-        };
+    // Rows start below the top border and the header.
+    let inner = block.inner(area);
+    app_state.areas.cards_table = Rect {
+        y: inner.y + 1,
+        height: inner.height.saturating_sub(1),
+        ..inner
+    };
+
+    // Narrow tables drop the id and then the deck column, the title always gets what is left.
+    let show_deck = inner.width >= 70;
+    let show_id = inner.width >= 90;
+    let columns = [
+        ("Title", Constraint::Min(15), true),
+        ("Due Date", Constraint::Length(24), true),
+        ("Deck", Constraint::Length(16), show_deck),
+        ("Id", Constraint::Length(6), show_id),
+    ];
+    let header = columns
+        .iter()
+        .filter(|(_, _, shown)| *shown)
+        .map(|(name, _, _)| Cell::from(*name))
+        .collect::<Row>()
+        .style(Style::default().fg(Color::Cyan))
+        .height(1);
+    let widths = columns
+        .iter()
+        .filter(|(_, _, shown)| *shown)
+        .map(|(_, width, _)| *width);
 
     frame.render_stateful_widget(
         Table::new(
-            cards_iter.map(|item| {
-                Row::new(vec![
-                    Cell::from(item.title.clone()),
+            app_state.visible_cards().map(|item| {
+                let marked = app_state.marked.contains(&item.id);
+                let title = if marked {
+                    Cell::from(format!("✓ {}", item.title)).yellow()
+                } else {
+                    Cell::from(item.title.clone())
+                };
+                let mut cells = vec![
+                    title,
                     Cell::from(date_to_relative_string(item.next_show_date)),
-                    Cell::from(item.deck.clone()),
-                    Cell::from(item.id.to_string()),
-                ])
+                ];
+                if show_deck {
+                    cells.push(Cell::from(item.deck.clone()));
+                }
+                if show_id {
+                    cells.push(Cell::from(item.id.to_string()));
+                }
+                Row::new(cells)
             }),
-            vec![
-                Constraint::Length(35),
-                Constraint::Length(25),
-                Constraint::Length(20),
-                Constraint::Length(10),
-            ],
+            widths,
         )
         .style(Style::new().fg(OFF_WHITE))
         .header(header)
@@ -386,9 +966,11 @@ impl Component for DeckSidebar {
         ];
 
         items.extend(
-            app_state.decks.iter().enumerate().map(|(ind, deck)| {
-                ListItem::new(format!("[{}] {}", ind + 1, deck.name.to_string()))
-            }),
+            app_state
+                .decks
+                .iter()
+                .enumerate()
+                .map(|(ind, deck)| ListItem::new(format!("[{}] {}", ind + 1, deck.name))),
         );
 
         let mut block = Block::bordered()
@@ -404,6 +986,7 @@ impl Component for DeckSidebar {
             .highlight_style(Style::default().bold())
             .block(block);
 
+        app_state.areas.sidebar = area;
         frame.render_stateful_widget(sidebar, area, &mut app_state.decks_list_state);
         Ok(())
     }
@@ -414,7 +997,24 @@ struct Keybindings {}
 
 impl Component for Keybindings {
     fn draw(&mut self, app_state: &mut AppState, frame: &mut Frame, area: Rect) -> Result<()> {
-        let key_bindings = if app_state.focused == Focused::Sidebar {
+        let mut key_bindings = if app_state.card_form.is_some() {
+            vec![("Tab", "Next field"), ("Ctrl-s", "Save"), ("Esc", "Cancel")]
+        } else if app_state.bulk_summary.is_some() {
+            vec![("y", "Apply"), ("e", "Edit again"), ("n", "Cancel")]
+        } else if app_state.broken_links.is_some() {
+            vec![
+                ("j/k", "Move down/up"),
+                ("s", "Suspend card"),
+                ("e", "Edit card"),
+                ("Esc", "Close"),
+            ]
+        } else if app_state.recover_draft.is_some() {
+            vec![
+                ("y", "Recover draft"),
+                ("n", "Discard draft"),
+                ("Esc", "Later"),
+            ]
+        } else if app_state.focused == Focused::Sidebar {
             vec![
                 ("Tab/l", "Focus cards"),
                 ("k/j", "Previous/Next Collection"),
@@ -426,9 +1026,20 @@ impl Component for Keybindings {
                 vec![("1-4", "Revise card with <ease>"), ("q", "Quit")]
             } else {
                 vec![
+                    ("J/K", "Scroll description"),
+                    ("[/]", "Scroll revisions"),
+                    ("z", "Zoom description"),
+                    ("b/i", "Toggle sidebar/details"),
                     ("<n>", "Quick deck filter"),
                     ("Tab/h", "Focus decks"),
                     ("j/k", "Move down/up"),
+                    ("/", "Search"),
+                    ("c", "Clear search"),
+                    ("+", "Quick capture"),
+                    ("v/V", "Mark/Bulk edit"),
+                    ("f/F", "Add/Edit in form"),
+                    ("o", "Open source"),
+                    ("L", "Check links"),
                     ("a", "Add card"),
                     ("e", "Edit card"),
                     ("d", "Delete card"),
@@ -439,6 +1050,17 @@ impl Component for Keybindings {
             }
         };
 
+        // Leading bindings are dropped when the terminal is too narrow to show them all.
+        let width = |bindings: &[(&str, &str)]| -> usize {
+            bindings
+                .iter()
+                .map(|(keys, desc)| keys.chars().count() + desc.chars().count() + 5)
+                .sum()
+        };
+        while key_bindings.len() > 1 && width(&key_bindings) > area.width as usize {
+            key_bindings.remove(0);
+        }
+
         let line = Line::from(
             key_bindings
                 .iter()
@@ -446,7 +1068,7 @@ impl Component for Keybindings {
                 .flat_map(|(i, (keys, desc))| {
                     vec![
                         "[".fg(OFF_WHITE),
-                        Span::from((*keys).yellow()),
+                        (*keys).yellow(),
                         "→ ".fg(OFF_WHITE),
                         Span::from(*desc),
                         "]".fg(OFF_WHITE),
@@ -11,7 +11,7 @@ use ratatui::style::{Color, Modifier, Style};
 use serde::{de::Deserializer, Deserialize};
 use tracing::error;
 
-use crate::{action::Action, app::Mode};
+use crate::{action::Action, app::Mode, highlight, source::Openers};
 
 const CONFIG: &str = include_str!("../.config/config.json5");
 
@@ -21,6 +21,25 @@ pub struct AppConfig {
     pub data_dir: PathBuf,
     #[serde(default)]
     pub config_dir: PathBuf,
+    /// Ticks per second, used for multi-key bindings and time based redraws.
+    #[serde(default)]
+    pub tick_rate: f64,
+    /// Upper bound on redraws per second. Frames are only drawn after something changed.
+    #[serde(default)]
+    pub frame_rate: f64,
+    /// Capture the mouse for clicking and scrolling. Disable to keep the terminal's own text
+    /// selection.
+    #[serde(default)]
+    pub mouse: bool,
+    /// Theme for code blocks in descriptions, one of the themes bundled with syntect.
+    #[serde(default)]
+    pub code_theme: String,
+    /// Commands that open the source of a card.
+    #[serde(default)]
+    pub openers: Openers,
+    /// Notes with this tag become cards in `vault sync`, as well as those with `revise: true`.
+    #[serde(default)]
+    pub vault_tag: String,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -52,7 +71,11 @@ impl Config {
         let config_dir = get_config_dir();
         let mut builder = config::Config::builder()
             .set_default("data_dir", data_dir.to_str().unwrap())?
-            .set_default("config_dir", config_dir.to_str().unwrap())?;
+            .set_default("config_dir", config_dir.to_str().unwrap())?
+            .set_default("tick_rate", 4.0)?
+            .set_default("frame_rate", 60.0)?
+            .set_default("mouse", true)?
+            .set_default("code_theme", highlight::DEFAULT_THEME)?;
 
         let config_files = [
             ("config.json5", config::FileFormat::Json5),
@@ -76,6 +99,13 @@ impl Config {
         }
 
         let mut cfg: Self = builder.build()?.try_deserialize()?;
+        for (key, rate) in [
+            ("tick_rate", cfg.config.tick_rate),
+            ("frame_rate", cfg.config.frame_rate),
+        ] {
+            check_rate(rate)
+                .map_err(|err| config::ConfigError::Message(format!("{key}: {err}")))?;
+        }
 
         for (mode, default_bindings) in default_config.keybindings.iter() {
             let user_bindings = cfg.keybindings.entry(*mode).or_default();
@@ -96,6 +126,26 @@ impl Config {
     }
 }
 
+/// A tick or frame rate from the command line, see [`check_rate`].
+pub fn parse_rate(text: &str) -> Result<f64, String> {
+    let rate = text
+        .parse()
+        .map_err(|_| format!("`{text}` is not a number"))?;
+    check_rate(rate)?;
+    Ok(rate)
+}
+
+/// Rates are per second, only positive ones give an interval between ticks or frames.
+pub fn check_rate(rate: f64) -> Result<(), String> {
+    if rate.is_finite() && rate > 0.0 {
+        Ok(())
+    } else {
+        Err(format!(
+            "the rate must be a positive number per second, not {rate}"
+        ))
+    }
+}
+
 pub fn get_data_dir() -> PathBuf {
     let directory = if let Some(s) = DATA_FOLDER.clone() {
         s
@@ -453,6 +503,19 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("30"), Ok(30.0));
+        assert_eq!(parse_rate("0.5"), Ok(0.5));
+        for rate in ["0", "-4", "NaN", "inf", "fast"] {
+            assert!(parse_rate(rate).is_err(), "{rate}");
+        }
+        assert_eq!(
+            check_rate(0.0),
+            Err("the rate must be a positive number per second, not 0".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_style_default() {
         let style = parse_style("");
@@ -5,6 +5,7 @@ use crate::store::ID;
 
 pub type ReviseResult<T> = Result<T, ReviseError>;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum ReviseError {
     #[error("Rusqlite error: {0}")]
@@ -13,4 +14,10 @@ pub enum ReviseError {
     NotFoundError(ID),
     #[error("data store disconnected")]
     IOError(#[from] io::Error),
+    #[error("Failed to launch editor `{0}`: {1}")]
+    EditorError(String, io::Error),
+    #[error("Failed to open `{0}`: {1}")]
+    OpenerError(String, io::Error),
+    #[error("Sync failed: {0}")]
+    SyncError(String),
 }
@@ -1,8 +1,33 @@
-use std::env;
+use std::{collections::VecDeque, env, fmt::Write as _, path::PathBuf, sync::Mutex};
 
 use color_eyre::Result;
 use tracing::error;
 
+use crate::{config, logging};
+
+/// Number of trailing log lines copied into a crash report.
+const CRASH_REPORT_LOG_LINES: usize = 50;
+
+/// Number of recent keys and actions kept for a crash report.
+const CRASH_REPORT_EVENTS: usize = 50;
+
+/// Recent keys and actions, kept regardless of the log level.
+static RECENT_EVENTS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Remembers a key or action so that a crash report can show what led up to the crash.
+pub fn record_event(event: String) {
+    let Ok(mut events) = RECENT_EVENTS.lock() else {
+        return;
+    };
+    if events.len() >= CRASH_REPORT_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(format!(
+        "{} {event}",
+        chrono::Local::now().format("%H:%M:%S%.3f")
+    ));
+}
+
 pub fn init() -> Result<()> {
     let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default()
         .panic_section(format!(
@@ -15,11 +40,9 @@ pub fn init() -> Result<()> {
         .into_hooks();
     eyre_hook.install()?;
     std::panic::set_hook(Box::new(move |panic_info| {
-        // if let Ok(mut t) = crate::tui::Tui::new() {
-        //     if let Err(r) = t.exit() {
-        //         error!("Unable to exit Terminal: {:?}", r);
-        //     }
-        // }
+        if let Err(r) = crate::tui::restore() {
+            error!("Unable to restore terminal: {:?}", r);
+        }
 
         #[cfg(not(debug_assertions))]
         {
@@ -32,7 +55,9 @@ pub fn init() -> Result<()> {
             eprintln!("{}", panic_hook.panic_report(panic_info)); // prints color-eyre stack trace to stderr
         }
         let msg = format!("{}", panic_hook.panic_report(panic_info));
-        error!("Error: {}", strip_ansi_escapes::strip_str(msg));
+        let msg = strip_ansi_escapes::strip_str(msg);
+        error!("Error: {}", msg);
+        report_crash(&msg);
 
         #[cfg(debug_assertions)]
         {
@@ -46,9 +71,99 @@ pub fn init() -> Result<()> {
 
         std::process::exit(libc::EXIT_FAILURE);
     }));
+    install_signal_handlers()?;
     Ok(())
 }
 
+/// Restores the terminal, keeps open edits as drafts and exits when the process is asked to
+/// terminate.
+///
+/// The handler runs on its own thread, so the terminal is restored even if the UI loop is busy
+/// or blocked on the database.
+#[cfg(not(windows))]
+fn install_signal_handlers() -> Result<()> {
+    use signal_hook::{
+        consts::signal::{SIGHUP, SIGTERM},
+        iterator::Signals,
+    };
+
+    let mut signals = Signals::new([SIGTERM, SIGHUP])?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            if let Err(r) = crate::tui::restore() {
+                error!("Unable to restore terminal: {:?}", r);
+            }
+            let name = if signal == SIGTERM {
+                "SIGTERM"
+            } else {
+                "SIGHUP"
+            };
+            error!("Received {name}, shutting down");
+            // The editor may still be open, what it saved is offered on the next start.
+            crate::usecase::save_open_buffers();
+            report_crash(&format!("Received {name}"));
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+fn install_signal_handlers() -> Result<()> {
+    Ok(())
+}
+
+/// Writes a crash report and tells the user where to find it.
+///
+/// Must only be called after the terminal has been restored, otherwise the message is lost on the
+/// alternate screen.
+pub fn report_crash(reason: &str) {
+    match write_crash_report(reason) {
+        Ok(path) => eprintln!("A crash report was written to {}", path.display()),
+        Err(err) => eprintln!("Unable to write crash report: {err}"),
+    }
+}
+
+/// Writes `reason`, the recent keys and actions and the tail of the log file to a timestamped file in the data dir.
+pub fn write_crash_report(reason: &str) -> Result<PathBuf> {
+    let directory = config::get_data_dir();
+    std::fs::create_dir_all(&directory)?;
+    let now = chrono::Local::now();
+    let path = directory.join(format!("crash-{}.log", now.format("%Y%m%d-%H%M%S")));
+
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(report, "time: {}", now.to_rfc3339())?;
+    writeln!(report, "\n{}", reason.trim_end())?;
+    writeln!(report, "\nRecent keys and actions:")?;
+    // The lock may be poisoned by the panic being reported; the events are still intact.
+    let events = RECENT_EVENTS.lock().unwrap_or_else(|err| err.into_inner());
+    for event in events.iter() {
+        writeln!(report, "{event}")?;
+    }
+    drop(events);
+    writeln!(report, "\nLast log entries:")?;
+    let log =
+        std::fs::read_to_string(directory.join(logging::LOG_FILE.clone())).unwrap_or_default();
+    for line in last_lines(&log, CRASH_REPORT_LOG_LINES) {
+        writeln!(report, "{line}")?;
+    }
+
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn last_lines(s: &str, n: usize) -> impl Iterator<Item = &str> {
+    let lines: Vec<&str> = s.lines().collect();
+    let start = lines.len().saturating_sub(n);
+    lines.into_iter().skip(start)
+}
+
 /// Similar to the `std::dbg!` macro, but generates `tracing` events rather
 /// than printing to stdout.
 ///
@@ -74,3 +189,20 @@ macro_rules! trace_dbg {
                 trace_dbg!(level: tracing::Level::DEBUG, $ex)
         };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_event_keeps_recent() {
+        for i in 0..=CRASH_REPORT_EVENTS {
+            record_event(format!("test event {i}"));
+        }
+        let events = RECENT_EVENTS.lock().unwrap();
+        assert_eq!(events.len(), CRASH_REPORT_EVENTS);
+        assert!(!events.iter().any(|event| event.ends_with(" test event 0")));
+        let last = format!(" test event {CRASH_REPORT_EVENTS}");
+        assert!(events.iter().any(|event| event.ends_with(&last)));
+    }
+}
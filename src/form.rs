@@ -0,0 +1,399 @@
+//! The card form shown inside the TUI, an alternative to editing cards in an external editor, and
+//! the quick-capture line that adds a card from its title alone.
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::{
+    frontmatter::Frontmatter,
+    store::ID,
+    usecase::{Card, Deck},
+};
+
+/// Deck of quick-capture cards when neither the command nor the sidebar names one.
+pub const QUICK_CAPTURE_DECK: &str = "Inbox";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Deck,
+    Tags,
+    Source,
+    Body,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::Title,
+        Field::Deck,
+        Field::Tags,
+        Field::Source,
+        Field::Body,
+    ];
+
+    fn next(self) -> Self {
+        let ind = Self::ALL.iter().position(|&f| f == self).unwrap();
+        Self::ALL[(ind + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        let ind = Self::ALL.iter().position(|&f| f == self).unwrap();
+        Self::ALL[(ind + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// What a key press did to the form.
+#[derive(Debug, PartialEq)]
+pub enum FormEvent {
+    Submit(Frontmatter, String),
+    Cancel,
+}
+
+pub struct CardForm {
+    /// The card being edited, `None` for a new card.
+    pub id: Option<ID>,
+    pub title: Input,
+    pub deck: Input,
+    pub tags: Input,
+    pub source: Input,
+    pub body: TextArea,
+    pub focused: Field,
+    /// Why the last submit was refused.
+    pub error: Option<String>,
+    deck_names: Vec<String>,
+    /// Custom frontmatter fields, kept as they are since the form has no inputs for them.
+    fields: Vec<(String, String)>,
+}
+
+impl CardForm {
+    /// An empty form for a new card in `deck`.
+    pub fn add(deck: Option<&str>, decks: &[Deck]) -> Self {
+        let deck = deck.unwrap_or_default().to_string();
+        Self {
+            id: None,
+            title: Input::default(),
+            focused: Field::Title,
+            deck: Input::new(deck),
+            tags: Input::default(),
+            source: Input::default(),
+            body: TextArea::default(),
+            error: None,
+            deck_names: decks.iter().map(|deck| deck.name.clone()).collect(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// A form for a new card filled in from a template, see [`crate::template`].
+    pub fn from_template(fm: Frontmatter, body: &str, decks: &[Deck]) -> Self {
+        Self {
+            title: Input::new(fm.title),
+            tags: Input::new(fm.tags.join(", ")),
+            source: Input::new(fm.source),
+            body: TextArea::new(body),
+            fields: fm.fields,
+            ..Self::add(Some(&fm.deck), decks)
+        }
+    }
+
+    /// A form filled in with `card`.
+    pub fn edit(card: &Card, decks: &[Deck]) -> Self {
+        Self {
+            id: Some(card.id),
+            title: Input::new(card.title.clone()),
+            deck: Input::new(card.deck.clone()),
+            tags: Input::new(card.tags.join(", ")),
+            source: Input::new(card.source.clone()),
+            body: TextArea::new(&card.desc),
+            fields: card.fields.clone(),
+            ..Self::add(None, decks)
+        }
+    }
+
+    /// The rest of the first deck name starting with what was typed into the deck field.
+    pub fn deck_completion(&self) -> Option<&str> {
+        let typed = self.deck.value();
+        if typed.is_empty() || self.deck.cursor() != typed.chars().count() {
+            return None;
+        }
+        let typed_lower = typed.to_lowercase();
+        self.deck_names
+            .iter()
+            .filter(|name| name.len() > typed.len())
+            .find(|name| name.to_lowercase().starts_with(&typed_lower))
+            .and_then(|name| name.get(typed.len()..))
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<FormEvent> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(FormEvent::Cancel),
+            KeyCode::Char('s') if ctrl => return self.submit(),
+            KeyCode::Tab => match self.deck_completion() {
+                Some(rest) if self.focused == Field::Deck => {
+                    let deck = format!("{}{rest}", self.deck.value());
+                    self.deck = Input::new(deck);
+                }
+                _ => self.focused = self.focused.next(),
+            },
+            KeyCode::BackTab => self.focused = self.focused.previous(),
+            KeyCode::Enter | KeyCode::Down if self.focused != Field::Body => {
+                self.focused = self.focused.next();
+            }
+            KeyCode::Up if self.focused != Field::Body => {
+                self.focused = self.focused.previous();
+            }
+            KeyCode::Up if self.focused == Field::Body && self.body.row == 0 => {
+                self.focused = Field::Source;
+            }
+            _ => {
+                let input = match self.focused {
+                    Field::Title => &mut self.title,
+                    Field::Deck => &mut self.deck,
+                    Field::Tags => &mut self.tags,
+                    Field::Source => &mut self.source,
+                    Field::Body => {
+                        self.body.handle_key(key);
+                        return None;
+                    }
+                };
+                input.handle_event(&Event::Key(key));
+            }
+        }
+        None
+    }
+
+    fn submit(&mut self) -> Option<FormEvent> {
+        let fm = Frontmatter {
+            id: self.id,
+            title: self.title.value().trim().to_string(),
+            deck: self.deck.value().trim().to_string(),
+            tags: split_tags(self.tags.value()),
+            source: self.source.value().trim().to_string(),
+            fields: self.fields.clone(),
+            ..Default::default()
+        };
+        if let Err(err) = fm.validate() {
+            self.error = Some(err);
+            return None;
+        }
+        Some(FormEvent::Submit(fm, self.body.value()))
+    }
+}
+
+fn split_tags(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// A minimal multi-line editor for the body. Positions are in characters.
+#[derive(Debug, Clone)]
+pub struct TextArea {
+    lines: Vec<String>,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Default for TextArea {
+    fn default() -> Self {
+        Self {
+            lines: vec![String::new()],
+            row: 0,
+            col: 0,
+        }
+    }
+}
+
+impl TextArea {
+    pub fn new(text: &str) -> Self {
+        let mut lines: Vec<_> = text.lines().map(str::to_string).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Self {
+            lines,
+            ..Default::default()
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn value(&self) -> String {
+        let mut text = self.lines.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    fn byte_offset(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices()
+            .nth(self.col)
+            .map_or(line.len(), |(ind, _)| ind)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char(c) if !ctrl => {
+                let offset = self.byte_offset();
+                self.lines[self.row].insert(offset, c);
+                self.col += 1;
+            }
+            KeyCode::Enter => {
+                let offset = self.byte_offset();
+                let rest = self.lines[self.row].split_off(offset);
+                self.row += 1;
+                self.col = 0;
+                self.lines.insert(self.row, rest);
+            }
+            KeyCode::Backspace if self.col > 0 => {
+                self.col -= 1;
+                let offset = self.byte_offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.col < self.line_len(self.row) => {
+                let offset = self.byte_offset();
+                self.lines[self.row].remove(offset);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let line = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Left if self.col > 0 => self.col -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+            }
+            KeyCode::Right if self.col < self.line_len(self.row) => self.col += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = 0;
+            }
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(self.row),
+            _ => {}
+        }
+    }
+}
+
+/// Reads a quick-capture line such as `Raft paper #distsys @papers`: words starting with `#` are
+/// tags, one starting with `@` is the deck, the rest is the title. Returns `None` without a title.
+pub fn parse_quick_capture(text: &str, default_deck: Option<&str>) -> Option<Frontmatter> {
+    let mut fm = Frontmatter::default();
+    let mut title = Vec::new();
+    for word in text.split_whitespace() {
+        match (word.strip_prefix('#'), word.strip_prefix('@')) {
+            (Some(tag), _) if !tag.is_empty() => fm.tags.push(tag.to_string()),
+            (_, Some(deck)) if !deck.is_empty() => fm.deck = deck.to_string(),
+            _ => title.push(word),
+        }
+    }
+    if title.is_empty() {
+        return None;
+    }
+    fm.title = title.join(" ");
+    if fm.deck.is_empty() {
+        fm.deck = default_deck.unwrap_or(QUICK_CAPTURE_DECK).to_string();
+    }
+    Some(fm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(form: &mut CardForm, codes: &[KeyCode]) -> Option<FormEvent> {
+        codes
+            .iter()
+            .filter_map(|&code| form.handle_key(KeyEvent::from(code)))
+            .last()
+    }
+
+    fn chars(text: &str) -> Vec<KeyCode> {
+        text.chars()
+            .map(|c| match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_form_submit() {
+        let decks = vec![Deck {
+            id: 1,
+            name: "papers".to_string(),
+            uuid: String::new(),
+        }];
+        let mut form = CardForm::add(None, &decks);
+        press(&mut form, &chars("Raft\npa"));
+        assert_eq!(form.deck_completion(), Some("pers"));
+        press(&mut form, &[KeyCode::Tab, KeyCode::Tab]);
+        press(&mut form, &chars("a, b\nnotes/raft.md:3\n# Notes\nline"));
+        press(&mut form, &[KeyCode::Up, KeyCode::End, KeyCode::Backspace]);
+
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let Some(FormEvent::Submit(fm, body)) = form.handle_key(save) else {
+            panic!("form was not submitted");
+        };
+        assert_eq!(fm.title, "Raft");
+        assert_eq!(fm.deck, "papers");
+        assert_eq!(fm.tags, vec!["a", "b"]);
+        assert_eq!(fm.source, "notes/raft.md:3");
+        assert_eq!(body, "# Note\nline\n");
+    }
+
+    #[test]
+    fn test_form_requires_title() {
+        let mut form = CardForm::add(Some("rust"), &[]);
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(form.handle_key(save), None);
+        assert_eq!(form.error.as_deref(), Some("title: must not be empty"));
+        assert_eq!(press(&mut form, &[KeyCode::Esc]), Some(FormEvent::Cancel));
+    }
+
+    #[test]
+    fn test_text_area_joins_lines() {
+        let mut area = TextArea::new("ab\ncd\n");
+        area.handle_key(KeyEvent::from(KeyCode::Down));
+        area.handle_key(KeyEvent::from(KeyCode::Backspace));
+        assert_eq!(area.value(), "abcd\n");
+        assert_eq!((area.row, area.col), (0, 2));
+    }
+
+    #[test]
+    fn test_parse_quick_capture() {
+        let fm = parse_quick_capture("Raft paper #distsys @papers", Some("rust")).unwrap();
+        assert_eq!(fm.title, "Raft paper");
+        assert_eq!(fm.deck, "papers");
+        assert_eq!(fm.tags, vec!["distsys"]);
+        assert_eq!(
+            parse_quick_capture("x", None).unwrap().deck,
+            QUICK_CAPTURE_DECK
+        );
+        assert_eq!(parse_quick_capture("#only @tags", None), None);
+    }
+}
@@ -0,0 +1,515 @@
+//! The editor buffer of a card: a YAML frontmatter block with the card's fields, followed by the
+//! Markdown body that is stored as the card's description.
+
+use chrono::NaiveDate;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::{source::Source, store::ID};
+
+/// Fields of a card as written in the frontmatter. Fields that are missing from the buffer are
+/// `None` and keep their current value.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Frontmatter {
+    pub id: Option<ID>,
+    pub title: String,
+    pub deck: String,
+    pub tags: Vec<String>,
+    /// Where the material lives, see [`Source`].
+    pub source: String,
+    pub due: Option<NaiveDate>,
+    pub suspended: Option<bool>,
+    /// `delete: true` in a bulk edit removes the card.
+    pub delete: bool,
+    /// Any other keys, e.g. `pages` or `repo` from a template, in the order they were written.
+    pub fields: Vec<(String, String)>,
+}
+
+const DELIMITER: &str = "---";
+
+/// Splits off a frontmatter block at the very start of `text`. Returns the block without its
+/// delimiters, if there is one, and the rest of the text.
+pub fn split(text: &str) -> (Option<&str>, &str) {
+    let Some(rest) = text
+        .strip_prefix(DELIMITER)
+        .and_then(|rest| rest.strip_prefix('\n').or(rest.strip_prefix("\r\n")))
+    else {
+        return (None, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            let body = &rest[offset + line.len()..];
+            return (Some(&rest[..offset]), body);
+        }
+        offset += line.len();
+    }
+    // An unterminated block is not frontmatter.
+    (None, text)
+}
+
+/// The frontmatter block as written by the user, before validation.
+#[derive(Debug, Default, Deserialize)]
+struct RawFrontmatter {
+    id: Option<ID>,
+    #[serde(default, deserialize_with = "scalar")]
+    title: Option<String>,
+    #[serde(default, deserialize_with = "scalar")]
+    deck: Option<String>,
+    #[serde(default)]
+    tags: Option<Tags>,
+    #[serde(default, deserialize_with = "scalar")]
+    source: Option<String>,
+    #[serde(default, deserialize_with = "scalar")]
+    due: Option<String>,
+    suspended: Option<bool>,
+    delete: Option<bool>,
+    #[serde(flatten)]
+    fields: serde_yaml::Mapping,
+}
+
+/// A list of tags, or a single comma separated string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<serde_yaml::Value>),
+    Text(String),
+}
+
+/// Accepts any scalar as a string, so `title: 1984` does not need quotes.
+fn scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let value = Option::<serde_yaml::Value>::deserialize(deserializer)?;
+    value
+        .map(|value| {
+            scalar_to_string(&value)
+                .ok_or_else(|| D::Error::custom("expected a single value, not a list or map"))
+        })
+        .transpose()
+}
+
+fn scalar_to_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.trim().to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Parses an editor buffer into its fields and body. Only a block at the very start of the
+/// buffer is frontmatter, a `---` further down is part of the body.
+///
+/// Errors are meant for the user and name the offending line of the buffer.
+pub fn parse(text: &str) -> Result<(Frontmatter, String), String> {
+    let (Some(block), body) = split(text) else {
+        return Err("the card must start with a frontmatter block between `---` lines".to_string());
+    };
+    let raw: RawFrontmatter = if block.trim().is_empty() {
+        RawFrontmatter::default()
+    } else {
+        serde_yaml::from_str(block).map_err(|err| {
+            // Drop serde_yaml's own positions, e.g. ` at line 3 column 1`.
+            let mut message = err.to_string();
+            while let Some(start) = message.find(" at line ") {
+                let rest = &message[start + " at line ".len()..];
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == ' ' || "column".contains(c)))
+                    .unwrap_or(rest.len());
+                message.replace_range(start..start + " at line ".len() + len, "");
+            }
+            // Location is relative to the block, the opening `---` is line 1 of the buffer.
+            match err.location() {
+                Some(location) => format!("line {}: {message}", location.line() + 1),
+                None => message,
+            }
+        })?
+    };
+
+    let tags = match raw.tags {
+        None => Vec::new(),
+        Some(Tags::Text(text)) => text
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+        Some(Tags::List(values)) => values
+            .iter()
+            .map(|value| scalar_to_string(value).ok_or("tags: expected a list of words"))
+            .filter(|tag| !tag.as_ref().is_ok_and(|tag| tag.is_empty()))
+            .collect::<Result<_, _>>()?,
+    };
+    let due = raw
+        .due
+        .filter(|due| !due.is_empty())
+        .map(|due| {
+            NaiveDate::parse_from_str(&due, "%Y-%m-%d")
+                .map_err(|_| format!("due: `{due}` is not a date like 2024-12-31"))
+        })
+        .transpose()?;
+
+    let mut fields = Vec::new();
+    for (key, value) in raw.fields {
+        let key = scalar_to_string(&key).unwrap_or_default();
+        // Other keys are free form, but a known key in other case would be silently ignored.
+        if let Some(known) = KEYS.iter().find(|known| key.to_lowercase() == **known) {
+            return Err(format!("unknown field `{key}`, did you mean `{known}`?"));
+        }
+        let value = scalar_to_string(&value)
+            .ok_or_else(|| format!("{key}: expected a single value, not a list or map"))?;
+        // Fields a template left empty are not worth keeping.
+        if !key.is_empty() && !value.is_empty() {
+            fields.push((key, value));
+        }
+    }
+
+    let fm = Frontmatter {
+        id: raw.id,
+        title: raw.title.unwrap_or_default(),
+        deck: raw.deck.unwrap_or_default(),
+        tags,
+        source: raw.source.unwrap_or_default(),
+        due,
+        suspended: raw.suspended,
+        delete: raw.delete.unwrap_or_default(),
+        fields,
+    };
+    Ok((fm, body.to_string()))
+}
+
+/// Whether `key` is `known` in other case, or with one letter added, removed or changed.
+fn is_typo(key: &str, known: &str) -> bool {
+    let key: Vec<_> = key.to_lowercase().chars().collect();
+    let known: Vec<_> = known.chars().collect();
+    let (shorter, longer) = if key.len() <= known.len() {
+        (&key, &known)
+    } else {
+        (&known, &key)
+    };
+    if longer.len() - shorter.len() > 1 {
+        return false;
+    }
+    let prefix = shorter
+        .iter()
+        .zip(longer)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = shorter[prefix..]
+        .iter()
+        .rev()
+        .zip(longer[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    longer.len() - prefix - suffix <= 1
+}
+
+/// Keys that may start a frontmatter block, see [`split_cards`].
+const KEYS: [&str; 8] = [
+    "id",
+    "title",
+    "deck",
+    "tags",
+    "source",
+    "due",
+    "suspended",
+    "delete",
+];
+
+/// Splits a document with several cards into one buffer per card, each with the line number it
+/// starts on. A card starts at a `---` line that is followed by a frontmatter key, so horizontal
+/// rules in bodies stay where they are. Blank lines and `#` comments before the first card are
+/// ignored.
+pub fn split_cards(text: &str) -> Result<Vec<(usize, &str)>, String> {
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+    let is_start = |ind: usize| {
+        lines[ind].trim_end() == DELIMITER
+            && lines.get(ind + 1).is_some_and(|next| {
+                KEYS.iter().any(|key| {
+                    next.strip_prefix(key)
+                        .is_some_and(|rest| rest.starts_with(':'))
+                })
+            })
+    };
+
+    let mut cards = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+    for (ind, line) in lines.iter().enumerate() {
+        if is_start(ind) {
+            if let Some((line, from)) = start {
+                cards.push((line, &text[from..offset]));
+            }
+            start = Some((ind + 1, offset));
+        } else if start.is_none() && !line.trim().is_empty() && !line.starts_with('#') {
+            return Err(format!(
+                "line {}: expected a `---` line starting a card",
+                ind + 1
+            ));
+        }
+        offset += line.len();
+    }
+    if let Some((line, from)) = start {
+        cards.push((line, &text[from..]));
+    }
+    Ok(cards)
+}
+
+/// Parses every card of a document written by [`render`]s joined together. Errors name the line
+/// of the document.
+pub fn parse_cards(text: &str) -> Result<Vec<(Frontmatter, String)>, String> {
+    split_cards(text)?
+        .into_iter()
+        .map(|(first_line, card)| {
+            parse(card).map_err(|err| {
+                let line = err
+                    .strip_prefix("line ")
+                    .and_then(|rest| rest.split_once(": "))
+                    .and_then(|(line, rest)| Some((line.parse::<usize>().ok()?, rest)));
+                match line {
+                    Some((line, rest)) => format!("line {}: {rest}", first_line + line - 1),
+                    None => format!("line {first_line}: {err}"),
+                }
+            })
+        })
+        .collect()
+}
+
+impl Frontmatter {
+    /// Checks the fields every card needs.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.is_empty() {
+            return Err("title: must not be empty".to_string());
+        }
+        if self.deck.is_empty() {
+            return Err("deck: must not be empty".to_string());
+        }
+        if !self.source.is_empty() {
+            Source::parse(&self.source)?;
+        }
+        Ok(())
+    }
+
+    /// Custom fields that look like a typo of a known key, e.g. `dek`. They are kept, as a field
+    /// such as `tag` may well be meant, but the user is told in case it is not.
+    pub fn typos(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter_map(|(key, _)| {
+                let known = KEYS.iter().find(|known| is_typo(key, known))?;
+                Some(format!(
+                    "`{key}` is kept as a custom field, did you mean `{known}`?"
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Marks the lines of an error block, which are removed before the buffer is parsed again.
+const ERROR_PREFIX: &str = "# error: ";
+const ERROR_HINT: &str = "# Lines starting with \"# error:\" are ignored. \
+    Fix the card and save, or save an empty file to cancel.";
+
+/// Puts `error` above the buffer, replacing an earlier error block.
+pub fn with_error(text: &str, error: &str) -> String {
+    let mut result = String::new();
+    for line in error.lines() {
+        result.push_str(ERROR_PREFIX);
+        result.push_str(line);
+        result.push('\n');
+    }
+    result.push_str(ERROR_HINT);
+    result.push('\n');
+    result.push_str(strip_error(text));
+    result
+}
+
+/// Removes the error block added by [`with_error`].
+pub fn strip_error(text: &str) -> &str {
+    let mut rest = text;
+    while let Some((line, next)) = rest.split_once('\n') {
+        if !line.starts_with(ERROR_PREFIX) && line != ERROR_HINT {
+            break;
+        }
+        rest = next;
+    }
+    rest
+}
+
+/// Writes the editor buffer for `fm` and `body`. `id`, `due` and `suspended` are left out when
+/// they are `None`.
+pub fn render(fm: &Frontmatter, body: &str) -> String {
+    let mut text = format!("{DELIMITER}\n");
+    if let Some(id) = fm.id {
+        text.push_str(&format!("id: {id}\n"));
+    }
+    text.push_str(&format!("title:{}\n", yaml_value(&fm.title)));
+    text.push_str(&format!("deck:{}\n", yaml_value(&fm.deck)));
+    let tags: Vec<_> = fm.tags.iter().map(|tag| yaml_value(tag)).collect();
+    text.push_str(&format!("tags: [{}]\n", tags.join(",").trim_start()));
+    text.push_str(&format!("source:{}\n", yaml_value(&fm.source)));
+    for (key, value) in &fm.fields {
+        // Keys need the same quoting as values, e.g. `see: also` or `#ref`.
+        let key = yaml_value(key);
+        text.push_str(&format!("{}:{}\n", key.trim_start(), yaml_value(value)));
+    }
+    if let Some(due) = fm.due {
+        text.push_str(&format!("due: {}\n", due.format("%Y-%m-%d")));
+    }
+    if let Some(suspended) = fm.suspended {
+        text.push_str(&format!("suspended: {suspended}\n"));
+    }
+    text.push_str(DELIMITER);
+    text.push('\n');
+    text.push_str(body);
+    text
+}
+
+/// Formats `value` after a `key:`, quoted if YAML needs it. Empty values are left out.
+fn yaml_value(value: &str) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    let yaml = serde_yaml::to_string(value).unwrap_or_else(|_| format!("{value:?}"));
+    format!(" {}", yaml.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_only_leading_block() {
+        let text = "---\ntitle: a\n---\nbody\n\n---\n\nmore\n";
+        assert_eq!(split(text), (Some("title: a\n"), "body\n\n---\n\nmore\n"));
+        assert_eq!(
+            split("body\n---\nx: y\n---\n"),
+            (None, "body\n---\nx: y\n---\n")
+        );
+        assert_eq!(split("---\nunterminated\n"), (None, "---\nunterminated\n"));
+    }
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let fm = Frontmatter {
+            id: Some(3),
+            title: "Retry: logic".to_string(),
+            deck: "rust".to_string(),
+            tags: vec!["net".to_string(), "worker".to_string()],
+            source: "src/net.rs:10-20".to_string(),
+            due: NaiveDate::from_ymd_opt(2026, 10, 21),
+            suspended: Some(false),
+            delete: false,
+            fields: vec![("pages".to_string(), "1-12".to_string())],
+        };
+        let text = render(&fm, "# Retry\n");
+        assert_eq!(
+            text,
+            "---\nid: 3\ntitle: 'Retry: logic'\ndeck: rust\ntags: [net, worker]\nsource: src/net.rs:10-20\npages: 1-12\ndue: 2026-10-21\nsuspended: false\n---\n# Retry\n"
+        );
+        assert_eq!(parse(&text), Ok((fm, "# Retry\n".to_string())));
+    }
+
+    #[test]
+    fn test_render_and_parse_quoted_field_keys() {
+        let fields = ["see: also", "#ref", " indented", "- item", "? why", "{x}"]
+            .map(|key| (key.to_string(), "x".to_string()));
+        let fm = Frontmatter {
+            title: "Raft".to_string(),
+            fields: fields.to_vec(),
+            ..Default::default()
+        };
+        let text = render(&fm, "");
+        assert!(text.contains("'see: also': x\n'#ref': x\n"), "{text}");
+        // Parsing trims keys, like values.
+        let mut expected = fm.clone();
+        expected.fields[2].0 = "indented".to_string();
+        assert_eq!(parse(&text), Ok((expected, String::new())));
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let text = "---\ntitle: \"Raft: the paper\"\ndeck: 1984\ntags:\n  - a\n  - b\n---\nbody\n---\nrule\n";
+        let (fm, body) = parse(text).unwrap();
+        assert_eq!(fm.title, "Raft: the paper");
+        assert_eq!(fm.deck, "1984");
+        assert_eq!(fm.tags, vec!["a", "b"]);
+        assert_eq!(body, "body\n---\nrule\n");
+        assert_eq!(
+            parse("---\ntags: a, b\n---\n").unwrap().0.tags,
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("---\ntitle: a\ndeck: [b\n---\n"),
+            Err(
+                "line 4: did not find expected ',' or ']', while parsing a flow sequence"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("---\ndue: tomorrow\n---\n"),
+            Err("due: `tomorrow` is not a date like 2024-12-31".to_string())
+        );
+        let (fm, _) = parse("---\ndek: a\n---\n").unwrap();
+        assert_eq!(
+            fm.typos(),
+            vec!["`dek` is kept as a custom field, did you mean `deck`?"]
+        );
+        let (fm, _) = parse("---\ndesc: Leader election\ntag: raft\n---\n").unwrap();
+        assert_eq!(
+            fm.fields,
+            vec![
+                ("desc".to_string(), "Leader election".to_string()),
+                ("tag".to_string(), "raft".to_string()),
+            ]
+        );
+        assert_eq!(
+            fm.typos(),
+            vec!["`tag` is kept as a custom field, did you mean `tags`?"]
+        );
+        assert!(parse("---\nTitle: a\n---\n")
+            .unwrap_err()
+            .contains("did you mean `title`"));
+        assert_eq!(
+            parse("---\nrepo: [a]\n---\n"),
+            Err("repo: expected a single value, not a list or map".to_string())
+        );
+        let (fm, _) = parse("---\npages: 3\nrepo:\n---\n").unwrap();
+        assert_eq!(fm.fields, vec![("pages".to_string(), "3".to_string())]);
+        assert!(parse("no frontmatter").is_err());
+    }
+
+    #[test]
+    fn test_parse_cards() {
+        let text = "# comment\n\n---\nid: 1\ntitle: a\n---\nbody\n---\n\nrule\n---\ntitle: b\ndelete: true\n---\n";
+        let cards = parse_cards(text).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].0.id, Some(1));
+        assert_eq!(cards[0].1, "body\n---\n\nrule\n");
+        assert_eq!(cards[1].0.title, "b");
+        assert!(cards[1].0.delete);
+
+        assert_eq!(
+            parse_cards("---\ntitle: a\n---\n---\ntitle: b\ndue: x\n---\n"),
+            Err("line 4: due: `x` is not a date like 2024-12-31".to_string())
+        );
+        assert_eq!(
+            parse_cards("---\ntitle: a\n---\n---\ntitle: b\ndeck: [c\n---\n"),
+            Err(
+                "line 7: did not find expected ',' or ']', while parsing a flow sequence"
+                    .to_string()
+            )
+        );
+        assert!(parse_cards("stray\n---\ntitle: a\n---\n").is_err());
+    }
+
+    #[test]
+    fn test_error_block_is_replaced() {
+        let text = "---\ntitle:\n---\n";
+        let once = with_error(text, "title: must not be empty");
+        assert!(once.starts_with("# error: title: must not be empty\n"));
+        assert_eq!(strip_error(&with_error(&once, "other")), text);
+    }
+}
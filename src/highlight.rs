@@ -0,0 +1,83 @@
+//! Syntax highlighting for fenced code blocks, using the grammars and themes bundled with syntect.
+
+use std::sync::OnceLock;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use tracing::warn;
+
+/// Theme used when the configured one does not exist.
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+pub struct Highlighter {
+    theme: Theme,
+    /// Loading the grammars takes a moment, so it waits for the first code block.
+    syntaxes: OnceLock<SyntaxSet>,
+}
+
+impl Highlighter {
+    /// Creates a highlighter for one of syntect's default themes, e.g. `base16-ocean.dark`,
+    /// `Solarized (light)` or `InspiredGitHub`.
+    pub fn new(theme: &str) -> Self {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let theme = themes.remove(theme).unwrap_or_else(|| {
+            let mut names: Vec<_> = themes.keys().cloned().collect();
+            names.sort();
+            warn!("Unknown code theme `{theme}`, using {DEFAULT_THEME}. Available: {names:?}");
+            themes.remove(DEFAULT_THEME).unwrap()
+        });
+        Self {
+            theme,
+            syntaxes: OnceLock::new(),
+        }
+    }
+
+    /// Highlights `code` as the language named by a fence tag such as `rust`, `py` or `toml`.
+    /// Returns `None` if the language is unknown.
+    pub fn highlight(&self, lang: &str, code: &str) -> Option<Vec<Line<'static>>> {
+        let syntaxes = self.syntaxes.get_or_init(SyntaxSet::load_defaults_newlines);
+        // Fence tags may carry attributes, e.g. `rust,ignore`.
+        let token = lang.split([',', ' ']).next().unwrap_or_default();
+        let syntax = syntaxes.find_syntax_by_token(token)?;
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut lines = Vec::new();
+        for line in LinesWithEndings::from(code) {
+            let ranges = highlighter.highlight_line(line, syntaxes).ok()?;
+            let spans = ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    Span::styled(
+                        text.trim_end_matches(['\n', '\r']).to_string(),
+                        to_style(style),
+                    )
+                })
+                .collect::<Vec<_>>();
+            lines.push(Line::from(spans));
+        }
+        Some(lines)
+    }
+}
+
+/// Keeps the terminal background, only the foreground and font style come from the theme.
+fn to_style(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let mut result = Style::new().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        result = result.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        result = result.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        result = result.add_modifier(Modifier::UNDERLINED);
+    }
+    result
+}
@@ -0,0 +1,378 @@
+//! `serve --http`: a REST API over the card operations and a page to review in the browser,
+//! on localhost only. Requests need the token, as `Authorization: Bearer <token>` or the cookie
+//! that `/login?token=<token>` sets for the browser.
+//!
+//! The endpoints run the methods of [`rpc::call`], so both servers check the params the same
+//! way.
+
+use std::{
+    fs,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path as UrlPath, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+        StatusCode,
+    },
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+    Form, Json, Router,
+};
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    markdown,
+    rpc::{self, RpcError, Shared},
+    store::{self, ID},
+};
+
+/// Overrides the token kept in the data dir.
+pub const TOKEN_ENV: &str = "REVISE_HTTP_TOKEN";
+
+const COOKIE_NAME: &str = "revise_token";
+
+/// The token from [`TOKEN_ENV`], else the one in `http-token` next to the database, which is
+/// made on first use. Returns the file it is kept in, if any.
+pub fn token() -> Result<(String, Option<PathBuf>)> {
+    if let Ok(token) = std::env::var(TOKEN_ENV) {
+        if !token.trim().is_empty() {
+            return Ok((token.trim().to_string(), None));
+        }
+    }
+    let file = store::data_path()
+        .parent()
+        .map_or_else(store::data_dir, Path::to_path_buf)
+        .join("http-token");
+    if let Ok(token) = fs::read_to_string(&file) {
+        if !token.trim().is_empty() {
+            return Ok((token.trim().to_string(), Some(file)));
+        }
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    // Only readable by the user before the token is in it, an empty file left over included.
+    #[cfg(unix)]
+    let mut out = {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        let out = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&file)?;
+        out.set_permissions(fs::Permissions::from_mode(0o600))?;
+        out
+    };
+    #[cfg(not(unix))]
+    let mut out = fs::File::create(&file)?;
+    out.write_all(token.as_bytes())?;
+    Ok((token, Some(file)))
+}
+
+/// Serves on `addr` until Ctrl-C. Only loopback addresses are allowed.
+pub async fn serve(usecase: Shared, addr: SocketAddr, token: String) -> Result<()> {
+    if !addr.ip().is_loopback() {
+        return Err(eyre!(
+            "--http only listens on localhost, e.g. 127.0.0.1:{}",
+            addr.port()
+        ));
+    }
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    println!("Listening on http://{addr}, press Ctrl-C to stop");
+    println!("Review page: http://{addr}/login?token={token}");
+    axum::serve(listener, router(usecase, token))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+struct AppState {
+    usecase: Shared,
+    token: Arc<str>,
+}
+
+fn router(usecase: Shared, token: String) -> Router {
+    let state = AppState {
+        usecase,
+        token: token.into(),
+    };
+    Router::new()
+        .route("/api/decks", get(list_decks).post(add_deck))
+        .route("/api/decks/{id}", delete(delete_deck))
+        .route("/api/cards", get(list_cards).post(add_card))
+        .route(
+            "/api/cards/{id}",
+            get(get_card).patch(edit_card).delete(remove_card),
+        )
+        .route("/api/cards/{id}/reviews", get(get_reviews))
+        .route("/api/cards/{id}/rate", post(rate))
+        .route("/api/due", get(list_due))
+        .route("/", get(|| async { Redirect::to("/review") }))
+        .route("/review", get(review_page))
+        .route("/review/{id}", post(rate_form))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .route("/login", get(login))
+        .with_state(state)
+}
+
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let cookie = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(&format!("{COOKIE_NAME}=")));
+    if bearer
+        .or(cookie)
+        .is_some_and(|token| same(token, &state.token))
+    {
+        return next.run(request).await;
+    }
+    if request.uri().path().starts_with("/api/") {
+        let error = json!({ "error": "missing or wrong token" });
+        return (StatusCode::UNAUTHORIZED, Json(error)).into_response();
+    }
+    let body = "<p>Open the review page link printed by <code>evise serve</code> to log in.</p>";
+    (StatusCode::UNAUTHORIZED, Html(page("Log in", body))).into_response()
+}
+
+/// Compares tokens in time that does not depend on where they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Deserialize)]
+struct Login {
+    token: String,
+}
+
+/// Keeps the token in a cookie, so the review page works without it in the URL.
+async fn login(State(state): State<AppState>, Query(login): Query<Login>) -> Response {
+    if !same(&login.token, &state.token) {
+        let body = "<p>Wrong token.</p>";
+        return (StatusCode::UNAUTHORIZED, Html(page("Log in", body))).into_response();
+    }
+    let cookie = format!(
+        "{COOKIE_NAME}={}; Path=/; HttpOnly; SameSite=Strict",
+        login.token
+    );
+    ([(SET_COOKIE, cookie)], Redirect::to("/review")).into_response()
+}
+
+struct ApiError(RpcError);
+
+impl From<RpcError> for ApiError {
+    fn from(err: RpcError) -> Self {
+        ApiError(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0.code {
+            rpc::NOT_FOUND => StatusCode::NOT_FOUND,
+            rpc::INVALID_PARAMS => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.0.message }))).into_response()
+    }
+}
+
+type ApiResult<T = Json<Value>> = Result<T, ApiError>;
+
+async fn call(state: &AppState, method: &str, params: Value) -> ApiResult<Value> {
+    Ok(rpc::call_shared(&state.usecase, method, params).await?)
+}
+
+/// The JSON body with the id from the URL added.
+fn with_id(body: Value, id: ID) -> Result<Value, ApiError> {
+    let Value::Object(mut params) = body else {
+        return Err(RpcError::new(rpc::INVALID_PARAMS, "expected a JSON object").into());
+    };
+    params.insert("id".to_string(), id.into());
+    Ok(Value::Object(params))
+}
+
+async fn list_decks(State(state): State<AppState>) -> ApiResult {
+    call(&state, "list_decks", Value::Null).await.map(Json)
+}
+
+async fn add_deck(State(state): State<AppState>, Json(body): Json<Value>) -> ApiResult<Response> {
+    let deck = call(&state, "add_deck", body).await?;
+    Ok((StatusCode::CREATED, Json(deck)).into_response())
+}
+
+async fn delete_deck(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<ID>,
+) -> ApiResult<StatusCode> {
+    call(&state, "delete_deck", json!({ "id": id })).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct CardsQuery {
+    deck: Option<String>,
+    #[serde(default)]
+    suspended: bool,
+}
+
+async fn list_cards(State(state): State<AppState>, Query(query): Query<CardsQuery>) -> ApiResult {
+    let params = json!({ "deck": query.deck, "suspended": query.suspended });
+    call(&state, "list_cards", params).await.map(Json)
+}
+
+async fn add_card(State(state): State<AppState>, Json(body): Json<Value>) -> ApiResult<Response> {
+    let card = call(&state, "add_card", body).await?;
+    Ok((StatusCode::CREATED, Json(card)).into_response())
+}
+
+async fn get_card(State(state): State<AppState>, UrlPath(id): UrlPath<ID>) -> ApiResult {
+    call(&state, "get_card", json!({ "id": id }))
+        .await
+        .map(Json)
+}
+
+async fn edit_card(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<ID>,
+    Json(body): Json<Value>,
+) -> ApiResult {
+    call(&state, "edit_card", with_id(body, id)?)
+        .await
+        .map(Json)
+}
+
+async fn remove_card(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<ID>,
+) -> ApiResult<StatusCode> {
+    call(&state, "remove_card", json!({ "id": id })).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_reviews(State(state): State<AppState>, UrlPath(id): UrlPath<ID>) -> ApiResult {
+    call(&state, "get_reviews", json!({ "id": id }))
+        .await
+        .map(Json)
+}
+
+async fn rate(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<ID>,
+    Json(body): Json<Value>,
+) -> ApiResult {
+    call(&state, "rate", with_id(body, id)?).await.map(Json)
+}
+
+#[derive(Deserialize)]
+struct DueQuery {
+    deck: Option<String>,
+}
+
+async fn list_due(State(state): State<AppState>, Query(query): Query<DueQuery>) -> ApiResult {
+    call(&state, "list_due", json!({ "deck": query.deck }))
+        .await
+        .map(Json)
+}
+
+const RATINGS: [&str; 4] = ["again", "hard", "good", "easy"];
+
+/// The first due card, with its answer folded and a button per rating.
+async fn review_page(State(state): State<AppState>) -> ApiResult<Html<String>> {
+    let due = call(&state, "list_due", Value::Null).await?;
+    let due = due.as_array().cloned().unwrap_or_default();
+    let Some(first) = due.first() else {
+        let body = "<p>Nothing to review, come back later.</p>";
+        return Ok(Html(page("Review", body)));
+    };
+    let card = call(&state, "get_card", json!({ "id": first["id"] })).await?;
+    let text = |key: &str| escape(card[key].as_str().unwrap_or_default());
+    let buttons: String = RATINGS
+        .iter()
+        .map(|rating| {
+            format!(r#"<button name="rating" value="{rating}" class="{rating}">{rating}</button>"#)
+        })
+        .collect();
+    let body = format!(
+        r#"<p class="meta">{deck} · {count} due</p>
+<h1>{title}</h1>
+<details>
+<summary>Show answer</summary>
+{desc}
+</details>
+<form method="post" action="/review/{id}">{buttons}</form>"#,
+        deck = text("deck"),
+        count = due.len(),
+        title = text("title"),
+        desc = markdown::to_html(card["desc"].as_str().unwrap_or_default()),
+        id = card["id"],
+    );
+    Ok(Html(page("Review", &body)))
+}
+
+#[derive(Deserialize)]
+struct RateForm {
+    rating: String,
+}
+
+async fn rate_form(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<ID>,
+    Form(form): Form<RateForm>,
+) -> ApiResult<Redirect> {
+    call(&state, "rate", json!({ "id": id, "rating": form.rating })).await?;
+    Ok(Redirect::to("/review"))
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title} · revise</title>
+<style>
+body {{ font-family: sans-serif; max-width: 46em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }}
+.meta {{ color: #777; }}
+summary {{ cursor: pointer; margin: 1em 0; }}
+pre {{ background: #f4f4f4; padding: 0.5em; overflow-x: auto; }}
+form {{ margin-top: 2em; }}
+button {{ font-size: 1em; padding: 0.4em 1.2em; margin-right: 0.5em; text-transform: capitalize; }}
+</style>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,4 +1,9 @@
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 use store::SqliteStore;
 use structopt::StructOpt;
 use usecase::Usecase;
@@ -7,16 +12,32 @@ use crate::app::App;
 
 mod action;
 mod app;
+mod bookmarks;
+mod check;
 mod cli;
 mod components;
 mod config;
+mod error;
 mod errors;
+mod form;
+mod frontmatter;
+mod highlight;
+mod http;
 mod logging;
-mod tui;
+mod markdown;
+mod rpc;
+#[cfg(unix)]
+mod socket;
+mod source;
 mod store;
+mod sync;
+mod sync_server;
+mod template;
+mod tui;
 mod usecase;
-mod error;
 mod utils;
+mod vault;
+mod worker;
 
 #[derive(StructOpt)]
 enum Opt {
@@ -24,30 +45,265 @@ enum Opt {
     Tui {
         #[structopt(long, help = "Specify editor command to use")]
         editor: Option<String>,
+        #[structopt(
+            long,
+            parse(try_from_str = config::parse_rate),
+            help = "Ticks per second, overrides the config file"
+        )]
+        tick_rate: Option<f64>,
+        #[structopt(
+            long,
+            parse(try_from_str = config::parse_rate),
+            help = "Maximum frames per second, overrides the config file"
+        )]
+        frame_rate: Option<f64>,
+        #[structopt(
+            long,
+            help = "Template for new cards, a name in the templates dir or a file"
+        )]
+        template: Option<String>,
+    },
+    /// Makes cards of Markdown notes
+    #[structopt(name = "vault")]
+    Vault(VaultCommand),
+    /// Makes cards of a Markdown file
+    #[structopt(name = "import")]
+    Import(ImportCommand),
+    /// Serves the cards to editor plugins and other programs
+    #[structopt(name = "serve")]
+    Serve {
+        #[structopt(
+            long,
+            help = "JSON-RPC over a Unix socket, revise.sock next to the database by default"
+        )]
+        socket: Option<Option<String>>,
+        #[structopt(
+            long,
+            help = "REST API and review page on this address, e.g. 127.0.0.1:8080"
+        )]
+        http: Option<SocketAddr>,
+    },
+    /// Merges the cards and reviews with other machines through a shared folder or a sync server
+    #[structopt(name = "sync")]
+    Sync {
+        #[structopt(help = "A folder the machines share, or the URL of a sync server")]
+        target: String,
+        #[structopt(
+            long,
+            env = "REVISE_SYNC_TOKEN",
+            hide_env_values = true,
+            help = "Token for the sync server, made with `sync-server add-user`"
+        )]
+        token: Option<String>,
+    },
+    /// Keeps the cards of several users for their machines to sync with
+    #[structopt(name = "sync-server")]
+    SyncServer {
+        #[structopt(long, default_value = "127.0.0.1:8090", help = "Address to listen on")]
+        addr: SocketAddr,
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help = "Where the collections are kept, `sync-server` in the data dir by default"
+        )]
+        dir: Option<PathBuf>,
+        #[structopt(subcommand)]
+        command: Option<ServerCommand>,
+    },
+    /// Reports cards whose source or description links to missing files or changed lines
+    #[structopt(name = "check-links")]
+    CheckLinks {
+        #[structopt(long, help = "Specify editor command to use")]
+        editor: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum VaultCommand {
+    /// Adds and updates cards for the notes with `revise: true` or the vault tag
+    #[structopt(name = "sync")]
+    Sync {
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+        #[structopt(long, help = "Keep running and sync again when notes change")]
+        watch: bool,
+        #[structopt(
+            long,
+            help = "Notes with this tag become cards too, overrides the config file"
+        )]
+        tag: Option<String>,
     },
 }
 
-async fn tui(usecase: Usecase<SqliteStore>) -> Result<()> {
+#[derive(StructOpt)]
+enum ImportCommand {
+    /// Adds a card for every heading of a level, running it again updates them
+    #[structopt(name = "headings")]
+    Headings {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(long, default_value = "2", help = "Level of the headings, 2 for `##`")]
+        level: usize,
+        #[structopt(long, help = "Deck of the cards, the name of the file by default")]
+        deck: Option<String>,
+    },
+    /// Adds a card for every link in a browser's bookmark export, with the link as its source
+    #[structopt(name = "bookmarks")]
+    Bookmarks {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(
+            long = "folder",
+            help = "Only links in this folder, a name or a path like `Toolbar/Reading`"
+        )]
+        folders: Vec<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum ServerCommand {
+    /// Prints a new token for a user, adding the user if needed. Their old token stops working
+    #[structopt(name = "add-user")]
+    AddUser { name: String },
+    /// Removes a user and their cards from the server
+    #[structopt(name = "remove-user")]
+    RemoveUser { name: String },
+}
+
+async fn run_sync_server(
+    addr: SocketAddr,
+    dir: Option<PathBuf>,
+    command: Option<&ServerCommand>,
+) -> Result<()> {
+    let dir = dir.unwrap_or_else(|| store::data_dir().join("sync-server"));
+    match command {
+        None => sync_server::serve(&dir, addr).await?,
+        Some(ServerCommand::AddUser { name }) => {
+            sync_server::check_user_name(name)?;
+            let token = sync_server::ServerStore::open(&dir)?.add_user(name)?;
+            println!("Token for {name}: {token}");
+            println!("Sync with: evise sync <server URL> --token {token}");
+        }
+        Some(ServerCommand::RemoveUser { name }) => {
+            if !sync_server::ServerStore::open(&dir)?.remove_user(name)? {
+                return Err(eyre!("no user named {name}"));
+            }
+            println!("Removed {name} and their cards");
+        }
+    }
+    Ok(())
+}
+
+async fn tui(
+    usecase: Usecase<SqliteStore>,
+    tick_rate: Option<f64>,
+    frame_rate: Option<f64>,
+) -> Result<()> {
     crate::errors::init()?;
     crate::logging::init()?;
 
-    let mut app = App::new(4.0, 60.0, usecase)?;
-    app.run().await?;
+    let mut app = App::new(tick_rate, frame_rate, usecase)?;
+    if let Err(err) = app.run().await {
+        // The `Tui` has been dropped at this point, restoring again just makes sure the report is
+        // printed to the main screen.
+        crate::tui::restore()?;
+        let msg = strip_ansi_escapes::strip_str(format!("{err:?}"));
+        tracing::error!("Fatal error: {msg}");
+        crate::errors::report_crash(&msg);
+        return Err(err);
+    }
     Ok(())
 }
 
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let opts = Opt::from_args();
+    // The server keeps its own collections, it does not open the user's.
+    if let Opt::SyncServer { addr, dir, command } = &opts {
+        return run_sync_server(*addr, dir.clone(), command.as_ref()).await;
+    }
 
     let usecase = match &opts {
-        Opt::Tui { editor } => Usecase::new_with_editor(editor.clone()),
+        Opt::Tui {
+            editor, template, ..
+        } => {
+            if let Some(name) = template.as_deref() {
+                if template::path(name).is_none() {
+                    return Err(eyre!(
+                        "template `{name}` not found in {}",
+                        template::templates_dir().display()
+                    ));
+                }
+            }
+            Usecase::new_with_editor(editor.clone()).with_template(template.clone())
+        }
+        Opt::CheckLinks { editor } => Usecase::new_with_editor(editor.clone()),
+        Opt::Vault(_)
+        | Opt::Import(_)
+        | Opt::Serve { .. }
+        | Opt::Sync { .. }
+        | Opt::SyncServer { .. } => Usecase::new_with_editor(None),
     };
 
     match &opts {
-        Opt::Tui { .. } => {
-            tui(usecase).await?;
+        Opt::Tui {
+            tick_rate,
+            frame_rate,
+            ..
+        } => {
+            tui(usecase, *tick_rate, *frame_rate).await?;
+        }
+        Opt::CheckLinks { .. } => cli::check_links(&usecase)?,
+        Opt::Sync { target, token } => cli::sync(&usecase, target, token.as_deref())?,
+        Opt::SyncServer { .. } => unreachable!("handled above"),
+        Opt::Serve { socket, http } => {
+            if socket.is_none() && http.is_none() {
+                return Err(eyre!("nothing to serve, pass --socket or --http"));
+            }
+            #[cfg(not(unix))]
+            if socket.is_some() {
+                return Err(eyre!(
+                    "sockets are only supported on Unix, use --http instead"
+                ));
+            }
+            // Both servers share one connection to the database.
+            let usecase = Arc::new(Mutex::new(usecase));
+            let socket = async {
+                #[cfg(unix)]
+                if let Some(path) = socket {
+                    let path = path
+                        .as_ref()
+                        .map_or_else(socket::default_socket_path, PathBuf::from);
+                    return socket::serve(usecase.clone(), &path).await;
+                }
+                Ok(())
+            };
+            let http = async {
+                let Some(addr) = http else {
+                    return Ok(());
+                };
+                let (token, file) = http::token()?;
+                if let Some(file) = file {
+                    println!("Token in {}", file.display());
+                }
+                http::serve(usecase.clone(), *addr, token).await
+            };
+            tokio::try_join!(socket, http)?;
+        }
+        Opt::Vault(VaultCommand::Sync { dir, watch, tag }) => {
+            let config = config::Config::new()?;
+            let vault_tag = config.config.vault_tag;
+            let tag = tag.clone().or((!vault_tag.is_empty()).then_some(vault_tag));
+            cli::vault_sync(&usecase, dir, tag.as_deref(), *watch)?;
+        }
+        Opt::Import(ImportCommand::Headings { file, level, deck }) => {
+            if !(1..=6).contains(level) {
+                return Err(eyre!("--level must be between 1 and 6"));
+            }
+            cli::import_headings(&usecase, file, *level, deck.as_deref())?;
+        }
+        Opt::Import(ImportCommand::Bookmarks { file, folders }) => {
+            cli::import_bookmarks(&usecase, file, folders)?;
         }
     };
 
@@ -0,0 +1,556 @@
+//! Renders card descriptions, which are CommonMark, as styled ratatui [`Text`].
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::highlight::Highlighter;
+
+const HEADING: Style = Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+const CODE: Style = Style::new().fg(Color::LightYellow);
+const LINK: Style = Style::new()
+    .fg(Color::LightBlue)
+    .add_modifier(Modifier::UNDERLINED);
+const DIM: Style = Style::new().fg(Color::DarkGray);
+const QUOTE: Style = Style::new().fg(Color::Gray).add_modifier(Modifier::ITALIC);
+const METADATA_KEY: Style = Style::new().fg(Color::Cyan);
+
+/// Renders `markdown` for the description pane. A leading YAML frontmatter block is shown as a
+/// table of its keys and values, fenced code blocks are highlighted by `highlighter`.
+pub fn render(markdown: &str, highlighter: Option<&Highlighter>) -> Text<'static> {
+    let options = Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut renderer = Renderer {
+        highlighter,
+        ..Default::default()
+    };
+    for event in Parser::new_ext(markdown, options) {
+        renderer.event(event);
+    }
+    renderer.finish()
+}
+
+#[derive(Default)]
+struct Renderer<'a> {
+    highlighter: Option<&'a Highlighter>,
+    lines: Vec<Line<'static>>,
+    /// Spans of the line being built.
+    line: Vec<Span<'static>>,
+    /// Inline styles, innermost last.
+    styles: Vec<Style>,
+    /// Next number of every open list, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Marker of a list item that has not been written yet.
+    item_marker: Option<String>,
+    quote_depth: usize,
+    /// Destinations of the open links.
+    links: Vec<String>,
+    /// Text of the open code or metadata block.
+    block: Option<String>,
+    /// Language tag of the open fenced code block.
+    code_lang: Option<String>,
+    /// The last line is a separator added by [`Renderer::blank`].
+    ends_with_blank: bool,
+}
+
+impl Renderer<'_> {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match self.block.as_mut() {
+                Some(block) => block.push_str(&text),
+                None => self.push(text.into_string(), self.style()),
+            },
+            Event::Code(code) => self.push(code.into_string(), CODE),
+            Event::InlineMath(math) | Event::DisplayMath(math) => {
+                self.push(math.into_string(), CODE)
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                // Block HTML arrives one line at a time, including the line break.
+                let html = html.into_string();
+                self.push(html.trim_end_matches('\n').to_string(), DIM);
+                if html.ends_with('\n') {
+                    self.flush();
+                }
+            }
+            Event::FootnoteReference(name) => self.push(format!("[^{name}]"), DIM),
+            Event::SoftBreak => self.push(" ".to_string(), self.style()),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.push("─".repeat(30), DIM);
+                self.flush();
+                self.blank();
+            }
+            Event::TaskListMarker(done) => {
+                let marker = if done { "[x] " } else { "[ ] " };
+                self.push(marker.to_string(), DIM);
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush();
+                let hashes = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                };
+                self.push(format!("{} ", "#".repeat(hashes)), DIM);
+                self.styles.push(HEADING);
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth += 1;
+                self.styles.push(QUOTE);
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                self.block = Some(String::new());
+                self.code_lang = match kind {
+                    CodeBlockKind::Fenced(lang) if !lang.is_empty() => Some(lang.into_string()),
+                    _ => None,
+                };
+            }
+            Tag::MetadataBlock(_) => {
+                self.flush();
+                self.block = Some(String::new());
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.item_marker = Some(marker);
+            }
+            Tag::Emphasis => self
+                .styles
+                .push(self.style().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.styles.push(self.style().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self
+                .styles
+                .push(self.style().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link { dest_url, .. } => {
+                self.links.push(dest_url.into_string());
+                self.styles.push(LINK);
+            }
+            Tag::Image { dest_url, .. } => {
+                self.push("[image: ".to_string(), DIM);
+                self.links.push(dest_url.into_string());
+                self.styles.push(LINK);
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush();
+                self.blank();
+            }
+            TagEnd::Heading(_) => {
+                self.styles.pop();
+                self.flush();
+                self.blank();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                // The separator after the last paragraph still has the quote bar.
+                if self.ends_with_blank {
+                    self.lines.pop();
+                    self.ends_with_blank = false;
+                }
+                self.styles.pop();
+                self.quote_depth -= 1;
+                self.blank();
+            }
+            TagEnd::CodeBlock => {
+                let code = self.block.take().unwrap_or_default();
+                let lang = self.code_lang.take();
+                let highlighted = self
+                    .highlighter
+                    .zip(lang)
+                    .and_then(|(highlighter, lang)| highlighter.highlight(&lang, &code));
+                let lines = highlighted.unwrap_or_else(|| {
+                    code.trim_end_matches('\n')
+                        .split('\n')
+                        .map(|line| Line::from(Span::styled(line.to_string(), CODE)))
+                        .collect()
+                });
+                for line in lines {
+                    if line.spans.is_empty() {
+                        self.push(String::new(), CODE);
+                    }
+                    for span in line.spans {
+                        self.push(span.content.into_owned(), span.style);
+                    }
+                    self.flush();
+                }
+                self.blank();
+            }
+            TagEnd::MetadataBlock(_) => {
+                let metadata = self.block.take().unwrap_or_default();
+                self.metadata_table(&metadata);
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank();
+                }
+            }
+            TagEnd::Item => self.flush(),
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                let url = self.links.pop().unwrap_or_default();
+                // Autolinks already show their destination.
+                let text: String = self.line.iter().map(|span| span.content.as_ref()).collect();
+                if !text.ends_with(&url) {
+                    self.push(format!(" <{url}>"), DIM);
+                }
+            }
+            TagEnd::Image => {
+                self.styles.pop();
+                self.links.pop();
+                self.push("]".to_string(), DIM);
+            }
+            _ => {}
+        }
+    }
+
+    /// Shows the frontmatter as aligned `key  value` lines, lines that are not a plain key and
+    /// value pair are shown as they are.
+    fn metadata_table(&mut self, metadata: &str) {
+        let rows: Vec<_> = metadata
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| match line.split_once(':') {
+                Some((key, value)) if !key.starts_with([' ', '-']) => {
+                    (Some(key.trim()), value.trim())
+                }
+                _ => (None, line),
+            })
+            .collect();
+        let width = rows
+            .iter()
+            .filter_map(|(key, _)| key.map(|key| key.chars().count()))
+            .max()
+            .unwrap_or(0);
+        for (key, value) in rows {
+            match key {
+                Some(key) => {
+                    self.push(format!("{key:width$}  "), METADATA_KEY);
+                    self.push(value.to_string(), Style::new());
+                }
+                None => self.push(value.to_string(), DIM),
+            }
+            self.flush();
+        }
+        self.push("─".repeat(30), DIM);
+        self.flush();
+        self.blank();
+    }
+
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn push(&mut self, text: String, style: Style) {
+        if self.line.is_empty() {
+            self.line = self.prefix();
+        }
+        self.line.push(Span::styled(text, style));
+    }
+
+    /// Quote bars and list indentation that start every line.
+    fn prefix(&mut self) -> Vec<Span<'static>> {
+        let mut prefix = Vec::new();
+        if self.quote_depth > 0 {
+            prefix.push(Span::styled("│ ".repeat(self.quote_depth), DIM));
+        }
+        if !self.lists.is_empty() {
+            let indent = "  ".repeat(self.lists.len() - 1);
+            match self.item_marker.take() {
+                Some(marker) => prefix.push(Span::styled(format!("{indent}{marker}"), DIM)),
+                None => prefix.push(Span::raw(format!("{indent}  "))),
+            }
+        }
+        prefix
+    }
+
+    /// Ends the current line, if anything was written to it.
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(Line::from(std::mem::take(&mut self.line)));
+            self.ends_with_blank = false;
+        }
+    }
+
+    /// Adds an empty line between blocks, unless there already is one.
+    fn blank(&mut self) {
+        if !self.lines.is_empty() && !self.ends_with_blank {
+            self.ends_with_blank = true;
+            let prefix = match self.quote_depth {
+                0 => Line::default(),
+                depth => Line::from(Span::styled("│".repeat(depth), DIM)),
+            };
+            self.lines.push(prefix);
+        }
+    }
+
+    fn finish(mut self) -> Text<'static> {
+        self.flush();
+        while self.lines.last().is_some_and(|line| line.width() == 0) {
+            self.lines.pop();
+        }
+        Text::from(self.lines)
+    }
+}
+
+/// Renders `markdown` as HTML for the review page. Raw HTML in the Markdown is escaped rather
+/// than passed through, and links and images only keep destinations of [`safe_url`].
+pub fn to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_TABLES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: safe_url(dest_url),
+            title,
+            id,
+        }),
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+/// `url` if it is relative or an `http`, `https` or `mailto` URL, else nothing, so a card can't
+/// run script such as `javascript:` on the review page.
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    // Browsers skip whitespace and control characters in the scheme, `java\tscript:` runs too.
+    let cleaned: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    let scheme = cleaned
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+    match scheme {
+        None => url,
+        Some(scheme) if ["http", "https", "mailto"].contains(&scheme.to_lowercase().as_str()) => {
+            url
+        }
+        Some(_) => CowStr::Borrowed(""),
+    }
+}
+
+/// A heading and the text under it, up to the next heading of the same or a higher level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// 1 for `#`, 6 for `######`.
+    pub level: usize,
+    pub title: String,
+    /// Lines of the heading and its text, starting at 1 and inclusive.
+    pub lines: (usize, usize),
+    /// The text under the heading, with the subsections.
+    pub body: String,
+}
+
+/// The sections of `markdown` for every heading, in the order they appear. A frontmatter block
+/// is not mistaken for a heading.
+pub fn sections(markdown: &str) -> Vec<Section> {
+    let options = Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    // Headings as (level, title, start of the heading, end of the heading).
+    let mut headings = Vec::new();
+    let mut current: Option<(usize, String, usize, usize)> = None;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((level as usize, String::new(), range.start, range.end));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title, ..)) = current.as_mut() {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    let line_of = |offset: usize| markdown[..offset].matches('\n').count() + 1;
+    headings
+        .iter()
+        .enumerate()
+        .map(|(ind, (level, title, start, heading_end))| {
+            let end = headings[ind + 1..]
+                .iter()
+                .find(|(next_level, ..)| next_level <= level)
+                .map_or(markdown.len(), |(_, _, next_start, _)| *next_start);
+            let text = markdown[..end].trim_end();
+            Section {
+                level: *level,
+                title: title.trim().to_string(),
+                lines: (line_of(*start), line_of(text.len()).max(line_of(*start))),
+                body: markdown[(*heading_end).min(text.len())..text.len()]
+                    .trim_matches('\n')
+                    .to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &Text) -> Vec<String> {
+        text.lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_render_blocks() {
+        let text = render(
+            "# Retry\n\nUse **backoff**.\n\n- one\n- two\n\n```rust\nlet a = 1;\n```\n",
+            None,
+        );
+        assert_eq!(
+            plain(&text),
+            vec![
+                "# Retry",
+                "",
+                "Use backoff.",
+                "",
+                "• one",
+                "• two",
+                "",
+                "let a = 1;"
+            ]
+        );
+        let backoff = &text.lines[2].spans[1];
+        assert!(backoff.style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_render_frontmatter_as_table() {
+        let text = render("---\ntitle: Raft\ndeck: papers\n---\nbody\n", None);
+        assert_eq!(
+            plain(&text)[..2],
+            ["title  Raft".to_string(), "deck   papers".to_string()]
+        );
+        assert_eq!(plain(&text).last().unwrap(), "body");
+    }
+
+    #[test]
+    fn test_render_highlighted_code() {
+        let highlighter = Highlighter::new(crate::highlight::DEFAULT_THEME);
+        let text = render("```rust\nfn main() {}\n```\n", Some(&highlighter));
+        assert_eq!(plain(&text), vec!["fn main() {}"]);
+        let colors: Vec<_> = text.lines[0].spans.iter().map(|s| s.style.fg).collect();
+        assert!(colors.iter().all(|c| matches!(c, Some(Color::Rgb(..)))));
+        assert!(colors.windows(2).any(|pair| pair[0] != pair[1]));
+
+        // Unknown languages fall back to the plain code style.
+        let text = render("```nope\nx\n```\n", Some(&highlighter));
+        assert!(text.lines[0].spans.iter().all(|s| s.style == CODE));
+    }
+
+    #[test]
+    fn test_render_block_quote() {
+        let text = render("> one\n>\n> two\n\nafter", None);
+        assert_eq!(plain(&text), vec!["│ one", "│", "│ two", "", "after"]);
+    }
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            to_html("# Raft\n\nSee <img src=x onerror=alert(1)> `a < b`\n\n<script>\n"),
+            "<h1>Raft</h1>\n<p>See &lt;img src=x onerror=alert(1)&gt; <code>a &lt; b</code></p>\n\
+             &lt;script&gt;\n"
+        );
+    }
+
+    #[test]
+    fn test_sections() {
+        let markdown = "---\ntitle: Notes\n---\n# Raft\n\nIntro\n\n## Leader `election`\n\
+                        Votes\n\n### Terms\nNumbers\n\n## Log\nEntries\n\n\n";
+        let sections = sections(markdown);
+        let summary: Vec<_> = sections
+            .iter()
+            .map(|s| (s.level, s.title.as_str(), s.lines))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "Raft", (4, 15)),
+                (2, "Leader election", (8, 12)),
+                (3, "Terms", (11, 12)),
+                (2, "Log", (14, 15)),
+            ]
+        );
+        assert_eq!(sections[1].body, "Votes\n\n### Terms\nNumbers");
+        assert_eq!(sections[3].body, "Entries");
+    }
+
+    #[test]
+    fn test_render_nested_lists_and_links() {
+        let text = render(
+            "1. [docs](https://example.com)\n   - nested\n2. <https://a.b>\n",
+            None,
+        );
+        assert_eq!(
+            plain(&text),
+            vec![
+                "1. docs <https://example.com>",
+                "  • nested",
+                "2. https://a.b"
+            ]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! The card operations as JSON-RPC 2.0 methods, served by [`socket`](crate::socket) and
+//! [`http`](crate::http).
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use chrono::NaiveDate;
+use color_eyre::Result;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::{
+    error::ReviseError,
+    frontmatter::Frontmatter,
+    store::{SqliteStore, ID},
+    usecase::{card_frontmatter, Card, CardSummary, Usecase},
+};
+
+/// The usecase of the servers, calls take turns.
+pub type Shared = Arc<Mutex<Usecase<SqliteStore>>>;
+
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The card or deck in the params does not exist.
+pub const NOT_FOUND: i64 = -32001;
+
+/// Runs [`call`] on a blocking thread once the usecase is free.
+pub async fn call_shared(usecase: &Shared, method: &str, params: Value) -> Result<Value, RpcError> {
+    let usecase = usecase.clone();
+    let method = method.to_string();
+    tokio::task::spawn_blocking(move || {
+        let usecase = usecase.lock().unwrap_or_else(PoisonError::into_inner);
+        call(&usecase, &method, params)
+    })
+    .await
+    .unwrap_or_else(|err| Err(RpcError::new(INTERNAL_ERROR, err.to_string())))
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl From<ReviseError> for RpcError {
+    fn from(err: ReviseError) -> Self {
+        match err {
+            ReviseError::NotFoundError(id) => Self::new(NOT_FOUND, format!("card {id} not found")),
+            err => Self::new(INTERNAL_ERROR, err.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddCard {
+    title: String,
+    deck: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    source: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EditCard {
+    id: ID,
+    title: Option<String>,
+    deck: Option<String>,
+    desc: Option<String>,
+    tags: Option<Vec<String>>,
+    source: Option<String>,
+    /// `YYYY-MM-DD`, the card becomes due at the start of the day.
+    due: Option<String>,
+    suspended: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Id {
+    id: ID,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListDue {
+    deck: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListCards {
+    deck: Option<String>,
+    #[serde(default)]
+    suspended: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddDeck {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rate {
+    id: ID,
+    /// 1 to 4, or `again`, `hard`, `good` or `easy`.
+    rating: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suspend {
+    id: ID,
+    #[serde(default = "yes")]
+    suspended: bool,
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::invalid_params(err.to_string()))
+}
+
+/// Runs one method, see the README for the list.
+pub fn call(
+    usecase: &Usecase<SqliteStore>,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    match method {
+        "add_card" => {
+            let params: AddCard = parse_params(params)?;
+            let fm = Frontmatter {
+                title: params.title,
+                deck: params.deck,
+                tags: params.tags,
+                source: params.source,
+                ..Default::default()
+            };
+            fm.validate().map_err(RpcError::invalid_params)?;
+            let id = usecase.save_form(&fm, params.desc);
+            Ok(card_json(&usecase.find_card(id)?))
+        }
+        "edit_card" => {
+            let params: EditCard = parse_params(params)?;
+            let card = usecase.find_card(params.id)?;
+            let mut fm = card_frontmatter(&card);
+            fm.title = params.title.unwrap_or(fm.title);
+            fm.deck = params.deck.unwrap_or(fm.deck);
+            fm.tags = params.tags.unwrap_or(fm.tags);
+            fm.source = params.source.unwrap_or(fm.source);
+            fm.suspended = params.suspended.or(fm.suspended);
+            if let Some(due) = params.due {
+                let due = NaiveDate::parse_from_str(&due, "%Y-%m-%d").map_err(|_| {
+                    RpcError::invalid_params(format!("due: `{due}` is not a date like 2024-12-31"))
+                })?;
+                fm.due = Some(due);
+            }
+            fm.validate().map_err(RpcError::invalid_params)?;
+            usecase.save_form(&fm, params.desc.unwrap_or(card.desc));
+            Ok(card_json(&usecase.find_card(params.id)?))
+        }
+        "get_card" => {
+            let params: Id = parse_params(params)?;
+            Ok(card_json(&usecase.find_card(params.id)?))
+        }
+        "remove_card" => {
+            let params: Id = parse_params(params)?;
+            usecase.find_card(params.id)?;
+            usecase.remove_card(params.id);
+            Ok(Value::Null)
+        }
+        "get_reviews" => {
+            let params: Id = parse_params(params)?;
+            usecase.find_card(params.id)?;
+            let reviews = usecase
+                .get_reviews(params.id)
+                .iter()
+                .map(|review| {
+                    json!({
+                        "review_time": review.review_time.to_rfc3339(),
+                        "interval": review.interval,
+                        "last_interval": review.last_interval,
+                        "stability": review.stability,
+                        "difficulty": review.difficulty,
+                    })
+                })
+                .collect();
+            Ok(Value::Array(reviews))
+        }
+        "list_cards" => {
+            let params: ListCards = parse_params(params)?;
+            let cards: Vec<_> = usecase
+                .list_card_summaries(None, true, params.suspended)
+                .iter()
+                .filter(|card| params.deck.as_ref().is_none_or(|deck| &card.deck == deck))
+                .map(summary_json)
+                .collect();
+            Ok(Value::Array(cards))
+        }
+        "list_due" => {
+            let params: ListDue = parse_params(params)?;
+            let cards: Vec<_> = usecase
+                .list_card_summaries(None, false, false)
+                .iter()
+                .filter(|card| params.deck.as_ref().is_none_or(|deck| &card.deck == deck))
+                .map(summary_json)
+                .collect();
+            Ok(Value::Array(cards))
+        }
+        "rate" => {
+            let params: Rate = parse_params(params)?;
+            let rating = match &params.rating {
+                Value::Number(n) => n.as_u64().filter(|n| (1..=4).contains(n)),
+                Value::String(name) => ["again", "hard", "good", "easy"]
+                    .iter()
+                    .position(|rating| rating == name)
+                    .map(|ind| ind as u64 + 1),
+                _ => None,
+            };
+            let Some(rating) = rating else {
+                return Err(RpcError::invalid_params(
+                    "rating: must be 1 to 4, or again, hard, good or easy",
+                ));
+            };
+            usecase.find_card(params.id)?;
+            usecase.revise_card(params.id, rating as usize);
+            Ok(card_json(&usecase.find_card(params.id)?))
+        }
+        "suspend" => {
+            let params: Suspend = parse_params(params)?;
+            usecase.find_card(params.id)?;
+            if params.suspended {
+                usecase.suspend_card(params.id);
+            } else {
+                usecase.unsuspend_card(params.id);
+            }
+            Ok(card_json(&usecase.find_card(params.id)?))
+        }
+        "list_decks" => {
+            let mut due: HashMap<String, usize> = HashMap::new();
+            for card in usecase.list_card_summaries(None, false, false) {
+                *due.entry(card.deck).or_default() += 1;
+            }
+            let decks = usecase
+                .list_decks()
+                .into_iter()
+                .map(|deck| {
+                    let count = due.get(&deck.name).copied().unwrap_or_default();
+                    json!({ "id": deck.id, "name": deck.name, "due": count })
+                })
+                .collect();
+            Ok(Value::Array(decks))
+        }
+        "add_deck" => {
+            let params: AddDeck = parse_params(params)?;
+            let name = params.name.trim();
+            if name.is_empty() {
+                return Err(RpcError::invalid_params("name: must not be empty"));
+            }
+            if usecase.list_decks().iter().any(|deck| deck.name == name) {
+                return Err(RpcError::invalid_params(format!(
+                    "deck `{name}` already exists"
+                )));
+            }
+            usecase.add_deck(name);
+            let deck = usecase
+                .list_decks()
+                .into_iter()
+                .find(|deck| deck.name == name);
+            let id = deck.map(|deck| deck.id).unwrap_or_default();
+            Ok(json!({ "id": id, "name": name, "due": 0 }))
+        }
+        "delete_deck" => {
+            let params: Id = parse_params(params)?;
+            if !usecase.list_decks().iter().any(|deck| deck.id == params.id) {
+                return Err(RpcError::new(
+                    NOT_FOUND,
+                    format!("deck {} not found", params.id),
+                ));
+            }
+            usecase.delete_deck(params.id);
+            Ok(Value::Null)
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method `{method}`"),
+        )),
+    }
+}
+
+pub fn card_json(card: &Card) -> Value {
+    let fields: serde_json::Map<_, _> = card
+        .fields
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+    json!({
+        "id": card.id,
+        "deck": card.deck,
+        "title": card.title,
+        "desc": card.desc,
+        "tags": card.tags,
+        "source": card.source,
+        "fields": fields,
+        "next_show_date": card.next_show_date.to_rfc3339(),
+        "created_at": card.created_at.to_rfc3339(),
+        "suspended": card.suspended,
+    })
+}
+
+pub fn summary_json(card: &CardSummary) -> Value {
+    json!({
+        "id": card.id,
+        "deck": card.deck,
+        "title": card.title,
+        "next_show_date": card.next_show_date.to_rfc3339(),
+    })
+}
@@ -0,0 +1,335 @@
+//! `serve --socket`: the JSON-RPC methods of [`rpc`](crate::rpc) over a Unix socket, for editor plugins.
+//!
+//! Requests and responses are one line of JSON each. Connected clients also get a `cards_due`
+//! notification whenever cards become due. Calls run one at a time on the server's own
+//! connection to the database, which the TUI can keep using meanwhile.
+
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::PoisonError,
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::broadcast,
+};
+
+use crate::{
+    rpc::{call_shared, summary_json, RpcError, Shared},
+    store::{self, ID},
+    usecase::CardSummary,
+};
+
+/// How often the server looks for cards that became due.
+pub const DUE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+
+/// `revise.sock` next to the database.
+pub fn default_socket_path() -> PathBuf {
+    store::data_path()
+        .parent()
+        .map_or_else(store::data_dir, Path::to_path_buf)
+        .join("revise.sock")
+}
+
+/// Serves on the socket at `path` until Ctrl-C, then removes the socket.
+pub async fn serve(usecase: Shared, path: &Path) -> Result<()> {
+    let listener = bind(path)?;
+    println!("Listening on {}, press Ctrl-C to stop", path.display());
+    tokio::select! {
+        result = run(listener, usecase, DUE_CHECK_INTERVAL) => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Binds the socket, only the user may connect. A socket left behind by a server that was
+/// killed is replaced, one that still answers is an error.
+fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(eyre!("a server is already listening on {}", path.display()));
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Accepts clients on `listener`, looking for due cards every `interval`.
+pub async fn run(listener: UnixListener, usecase: Shared, interval: Duration) -> Result<()> {
+    let (notify_tx, _) = broadcast::channel(16);
+    // Cards due when the server starts are no news.
+    let known = due_cards(&usecase)
+        .await?
+        .iter()
+        .map(|card| card.id)
+        .collect();
+    tokio::spawn(watch_due(
+        usecase.clone(),
+        notify_tx.clone(),
+        known,
+        interval,
+    ));
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(handle_connection(
+            stream,
+            usecase.clone(),
+            notify_tx.subscribe(),
+        ));
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    usecase: Shared,
+    mut notifications: broadcast::Receiver<String>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let message = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => match respond(&usecase, &line).await {
+                    Some(response) => response,
+                    None => continue,
+                },
+                _ => break,
+            },
+            notification = notifications.recv() => match notification {
+                Ok(notification) => notification,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if writer
+            .write_all(format!("{message}\n").as_bytes())
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Missing for notifications, which get no response.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// The response to one line from a client, `None` for notifications and empty lines.
+async fn respond(usecase: &Shared, line: &str) -> Option<String> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(err) => {
+            let error = RpcError::new(PARSE_ERROR, err.to_string());
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+    let request = match serde_json::from_value::<Request>(value.clone()) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            let id = value.get("id").cloned().unwrap_or_default();
+            let error = RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request");
+            return Some(response(id, Err(error)));
+        }
+    };
+
+    let result = call_shared(usecase, &request.method, request.params).await;
+    Some(response(request.id?, result))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+    .to_string()
+}
+
+async fn due_cards(usecase: &Shared) -> Result<Vec<CardSummary>> {
+    let usecase = usecase.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let usecase = usecase.lock().unwrap_or_else(PoisonError::into_inner);
+        usecase.list_card_summaries(None, false, false)
+    })
+    .await?;
+    Ok(due)
+}
+
+/// Sends a `cards_due` notification with the cards that became due since the last check,
+/// `known` are the ones due before.
+async fn watch_due(
+    usecase: Shared,
+    notify: broadcast::Sender<String>,
+    mut known: HashSet<ID>,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Ok(due) = due_cards(&usecase).await else {
+            continue;
+        };
+        let new: Vec<_> = due
+            .iter()
+            .filter(|card| !known.contains(&card.id))
+            .map(summary_json)
+            .collect();
+        if !new.is_empty() {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "cards_due",
+                "params": { "due": due.len(), "cards": new },
+            });
+            // Nobody may be listening.
+            let _ = notify.send(notification.to_string());
+        }
+        known = due.iter().map(|card| card.id).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+    use tokio::net::unix::OwnedReadHalf;
+    use tokio::net::unix::OwnedWriteHalf;
+
+    use super::*;
+    use crate::{
+        rpc::{INVALID_PARAMS, METHOD_NOT_FOUND, NOT_FOUND},
+        store::SqliteStore,
+        usecase::Usecase,
+    };
+    use std::sync::{Arc, Mutex};
+
+    struct Client {
+        reader: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+        notifications: Vec<Value>,
+    }
+
+    impl Client {
+        async fn send(&mut self, line: &str) -> Value {
+            self.writer
+                .write_all(format!("{line}\n").as_bytes())
+                .await
+                .unwrap();
+            loop {
+                let line = self.reader.next_line().await.unwrap().unwrap();
+                let message: Value = serde_json::from_str(&line).unwrap();
+                if message.get("method").is_some() {
+                    self.notifications.push(message);
+                } else {
+                    return message;
+                }
+            }
+        }
+
+        async fn notification(&mut self) -> Value {
+            if self.notifications.is_empty() {
+                let line = tokio::time::timeout(Duration::from_secs(5), self.reader.next_line());
+                let line = line.await.unwrap().unwrap().unwrap();
+                self.notifications
+                    .push(serde_json::from_str(&line).unwrap());
+            }
+            self.notifications.remove(0)
+        }
+
+        async fn call(&mut self, method: &str, params: Value) -> Value {
+            let request = json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params });
+            self.send(&request.to_string()).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(&dir.path().join("data.sqlite")).unwrap();
+        let path = dir.path().join("revise.sock");
+        let listener = bind(&path).unwrap();
+        let interval = Duration::from_millis(20);
+        let usecase = Arc::new(Mutex::new(Usecase::with_store(store)));
+        tokio::spawn(run(listener, usecase, interval));
+        assert!(bind(&path).is_err(), "the socket is in use");
+
+        let (reader, writer) = UnixStream::connect(&path).await.unwrap().into_split();
+        let mut client = Client {
+            reader: BufReader::new(reader).lines(),
+            writer,
+            notifications: Vec::new(),
+        };
+        let added = client
+            .call(
+                "add_card",
+                json!({ "title": "Raft", "deck": "papers", "desc": "Leader election", "source": "raft.md:3" }),
+            )
+            .await;
+        assert_eq!(added["id"], 7);
+        let card = &added["result"];
+        assert_eq!(
+            (card["title"].as_str(), card["source"].as_str()),
+            (Some("Raft"), Some("raft.md:3"))
+        );
+        let id = card["id"].clone();
+
+        let edited = client
+            .call("edit_card", json!({ "id": id, "tags": ["distsys"] }))
+            .await;
+        assert_eq!(edited["result"]["tags"], json!(["distsys"]));
+        assert_eq!(edited["result"]["desc"], "Leader election");
+
+        // Due dates are compared to the second, the new card is due in the next one.
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let decks = client.call("list_decks", json!(null)).await;
+        assert_eq!(decks["result"][0]["due"], 1);
+
+        let rated = client
+            .call("rate", json!({ "id": id, "rating": "good" }))
+            .await;
+        assert!(
+            rated["result"]["next_show_date"].as_str().unwrap()
+                > card["next_show_date"].as_str().unwrap()
+        );
+        let due = client.call("list_due", json!({})).await;
+        assert_eq!(due["result"], json!([]));
+
+        let missing = client.call("get_card", json!({ "id": 99 })).await;
+        assert_eq!(missing["error"]["code"], NOT_FOUND);
+        let invalid = client.call("rate", json!({ "id": id, "rating": 5 })).await;
+        assert_eq!(invalid["error"]["code"], INVALID_PARAMS);
+        let unknown = client.call("delete_everything", json!({})).await;
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        let garbage = client.send("{").await;
+        assert_eq!(garbage["error"]["code"], PARSE_ERROR);
+
+        // The card added above became due after the server started.
+        let notification = client.notification().await;
+        assert_eq!(notification["method"], "cards_due");
+        assert_eq!(notification["params"]["cards"][0]["title"], "Raft");
+    }
+}
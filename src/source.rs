@@ -0,0 +1,427 @@
+//! The `source` of a card: where the material to review lives. A local file with an optional
+//! line, line range or Markdown heading, or a URI such as `https://…` or `obsidian://…` that
+//! another app opens.
+
+use std::{fmt, fs, io, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::markdown;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// `path`, `path:12`, `path:12-20` or `path#Heading`. Lines start at 1 and the range is
+    /// inclusive.
+    File {
+        path: PathBuf,
+        lines: Option<(usize, usize)>,
+        /// Title of a Markdown heading, the source is its section.
+        heading: Option<String>,
+    },
+    /// Anything with a `scheme://`, opened by the URL opener.
+    Url(String),
+}
+
+impl Source {
+    /// Reads a `source` field as written in the frontmatter.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("source: must not be empty".to_string());
+        }
+        if let Some(path) = text.strip_prefix("file://") {
+            return Self::parse(path);
+        }
+        if let Some((scheme, _)) = text.split_once("://") {
+            let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+            if valid {
+                return Ok(Source::Url(text.to_string()));
+            }
+        }
+
+        // Headings may contain anything, a `#` in a file name is much rarer.
+        let (path, lines, heading) = match text.split_once('#') {
+            Some((path, heading)) if !path.is_empty() && !heading.trim().is_empty() => {
+                (path, None, Some(heading.trim().to_string()))
+            }
+            _ => match text.rsplit_once(':') {
+                Some((path, range))
+                    if !path.is_empty() && range.starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    (path, Some(parse_lines(range)?), None)
+                }
+                _ => (text, None, None),
+            },
+        };
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+            None => PathBuf::from(path),
+        };
+        Ok(Source::File {
+            path,
+            lines,
+            heading,
+        })
+    }
+
+    /// The first line of a file source, for openers that jump to it.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Source::File {
+                lines: Some((start, _)),
+                ..
+            } => Some(*start),
+            Source::File {
+                path,
+                heading: Some(heading),
+                ..
+            } => {
+                let text = fs::read_to_string(path).ok()?;
+                find_section(&text, heading).map(|(start, _)| start)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, Source::File { .. })
+    }
+
+    /// The lines a file source points at, for the preview pane. `None` for URLs.
+    pub fn excerpt(&self) -> Option<Excerpt> {
+        let Source::File {
+            path,
+            lines,
+            heading,
+        } = self
+        else {
+            return None;
+        };
+        let language = path
+            .extension()
+            .or(path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut excerpt = Excerpt {
+            language,
+            first_line: 1,
+            lines: Vec::new(),
+            warning: None,
+        };
+        let text = match fs::read(path).map(String::from_utf8) {
+            Ok(Ok(text)) => text,
+            Ok(Err(_)) => {
+                excerpt.warning = Some("Not a text file".to_string());
+                return Some(excerpt);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                excerpt.warning = Some(format!("File not found: {}", path.display()));
+                return Some(excerpt);
+            }
+            Err(err) => {
+                excerpt.warning = Some(format!("Can't read {}: {err}", path.display()));
+                return Some(excerpt);
+            }
+        };
+
+        let file_lines: Vec<_> = text.lines().collect();
+        let section = heading
+            .as_ref()
+            .and_then(|heading| find_section(&text, heading));
+        if let (Some(heading), None) = (heading, section) {
+            excerpt.warning = Some(format!("Heading `{heading}` not found"));
+        }
+        let (start, end) = lines.or(section).unwrap_or((1, file_lines.len()));
+        if start > file_lines.len() {
+            excerpt.warning = Some(format!(
+                "Line {start} not found, the file has {} lines",
+                file_lines.len()
+            ));
+            return Some(excerpt);
+        }
+        if end > file_lines.len() {
+            excerpt.warning = Some(format!(
+                "Lines {start}-{end} not found, the file ends at line {}",
+                file_lines.len()
+            ));
+        }
+        excerpt.first_line = start;
+        excerpt.lines = file_lines[start - 1..end.min(file_lines.len())]
+            .iter()
+            .take(MAX_EXCERPT_LINES)
+            .map(|line| line.to_string())
+            .collect();
+        Some(excerpt)
+    }
+
+    /// Hash of the lines a file source points at, `None` without a range or if the lines are
+    /// not there.
+    pub fn hash(&self) -> Option<String> {
+        let Source::File {
+            path,
+            lines: Some((start, end)),
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let text = fs::read_to_string(path).ok()?;
+        let lines: Vec<_> = text.lines().collect();
+        lines.get(start - 1..*end).map(hash_lines)
+    }
+
+    /// The command that opens this source, `editor` is used by the default file opener.
+    pub fn command(&self, openers: &Openers, editor: &str) -> Vec<String> {
+        let (template, value) = match self {
+            Source::File { path, .. } => (
+                non_empty(&openers.file).unwrap_or(DEFAULT_FILE_OPENER),
+                path.display().to_string(),
+            ),
+            Source::Url(url) => (
+                non_empty(&openers.url).unwrap_or(DEFAULT_URL_OPENER),
+                url.clone(),
+            ),
+        };
+        let line = self.line().map(|line| line.to_string());
+
+        // Placeholders are replaced after splitting, so paths with spaces stay one argument.
+        let mut args = Vec::new();
+        for arg in split_command(template) {
+            match arg.as_str() {
+                "{editor}" => args.extend(split_command(editor)),
+                // `+{line}` makes no sense without a line.
+                arg if arg.contains("{line}") && line.is_none() => {}
+                arg => args.push(
+                    arg.replace("{file}", &value)
+                        .replace("{url}", &value)
+                        .replace("{line}", line.as_deref().unwrap_or_default()),
+                ),
+            }
+        }
+        args
+    }
+}
+
+/// Longer excerpts are cut, the preview pane is no place to read a whole file.
+const MAX_EXCERPT_LINES: usize = 500;
+
+/// Lines of a local file shown in the preview pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Excerpt {
+    /// Extension or name of the file, to pick the highlighting.
+    pub language: String,
+    /// Number of the first line in `lines`.
+    pub first_line: usize,
+    pub lines: Vec<String>,
+    /// Why the excerpt is not what the source points at, e.g. the file is gone.
+    pub warning: Option<String>,
+}
+
+/// FNV-1a of the lines without trailing whitespace. It only has to be stable across versions,
+/// not secure.
+pub fn hash_lines(lines: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for line in lines {
+        for byte in line.trim_end().bytes().chain([b'\n']) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+fn parse_lines(range: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("source: `{range}` is not a line like 12 or a range like 12-20");
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start: usize = start.parse().map_err(|_| invalid())?;
+    let end: usize = end.parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Lines of the first section titled `heading` in a Markdown file.
+pub fn find_section(text: &str, heading: &str) -> Option<(usize, usize)> {
+    markdown::sections(text)
+        .into_iter()
+        .find(|section| section.title == heading)
+        .map(|section| section.lines)
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    (!text.trim().is_empty()).then_some(text)
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File {
+                path,
+                lines,
+                heading,
+            } => {
+                write!(f, "{}", path.display())?;
+                match (lines, heading) {
+                    (Some((start, end)), _) if start == end => write!(f, ":{start}"),
+                    (Some((start, end)), _) => write!(f, ":{start}-{end}"),
+                    (None, Some(heading)) => write!(f, "#{heading}"),
+                    (None, None) => Ok(()),
+                }
+            }
+            Source::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+const DEFAULT_FILE_OPENER: &str = "{editor} +{line} {file}";
+
+#[cfg(target_os = "macos")]
+const DEFAULT_URL_OPENER: &str = "open {url}";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_URL_OPENER: &str = "xdg-open {url}";
+
+/// Commands that open sources, set with `"openers"` in the config file. `{file}`, `{line}`,
+/// `{url}` and `{editor}` are replaced. Empty commands use the defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Openers {
+    /// Opens local files in the terminal, by default `{editor} +{line} {file}`.
+    #[serde(default)]
+    pub file: String,
+    /// Opens URLs in the background, by default `xdg-open {url}` or `open {url}` on macOS.
+    #[serde(default)]
+    pub url: String,
+}
+
+/// Splits a command line into arguments. Single quotes group words with spaces.
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in command.chars() {
+        match c {
+            '\'' => in_quotes = !in_quotes,
+            ' ' if !in_quotes => {
+                if !current.is_empty() {
+                    args.push(current.clone());
+                    current.clear();
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Source::parse("notes/raft.md:12-20"),
+            Ok(Source::File {
+                path: PathBuf::from("notes/raft.md"),
+                lines: Some((12, 20)),
+                heading: None,
+            })
+        );
+        assert_eq!(
+            Source::parse("~/notes/lang.md#C# basics"),
+            Ok(Source::File {
+                path: dirs::home_dir().unwrap_or_default().join("notes/lang.md"),
+                lines: None,
+                heading: Some("C# basics".to_string()),
+            })
+        );
+        assert_eq!(
+            Source::parse("file:///tmp/a.rs:3").unwrap().to_string(),
+            "/tmp/a.rs:3"
+        );
+        assert_eq!(
+            Source::parse("obsidian://open?vault=notes&file=raft"),
+            Ok(Source::Url(
+                "obsidian://open?vault=notes&file=raft".to_string()
+            ))
+        );
+        assert!(Source::parse("a.rs:20-10").is_err());
+        assert_eq!(
+            Source::parse("a.rs:x").unwrap().to_string(),
+            "a.rs:x",
+            "a colon without a line is part of the name"
+        );
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+
+        let excerpt = Source::parse(&format!("{}:2-3", path.display()))
+            .unwrap()
+            .excerpt()
+            .unwrap();
+        assert_eq!(
+            (excerpt.first_line, excerpt.lines),
+            (2, vec!["b".to_string(), "c".to_string()])
+        );
+        assert_eq!(excerpt.language, "md");
+        assert_eq!(excerpt.warning, None);
+
+        let excerpt = Source::parse(&format!("{}:3-5", path.display()))
+            .unwrap()
+            .excerpt()
+            .unwrap();
+        assert_eq!(excerpt.lines, vec!["c"]);
+        assert_eq!(
+            excerpt.warning.as_deref(),
+            Some("Lines 3-5 not found, the file ends at line 3")
+        );
+
+        let missing = Source::parse(&format!("{}:9", dir.path().join("gone.md").display()));
+        let excerpt = missing.unwrap().excerpt().unwrap();
+        assert!(excerpt.warning.unwrap().starts_with("File not found"));
+        assert_eq!(Source::parse("https://a.b").unwrap().excerpt(), None);
+
+        fs::write(&path, "# Raft\n\n## Log\nEntries\n\n## Votes\n").unwrap();
+        let source = Source::parse(&format!("{}#Log", path.display())).unwrap();
+        let excerpt = source.excerpt().unwrap();
+        assert_eq!((excerpt.first_line, excerpt.lines.len()), (3, 2));
+        assert_eq!(source.line(), Some(3));
+        let source = Source::parse(&format!("{}#Terms", path.display())).unwrap();
+        assert_eq!(
+            source.excerpt().unwrap().warning.as_deref(),
+            Some("Heading `Terms` not found")
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let openers = Openers::default();
+        let source = Source::parse("/tmp/my notes.md:7").unwrap();
+        assert_eq!(
+            source.command(&openers, "nvim -u 'my vimrc'"),
+            vec!["nvim", "-u", "my vimrc", "+7", "/tmp/my notes.md"]
+        );
+        let source = Source::parse("/tmp/a.md").unwrap();
+        assert_eq!(source.command(&openers, "vi"), vec!["vi", "/tmp/a.md"]);
+        let openers = Openers {
+            url: "firefox --new-tab {url}".to_string(),
+            ..Default::default()
+        };
+        let source = Source::parse("https://raft.github.io").unwrap();
+        assert_eq!(
+            source.command(&openers, "vi"),
+            vec!["firefox", "--new-tab", "https://raft.github.io"]
+        );
+    }
+}
@@ -1,26 +1,48 @@
 use crate::error::{ReviseError, ReviseResult};
+use crate::frontmatter;
 use crate::usecase::{Card, CardSummary, Deck, Review};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use uuid::Uuid;
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub type ID = i64;
 
+/// Selects all the columns read by [`Card::from_row`].
+const CARD_COLUMNS: &str = "
+    SELECT c.id, d.id deck_id, d.name deck_name, title, desc, tags, next_show_date, c.created_at,
+        suspended, fields, source, source_hash, note_id, c.uuid, modified_at
+    FROM cards c JOIN decks d ON c.deck_id = d.id";
+
+/// Selects all the columns read by [`Review::from_row`].
+const REVIEW_COLUMNS: &str = "
+    SELECT r.id, card_id, interval, last_interval, review_time, stability, difficulty, r.uuid
+    FROM revlog r";
+
 pub trait Store {
     fn add_deck(&self, name: &str) -> ReviseResult<()>;
     fn list_decks(&self) -> ReviseResult<Vec<Deck>>;
-    fn add_card(&self, deck_id: ID, title: &str, desc: &str) -> ReviseResult<()>;
+    fn add_card(&self, deck_id: ID, title: &str, desc: &str, tags: &[String]) -> ReviseResult<ID>;
     fn update_card(&self, id: ID, next_show_date: DateTime<Utc>) -> ReviseResult<()>;
     fn get_card(&self, id: ID) -> ReviseResult<Card>;
+    fn get_card_by_uuid(&self, uuid: &str) -> ReviseResult<Option<Card>>;
+    /// Every card with all its fields, for checks that look at the whole collection.
+    fn list_cards(&self) -> ReviseResult<Vec<Card>>;
     fn remove_card(&self, id: ID) -> ReviseResult<()>;
     fn add_review(&self, review: Review) -> ReviseResult<()>;
+    /// The review with the latest review time.
     fn get_last_review(&self, card_id: ID) -> ReviseResult<Option<Review>>;
     fn suspend_card(&self, card_id: ID) -> ReviseResult<()>;
     fn unsuspend_card(&self, card_id: ID) -> ReviseResult<()>;
     fn get_reviews(&self, card_id: ID) -> ReviseResult<Vec<Review>>;
-    fn update_card_details(&self, id: ID, title: &str, deck_id: ID, desc: &str)
-        -> ReviseResult<()>;
+    /// Saves everything but the id and uuid of `card`, and marks it modified now.
+    fn update_card_details(&self, card: &Card) -> ReviseResult<()>;
+    /// Saves the hash of the card's source without marking it modified, the source is the same.
+    fn set_source_hash(&self, card_id: ID, hash: &str) -> ReviseResult<()>;
     fn remove_orphan_decks(&self) -> ReviseResult<()>;
     fn delete_deck(&self, deck_id: ID) -> ReviseResult<()>;
     fn list_card_summaries(
@@ -29,6 +51,23 @@ pub trait Store {
         all: bool,
         is_suspended: bool,
     ) -> ReviseResult<Vec<CardSummary>>;
+
+    /// Saves a card received from another machine as it is, including its uuid and
+    /// modification time. Adds it if its id is 0. Returns its id.
+    fn save_synced_card(&self, card: &Card) -> ReviseResult<ID>;
+    /// Adds a review received from another machine unless it is already there, either way it
+    /// is not sent back. Returns whether it was added.
+    fn add_synced_review(&self, review: &Review) -> ReviseResult<bool>;
+    /// Reviews not sent to other machines yet, with the uuid of their card.
+    fn list_unsynced_reviews(&self) -> ReviseResult<Vec<(String, Review)>>;
+    fn mark_reviews_synced(&self, uuids: &[String]) -> ReviseResult<()>;
+    fn get_sync_state(&self, key: &str) -> ReviseResult<Option<String>>;
+    /// Sets `key`, or removes it if `value` is `None`.
+    fn set_sync_state(&self, key: &str, value: Option<&str>) -> ReviseResult<()>;
+    /// Keys starting with `prefix` without it, and their values.
+    fn list_sync_state(&self, prefix: &str) -> ReviseResult<Vec<(String, String)>>;
+    /// Forgets what was synced, so the next sync sends everything again.
+    fn reset_sync_state(&self) -> ReviseResult<()>;
 }
 
 pub struct SqliteStore {
@@ -38,35 +77,39 @@ pub struct SqliteStore {
 impl Store for SqliteStore {
     fn add_deck(&self, name: &str) -> ReviseResult<()> {
         let sql = "INSERT INTO decks
-        (name, created_at)
-        VALUES ($1, $2)";
+        (name, created_at, uuid)
+        VALUES ($1, $2, $3)";
 
         let now = Utc::now();
 
-        self.conn.execute(sql, params![&name, &now])?;
+        self.conn
+            .execute(sql, params![&name, &now, deck_uuid(name)])?;
 
         Ok(())
     }
 
     fn list_decks(&self) -> ReviseResult<Vec<Deck>> {
-        let sql = "SELECT id, name FROM decks";
+        let sql = "SELECT id, name, uuid FROM decks";
         let mut stmt = self.conn.prepare(sql)?;
         let rows = stmt.query_map([], Deck::from_row)?;
         let decks = rows.collect::<rusqlite::Result<Vec<Deck>>>()?;
         Ok(decks)
     }
 
-    fn add_card(&self, deck_id: ID, title: &str, desc: &str) -> ReviseResult<()> {
+    fn add_card(&self, deck_id: ID, title: &str, desc: &str, tags: &[String]) -> ReviseResult<ID> {
         let sql = "INSERT INTO cards
-        (deck_id, title, desc, next_show_date, created_at)
-        VALUES ($1, $2, $3, $4, $5)";
+        (deck_id, title, desc, tags, next_show_date, created_at, modified_at, uuid)
+        VALUES ($1, $2, $3, $4, $5, $6, $6, $7)";
 
         let now = Utc::now();
+        let uuid = Uuid::new_v4().to_string();
 
-        self.conn
-            .execute(sql, params![deck_id, &title, &desc, &now, &now])?;
+        self.conn.execute(
+            sql,
+            params![deck_id, &title, &desc, join_tags(tags), &now, &now, uuid],
+        )?;
 
-        Ok(())
+        Ok(self.conn.last_insert_rowid())
     }
 
     fn update_card(&self, id: ID, next_show_date: DateTime<Utc>) -> ReviseResult<()> {
@@ -78,27 +121,44 @@ impl Store for SqliteStore {
     }
 
     fn get_card(&self, id: i64) -> ReviseResult<Card> {
-        let sql = "
-        SELECT c.id, d.id deck_id, d.name deck_name, title, desc, next_show_date, c.created_at 
-        FROM cards c JOIN decks d ON c.deck_id = d.id where c.id = $1
-        ";
-        let mut stmt = self.conn.prepare(sql)?;
+        let sql = format!("{CARD_COLUMNS} where c.id = $1");
+        let mut stmt = self.conn.prepare(&sql)?;
         let mut rows = stmt.query_map([id], Card::from_row)?;
-        let row = rows.next().unwrap()?;
+        let row = rows.next().ok_or(ReviseError::NotFoundError(id))??;
         Ok(row)
     }
 
+    fn get_card_by_uuid(&self, uuid: &str) -> ReviseResult<Option<Card>> {
+        let sql = format!("{CARD_COLUMNS} where c.uuid = $1");
+        let card = self
+            .conn
+            .query_row(&sql, [uuid], Card::from_row)
+            .optional()?;
+        Ok(card)
+    }
+
+    fn list_cards(&self) -> ReviseResult<Vec<Card>> {
+        let sql = format!("{CARD_COLUMNS} ORDER BY c.id");
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map([], Card::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<Card>>>()?)
+    }
+
     fn remove_card(&self, id: ID) -> ReviseResult<()> {
         self.conn
-            .execute("DELETE FROM revlog WHERE card_id=$1", &[&id])?;
-        self.conn.execute("DELETE FROM cards WHERE id=$1", &[&id])?;
+            .execute("DELETE FROM revlog WHERE card_id=$1", [&id])?;
+        self.conn.execute("DELETE FROM cards WHERE id=$1", [&id])?;
 
         Ok(())
     }
 
     fn add_review(&self, review: Review) -> ReviseResult<()> {
-        let sql = "INSERT INTO revlog(card_id, last_interval, interval, review_time, stability, difficulty)
-        VALUES ($1, $2, $3, $4, $5, $6)";
+        let sql = "INSERT INTO revlog(card_id, last_interval, interval, review_time, stability, difficulty, uuid)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        let uuid = match review.uuid.as_str() {
+            "" => Uuid::new_v4().to_string(),
+            uuid => uuid.to_string(),
+        };
         self.conn.execute(
             sql,
             params![
@@ -107,7 +167,8 @@ impl Store for SqliteStore {
                 review.interval,
                 review.review_time,
                 review.stability,
-                review.difficulty
+                review.difficulty,
+                uuid
             ],
         )?;
 
@@ -115,15 +176,15 @@ impl Store for SqliteStore {
     }
 
     fn get_last_review(&self, card_id: ID) -> ReviseResult<Option<Review>> {
-        let sql = "
-        SELECT id, card_id, last_interval, interval, review_time, stability, difficulty
-        FROM revlog
+        // Reviews synced from other machines are not added in the order they were made.
+        let sql = format!(
+            "{REVIEW_COLUMNS}
         WHERE card_id = $1
-        ORDER BY id DESC
-        LIMIT 1
-        ";
+        ORDER BY review_time DESC, id DESC
+        LIMIT 1"
+        );
 
-        let mut stmt = self.conn.prepare(sql)?;
+        let mut stmt = self.conn.prepare(&sql)?;
         let rows = stmt.query_map([card_id], Review::from_row)?;
         let reviews = rows.collect::<rusqlite::Result<Vec<Review>>>()?;
         Ok(reviews.into_iter().next())
@@ -131,43 +192,63 @@ impl Store for SqliteStore {
 
     fn suspend_card(&self, card_id: ID) -> ReviseResult<()> {
         self.conn.execute(
-            "UPDATE cards SET suspended = true WHERE id = $1",
-            &[&card_id],
+            "UPDATE cards SET suspended = true, modified_at = $1 WHERE id = $2",
+            params![Utc::now(), card_id],
         )?;
         Ok(())
     }
 
     fn unsuspend_card(&self, card_id: ID) -> ReviseResult<()> {
         self.conn.execute(
-            "UPDATE cards SET suspended = false WHERE id = $1",
-            &[&card_id],
+            "UPDATE cards SET suspended = false, modified_at = $1 WHERE id = $2",
+            params![Utc::now(), card_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_source_hash(&self, card_id: ID, hash: &str) -> ReviseResult<()> {
+        self.conn.execute(
+            "UPDATE cards SET source_hash = $1 WHERE id = $2",
+            params![hash, card_id],
         )?;
         Ok(())
     }
 
     fn get_reviews(&self, card_id: ID) -> ReviseResult<Vec<Review>> {
-        let sql = "
-        SELECT id, card_id, last_interval, interval, review_time, stability, difficulty
-        FROM revlog where card_id = $1
-        ";
-        let mut stmt = self.conn.prepare(sql)?;
+        let sql = format!("{REVIEW_COLUMNS} where card_id = $1 ORDER BY review_time, id");
+        let mut stmt = self.conn.prepare(&sql)?;
         let rows = stmt.query_map([card_id], Review::from_row)?;
         let items = rows.collect::<rusqlite::Result<Vec<Review>>>()?;
         Ok(items)
     }
 
-    fn update_card_details(
-        &self,
-        id: ID,
-        title: &str,
-        deck_id: ID,
-        desc: &str,
-    ) -> ReviseResult<()> {
-        let sql = "UPDATE cards SET title=$1, deck_id=$2, desc = $3 WHERE id = $4";
-        let resp = self.conn.execute(sql, params![title, deck_id, desc, id])?;
+    fn update_card_details(&self, card: &Card) -> ReviseResult<()> {
+        let sql = "UPDATE cards
+        SET title = $1, deck_id = $2, desc = $3, tags = $4, next_show_date = $5, suspended = $6,
+            fields = $7, source = $8, source_hash = $9, note_id = $10, created_at = $11,
+            modified_at = $12
+        WHERE id = $13";
+        let resp = self.conn.execute(
+            sql,
+            params![
+                card.title,
+                card.deck_id,
+                card.desc,
+                join_tags(&card.tags),
+                card.next_show_date,
+                card.suspended,
+                join_fields(&card.fields),
+                card.source,
+                card.source_hash,
+                card.note_id,
+                card.created_at,
+                Utc::now(),
+                card.id
+            ],
+        )?;
 
         if resp == 0 {
-            return Err(ReviseError::NotFoundError(id));
+            return Err(ReviseError::NotFoundError(card.id));
         }
 
         Ok(())
@@ -185,10 +266,15 @@ impl Store for SqliteStore {
 
     fn delete_deck(&self, deck_id: ID) -> ReviseResult<()> {
         // First delete all cards in the deck
-        self.conn.execute("DELETE FROM revlog WHERE card_id IN (SELECT id FROM cards WHERE deck_id = $1)", &[&deck_id])?;
-        self.conn.execute("DELETE FROM cards WHERE deck_id = $1", &[&deck_id])?;
+        self.conn.execute(
+            "DELETE FROM revlog WHERE card_id IN (SELECT id FROM cards WHERE deck_id = $1)",
+            [&deck_id],
+        )?;
+        self.conn
+            .execute("DELETE FROM cards WHERE deck_id = $1", [&deck_id])?;
         // Then delete the deck itself
-        self.conn.execute("DELETE FROM decks WHERE id = $1", &[&deck_id])?;
+        self.conn
+            .execute("DELETE FROM decks WHERE id = $1", [&deck_id])?;
         Ok(())
     }
 
@@ -224,11 +310,139 @@ impl Store for SqliteStore {
         let items = rows.collect::<rusqlite::Result<Vec<CardSummary>>>()?;
         Ok(items)
     }
+
+    fn save_synced_card(&self, card: &Card) -> ReviseResult<ID> {
+        if card.id == 0 {
+            let sql = "INSERT INTO cards
+            (deck_id, title, desc, tags, next_show_date, created_at, suspended, fields, source,
+                source_hash, note_id, modified_at, uuid)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)";
+            self.conn.execute(
+                sql,
+                params![
+                    card.deck_id,
+                    card.title,
+                    card.desc,
+                    join_tags(&card.tags),
+                    card.next_show_date,
+                    card.created_at,
+                    card.suspended,
+                    join_fields(&card.fields),
+                    card.source,
+                    card.source_hash,
+                    card.note_id,
+                    card.modified_at,
+                    card.uuid
+                ],
+            )?;
+            return Ok(self.conn.last_insert_rowid());
+        }
+        self.update_card_details(card)?;
+        self.conn.execute(
+            "UPDATE cards SET modified_at = $1 WHERE id = $2",
+            params![card.modified_at, card.id],
+        )?;
+        Ok(card.id)
+    }
+
+    fn add_synced_review(&self, review: &Review) -> ReviseResult<bool> {
+        let sql = "INSERT OR IGNORE INTO revlog
+        (card_id, last_interval, interval, review_time, stability, difficulty, uuid, synced)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, true)";
+        let added = self.conn.execute(
+            sql,
+            params![
+                review.card_id,
+                review.last_interval,
+                review.interval,
+                review.review_time,
+                review.stability,
+                review.difficulty,
+                review.uuid
+            ],
+        )?;
+        if added == 0 {
+            self.mark_reviews_synced(std::slice::from_ref(&review.uuid))?;
+        }
+        Ok(added > 0)
+    }
+
+    fn list_unsynced_reviews(&self) -> ReviseResult<Vec<(String, Review)>> {
+        let sql = "
+        SELECT r.id, card_id, interval, last_interval, review_time, stability, difficulty, r.uuid,
+            c.uuid
+        FROM revlog r JOIN cards c ON r.card_id = c.id
+        WHERE NOT r.synced
+        ORDER BY r.id";
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| Ok((row.get(8)?, Review::from_row(row)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn mark_reviews_synced(&self, uuids: &[String]) -> ReviseResult<()> {
+        let mut stmt = self
+            .conn
+            .prepare("UPDATE revlog SET synced = true WHERE uuid = $1")?;
+        for uuid in uuids {
+            stmt.execute([uuid])?;
+        }
+        Ok(())
+    }
+
+    fn get_sync_state(&self, key: &str) -> ReviseResult<Option<String>> {
+        let value = self
+            .conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = $1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    fn set_sync_state(&self, key: &str, value: Option<&str>) -> ReviseResult<()> {
+        match value {
+            Some(value) => self.conn.execute(
+                "INSERT OR REPLACE INTO sync_state (key, value) VALUES ($1, $2)",
+                [key, value],
+            )?,
+            None => self
+                .conn
+                .execute("DELETE FROM sync_state WHERE key = $1", [key])?,
+        };
+        Ok(())
+    }
+
+    fn list_sync_state(&self, prefix: &str) -> ReviseResult<Vec<(String, String)>> {
+        let sql = "SELECT key, value FROM sync_state WHERE substr(key, 1, length($1)) = $1";
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map([prefix], |row| {
+            let key: String = row.get(0)?;
+            Ok((key[prefix.len()..].to_string(), row.get(1)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn reset_sync_state(&self) -> ReviseResult<()> {
+        self.conn.execute("DELETE FROM sync_state", [])?;
+        self.conn.execute("UPDATE revlog SET synced = false", [])?;
+        Ok(())
+    }
 }
 
 impl SqliteStore {
     pub fn new() -> Self {
-        let conn = Connection::open(data_path()).unwrap();
+        Self::open(&data_path()).unwrap()
+    }
+
+    /// Opens the database at `path`, creating and migrating it as needed.
+    pub fn open(path: &Path) -> ReviseResult<Self> {
+        let conn = Connection::open(path)?;
+        // The TUI and `serve` may use the database at the same time. Readers don't block the
+        // writer in WAL mode, and a writer waits for the other one instead of failing.
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
 
         conn.execute(
             "
@@ -238,8 +452,7 @@ impl SqliteStore {
             created_at text NOT NULL
         )",
             [],
-        )
-        .unwrap();
+        )?;
 
         conn.execute(
             "
@@ -254,14 +467,12 @@ impl SqliteStore {
             FOREIGN KEY(deck_id) REFERENCES decks(id)
         )",
             [],
-        )
-        .unwrap();
+        )?;
 
         conn.execute(
             "CREATE UNIQUE INDEX IF NOT EXISTS decks_name_key ON decks(name)",
             [],
-        )
-        .unwrap();
+        )?;
 
         conn.execute(
             "
@@ -276,11 +487,216 @@ impl SqliteStore {
             FOREIGN KEY(card_id) REFERENCES cards(id)
         )",
             [],
-        )
-        .unwrap();
+        )?;
+
+        migrate(&conn)?;
+
+        Ok(SqliteStore { conn })
+    }
+}
+
+/// How long a write waits for another process that is writing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Schema changes after the initial tables, applied in order. `PRAGMA user_version` holds the
+/// number of migrations already applied.
+const MIGRATIONS: &[fn(&Transaction) -> ReviseResult<()>] = &[
+    split_card_descs,
+    add_card_fields,
+    add_card_sources,
+    add_card_source_hashes,
+    add_card_note_ids,
+    add_sync_ids,
+];
+
+fn migrate(conn: &Connection) -> ReviseResult<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (ind, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.unchecked_transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", ind + 1)?;
+        tx.commit()?;
+        log::info!("applied migration {}", ind + 1);
+    }
+    Ok(())
+}
+
+/// Descriptions used to hold the whole editor buffer. Keep only the body and move the tags into
+/// their own column, the other frontmatter fields already have columns.
+fn split_card_descs(tx: &Transaction) -> ReviseResult<()> {
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN tags text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    let cards = tx
+        .prepare("SELECT id, desc FROM cards")?
+        .query_map([], |row| {
+            Ok((row.get::<_, ID>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, desc) in cards {
+        let (Some(_), body) = frontmatter::split(&desc) else {
+            continue;
+        };
+        // Old blocks were never validated, keep the body even if they are not valid YAML.
+        let tags = frontmatter::parse(&desc)
+            .map(|(fm, _)| fm.tags)
+            .unwrap_or_default();
+        tx.execute(
+            "UPDATE cards SET desc = $1, tags = $2 WHERE id = $3",
+            params![body, join_tags(&tags), id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Custom frontmatter fields, see [`join_fields`].
+fn add_card_fields(tx: &Transaction) -> ReviseResult<()> {
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN fields text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Where the material of a card lives, empty if it has no source.
+fn add_card_sources(tx: &Transaction) -> ReviseResult<()> {
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN source text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Hash of the lines a source points at when it was set, to notice when they change.
+fn add_card_source_hashes(tx: &Transaction) -> ReviseResult<()> {
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN source_hash text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Id of the vault note a card was synced from, see [`crate::vault`].
+fn add_card_note_ids(tx: &Transaction) -> ReviseResult<()> {
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN note_id text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Uuids that stay the same on every machine, and what is needed to sync them, see
+/// [`crate::sync`]. Existing rows get uuids derived from what they hold, so copies of one
+/// database, which is how it was shared before, don't duplicate each other on their first sync.
+fn add_sync_ids(tx: &Transaction) -> ReviseResult<()> {
+    for table in ["decks", "cards", "revlog"] {
+        tx.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN uuid text NOT NULL DEFAULT ''"),
+            [],
+        )?;
+    }
+    tx.execute(
+        "ALTER TABLE cards ADD COLUMN modified_at text NOT NULL DEFAULT ''",
+        [],
+    )?;
+    tx.execute("UPDATE cards SET modified_at = created_at", [])?;
+    tx.execute(
+        "ALTER TABLE revlog ADD COLUMN synced boolean NOT NULL DEFAULT false",
+        [],
+    )?;
+
+    let decks = tx
+        .prepare("SELECT id, name FROM decks")?
+        .query_map([], |row| {
+            Ok((row.get::<_, ID>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, name) in decks {
+        tx.execute(
+            "UPDATE decks SET uuid = $1 WHERE id = $2",
+            params![deck_uuid(&name), id],
+        )?;
+    }
+    let cards = tx
+        .prepare("SELECT id, created_at FROM cards")?
+        .query_map([], |row| {
+            Ok((row.get::<_, ID>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, created_at) in cards {
+        let uuid = Uuid::new_v5(&ID_NAMESPACE, format!("card {id} {created_at}").as_bytes());
+        tx.execute(
+            "UPDATE cards SET uuid = $1 WHERE id = $2",
+            params![uuid.to_string(), id],
+        )?;
+    }
+    let reviews = tx
+        .prepare(
+            "SELECT r.id, coalesce(c.uuid, ''), review_time
+            FROM revlog r LEFT JOIN cards c ON r.card_id = c.id",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, ID>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, card_uuid, review_time) in reviews {
+        let uuid = Uuid::new_v5(
+            &ID_NAMESPACE,
+            format!("review {id} {card_uuid} {review_time}").as_bytes(),
+        );
+        tx.execute(
+            "UPDATE revlog SET uuid = $1 WHERE id = $2",
+            params![uuid.to_string(), id],
+        )?;
+    }
+    tx.execute("CREATE UNIQUE INDEX cards_uuid_key ON cards(uuid)", [])?;
+    tx.execute("CREATE UNIQUE INDEX revlog_uuid_key ON revlog(uuid)", [])?;
+    tx.execute(
+        "
+    CREATE TABLE sync_state (
+        key text primary key,
+        value text NOT NULL
+    )",
+        [],
+    )?;
+    Ok(())
+}
 
-        SqliteStore { conn }
+/// Namespace of the uuids derived from names, see [`add_sync_ids`].
+const ID_NAMESPACE: Uuid = Uuid::from_u128(0x6a0c_2d1e_4f7b_4c59_9e3a_8b1d_5f20_c7e4);
+
+/// Decks are known by their name, two machines adding the same deck give it the same uuid.
+pub fn deck_uuid(name: &str) -> String {
+    Uuid::new_v5(&ID_NAMESPACE, format!("deck {name}").as_bytes()).to_string()
+}
+
+/// Tags are stored comma separated.
+fn join_tags(tags: &[String]) -> String {
+    tags.join(",")
+}
+
+fn split_tags(tags: String) -> Vec<String> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Custom fields are stored as a JSON list of `[key, value]` pairs to keep their order.
+fn join_fields(fields: &[(String, String)]) -> String {
+    if fields.is_empty() {
+        return String::new();
     }
+    serde_json::to_string(fields).unwrap_or_default()
+}
+
+fn split_fields(fields: String) -> Vec<(String, String)> {
+    serde_json::from_str(&fields).unwrap_or_default()
 }
 
 pub fn data_dir() -> PathBuf {
@@ -312,8 +728,16 @@ impl Card {
             deck: row.get(2)?,
             title: row.get(3)?,
             desc: row.get(4)?,
-            next_show_date: row.get(5)?,
-            created_at: row.get(6)?,
+            tags: split_tags(row.get(5)?),
+            next_show_date: row.get(6)?,
+            created_at: row.get(7)?,
+            suspended: row.get::<_, Option<bool>>(8)?.unwrap_or_default(),
+            fields: split_fields(row.get(9)?),
+            source: row.get(10)?,
+            source_hash: row.get(11)?,
+            note_id: row.get(12)?,
+            uuid: row.get(13)?,
+            modified_at: row.get(14)?,
         })
     }
 }
@@ -335,6 +759,7 @@ impl Deck {
         Ok(Deck {
             id: row.get(0)?,
             name: row.get(1)?,
+            uuid: row.get(2)?,
         })
     }
 }
@@ -349,6 +774,7 @@ impl Review {
             review_time: row.get(4)?,
             stability: row.get(5)?,
             difficulty: row.get(6)?,
+            uuid: row.get(7)?,
         })
     }
 }
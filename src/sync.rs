@@ -0,0 +1,814 @@
+//! `sync <dir>`: merges the cards and reviews of several machines through a folder they share,
+//! e.g. with Syncthing, a git repository or a USB stick.
+//!
+//! Every machine appends the changes made on it to its own log in the folder, `<device>.jsonl`,
+//! and reads the logs of the others from where it stopped the last time. Logs are never
+//! rewritten, so file sync tools never see two machines edit the same file. A line is an
+//! [`Entry`]:
+//!
+//! ```json
+//! {"v":1,"device":"9f3a1c2e7b40","time":"2024-05-01T10:00:00Z","type":"delete_card","uuid":"…"}
+//! ```
+//!
+//! Reviews are merged by uuid, so every machine ends up with all of them and a card is due after
+//! its latest review, wherever it was made. A card edited on two machines keeps the edit made
+//! last, the other one is reported as a conflict. Deleting a card wins over edits.
+//!
+//! [`sync_with_server`] exchanges the same entries with a [`crate::sync_server`] instead.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration as StdDuration,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{ReviseError, ReviseResult},
+    source::{self, Source},
+    store::{self, Store, ID},
+    sync_server::{self, Cursor, Pull, Version},
+    usecase::{Card, Deck, Review},
+};
+
+/// Version of the log format. Entries of a newer one are left for a newer revise to read.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub v: u32,
+    /// The machine the change was made on.
+    pub device: String,
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+    /// A card was added or edited.
+    Card(CardState),
+    Review(ReviewState),
+    DeleteCard {
+        uuid: String,
+    },
+}
+
+/// The fields of a card that are synced. The due date is not one of them, it follows from the
+/// reviews.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardState {
+    pub uuid: String,
+    pub deck: String,
+    pub deck_uuid: String,
+    pub title: String,
+    pub desc: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub fields: Vec<(String, String)>,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub note_id: String,
+    #[serde(default)]
+    pub suspended: bool,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+}
+
+impl CardState {
+    fn of(card: &Card) -> Self {
+        CardState {
+            uuid: card.uuid.clone(),
+            deck: card.deck.clone(),
+            deck_uuid: store::deck_uuid(&card.deck),
+            title: card.title.clone(),
+            desc: card.desc.clone(),
+            tags: card.tags.clone(),
+            fields: card.fields.clone(),
+            source: card.source.clone(),
+            note_id: card.note_id.clone(),
+            suspended: card.suspended,
+            created_at: card.created_at,
+            modified_at: card.modified_at,
+        }
+    }
+
+    /// Hash of everything but the modification time, to tell whether two states differ.
+    fn hash(&self) -> String {
+        let state = CardState {
+            modified_at: DateTime::UNIX_EPOCH,
+            ..self.clone()
+        };
+        source::hash_lines(&[&serde_json::to_string(&state).expect("a card serializes")])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewState {
+    pub uuid: String,
+    pub card_uuid: String,
+    pub review_time: DateTime<Utc>,
+    pub interval: u32,
+    pub last_interval: u32,
+    pub stability: f32,
+    pub difficulty: f32,
+}
+
+/// What a sync did, by card title.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    /// Number of changes sent to the other machines.
+    pub sent: usize,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    /// Number of reviews made on the other machines.
+    pub reviews: usize,
+    /// Cards changed on two machines, and which change was kept.
+    pub conflicts: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Name of this machine's log, kept in `device-id` next to the database so that a copy of the
+/// database on another machine gets a name of its own. It can be renamed before the first sync.
+pub fn device_id() -> ReviseResult<String> {
+    let file = store::data_path()
+        .parent()
+        .map_or_else(store::data_dir, Path::to_path_buf)
+        .join("device-id");
+    if let Ok(id) = fs::read_to_string(&file) {
+        if !id.trim().is_empty() {
+            return Ok(id.trim().to_string());
+        }
+    }
+    let id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+    fs::write(&file, &id)?;
+    Ok(id)
+}
+
+/// Syncs with the logs in `dir`, writing the changes made here to the log of `device`.
+pub fn sync_dir<S: Store>(store: &S, dir: &Path, device: &str) -> ReviseResult<Report> {
+    let dir = dir.canonicalize()?;
+    let mut report = Report::default();
+    switch_remote(store, &format!("dir:{}", dir.display()))?;
+
+    let mut entries = Vec::new();
+    let mut read = Vec::new();
+    for path in logs(&dir)? {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let key = format!("lines:{name}");
+        let skip = store
+            .get_sync_state(&key)?
+            .and_then(|lines| lines.parse().ok())
+            .unwrap_or(0);
+        let (new, lines) = read_log(&path, skip, &mut report.warnings)?;
+        entries.extend(new);
+        read.push((key, lines));
+    }
+    apply(store, &entries, &mut report)?;
+    for (key, lines) in read {
+        store.set_sync_state(&key, Some(&lines.to_string()))?;
+    }
+
+    let changes = local_changes(store, device)?;
+    if !changes.is_empty() {
+        let path = dir.join(format!("{device}.jsonl"));
+        let text: String = changes.iter().map(to_line).collect();
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        mark_sent(store, &changes)?;
+        // The lines written here were applied already.
+        let lines = fs::read_to_string(&path)?.lines().count();
+        store.set_sync_state(&format!("lines:{device}"), Some(&lines.to_string()))?;
+    }
+    report.sent = changes.len();
+    Ok(report)
+}
+
+/// Pushes to a sync server that many times at most while other machines keep pushing first.
+const PUSH_ATTEMPTS: usize = 5;
+
+/// Syncs with the collection of the user of `token` on the sync server at `url`, see
+/// [`crate::sync_server`] for the protocol.
+pub fn sync_with_server<S: Store>(
+    store: &S,
+    url: &str,
+    token: &str,
+    device: &str,
+) -> ReviseResult<Report> {
+    let url = url.trim_end_matches('/');
+    let agent = ureq::AgentBuilder::new()
+        .timeout(StdDuration::from_secs(60))
+        .build();
+    let version: Version = request(agent.get(&format!("{url}/version")), None)?;
+    if version.protocol != sync_server::PROTOCOL {
+        return Err(ReviseError::SyncError(format!(
+            "the server speaks version {} of the protocol and this revise {}, update the older one",
+            version.protocol,
+            sync_server::PROTOCOL
+        )));
+    }
+    // Another user's collection has none of what was synced.
+    let user: String = Sha256::digest(token.as_bytes())[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    switch_remote(store, &format!("server:{url} {user}"))?;
+
+    let changes_url = format!("{url}/v{}/changes", sync_server::PROTOCOL);
+    let auth = format!("Bearer {token}");
+    let mut report = Report::default();
+    for _ in 0..PUSH_ATTEMPTS {
+        let mut cursor: usize = store
+            .get_sync_state("cursor")?
+            .and_then(|cursor| cursor.parse().ok())
+            .unwrap_or(0);
+        loop {
+            let pull: Pull = request(
+                agent
+                    .get(&changes_url)
+                    .query("since", &cursor.to_string())
+                    .set("Authorization", &auth),
+                None,
+            )?;
+            if pull.cursor < cursor {
+                // The collection lost what was synced with it, e.g. the user was removed and
+                // added again on the server. It gets everything again.
+                report.warnings.push(
+                    "the collection on the server was reset, sending all cards and reviews again"
+                        .into(),
+                );
+                let remote = store.get_sync_state("remote")?;
+                store.reset_sync_state()?;
+                store.set_sync_state("remote", remote.as_deref())?;
+                cursor = 0;
+                continue;
+            }
+            let mut entries = Vec::new();
+            for change in pull.changes {
+                match parse_entry(change) {
+                    Ok(Some(entry)) => entries.push(entry),
+                    Ok(None) => report
+                        .warnings
+                        .push("skipped changes of a newer revise, update to read them".into()),
+                    Err(err) => report.warnings.push(format!("skipped a change: {err}")),
+                }
+            }
+            apply(store, &entries, &mut report)?;
+            cursor = pull.cursor;
+            store.set_sync_state("cursor", Some(&cursor.to_string()))?;
+            if !pull.more {
+                break;
+            }
+        }
+
+        let changes = local_changes(store, device)?;
+        let mut late = false;
+        for part in changes.chunks(sync_server::PUSH_SIZE) {
+            let push = json!({ "since": cursor, "changes": part });
+            let pushed: Result<Cursor, _> = request(
+                agent.post(&changes_url).set("Authorization", &auth),
+                Some(push),
+            );
+            match pushed {
+                Ok(pushed) => {
+                    mark_sent(store, part)?;
+                    report.sent += part.len();
+                    // What was pushed was applied here already.
+                    cursor = pushed.cursor;
+                    store.set_sync_state("cursor", Some(&cursor.to_string()))?;
+                }
+                Err(Failure {
+                    status: Some(409), ..
+                }) => {
+                    late = true;
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if !late {
+            report.warnings.dedup();
+            return Ok(report);
+        }
+    }
+    Err(ReviseError::SyncError(
+        "other machines keep pushing changes, try again".to_string(),
+    ))
+}
+
+/// A request to a sync server that failed, with the status if it got one.
+#[derive(Debug)]
+struct Failure {
+    status: Option<u16>,
+    message: String,
+}
+
+impl From<Failure> for ReviseError {
+    fn from(failure: Failure) -> Self {
+        ReviseError::SyncError(failure.message)
+    }
+}
+
+fn request<T: serde::de::DeserializeOwned>(
+    request: ureq::Request,
+    body: Option<Value>,
+) -> Result<T, Failure> {
+    let url = request.url().to_string();
+    let response = match body {
+        Some(body) => request.send_json(body),
+        None => request.call(),
+    };
+    let failure = |status, message| Failure { status, message };
+    match response {
+        Ok(response) => response
+            .into_json()
+            .map_err(|err| failure(None, format!("unexpected answer from {url}: {err}"))),
+        Err(ureq::Error::Status(status, response)) => {
+            let body: Value = response.into_json().unwrap_or_default();
+            let message = match (status, body["error"].as_str()) {
+                (401, _) => "the server does not know the token".to_string(),
+                (404, _) => format!("{url} is not a revise sync server"),
+                (_, Some(error)) => format!("{error} ({status})"),
+                _ => format!("{url} answered {status}"),
+            };
+            Err(failure(Some(status), message))
+        }
+        Err(err) => Err(failure(None, err.to_string())),
+    }
+}
+
+/// Forgets what was synced if the last sync was with another `remote`, which has none of it.
+pub fn switch_remote<S: Store>(store: &S, remote: &str) -> ReviseResult<()> {
+    if store.get_sync_state("remote")?.as_deref() != Some(remote) {
+        store.reset_sync_state()?;
+        store.set_sync_state("remote", Some(remote))?;
+    }
+    Ok(())
+}
+
+/// The changes made here since the last sync.
+pub fn local_changes<S: Store>(store: &S, device: &str) -> ReviseResult<Vec<Entry>> {
+    let now = Utc::now();
+    let entry = |change| Entry {
+        v: VERSION,
+        device: device.to_string(),
+        time: now,
+        change,
+    };
+    let synced: HashMap<String, String> = store.list_sync_state("card:")?.into_iter().collect();
+    let cards = store.list_cards()?;
+    let mut changes = Vec::new();
+    for card in &cards {
+        let state = CardState::of(card);
+        if synced.get(&card.uuid) != Some(&state.hash()) {
+            changes.push(entry(Change::Card(state)));
+        }
+    }
+    let uuids: HashSet<&str> = cards.iter().map(|card| card.uuid.as_str()).collect();
+    for uuid in synced.keys().filter(|uuid| !uuids.contains(uuid.as_str())) {
+        changes.push(entry(Change::DeleteCard { uuid: uuid.clone() }));
+    }
+    for (card_uuid, review) in store.list_unsynced_reviews()? {
+        changes.push(entry(Change::Review(ReviewState {
+            uuid: review.uuid,
+            card_uuid,
+            review_time: review.review_time,
+            interval: review.interval,
+            last_interval: review.last_interval,
+            stability: review.stability,
+            difficulty: review.difficulty,
+        })));
+    }
+    Ok(changes)
+}
+
+/// Remembers that `entries` from [`local_changes`] were sent, so they are not sent again.
+pub fn mark_sent<S: Store>(store: &S, entries: &[Entry]) -> ReviseResult<()> {
+    let mut reviews = Vec::new();
+    for entry in entries {
+        match &entry.change {
+            Change::Card(state) => {
+                store.set_sync_state(&format!("card:{}", state.uuid), Some(&state.hash()))?
+            }
+            Change::DeleteCard { uuid } => {
+                store.set_sync_state(&format!("card:{uuid}"), None)?;
+                store.set_sync_state(&format!("deleted:{uuid}"), Some(&entry.time.to_rfc3339()))?;
+            }
+            Change::Review(state) => reviews.push(state.uuid.clone()),
+        }
+    }
+    store.mark_reviews_synced(&reviews)
+}
+
+/// Applies changes made on other machines. Cards go first, so reviews find their card whichever
+/// log they came from.
+pub fn apply<S: Store>(store: &S, entries: &[Entry], report: &mut Report) -> ReviseResult<()> {
+    for entry in entries {
+        match &entry.change {
+            Change::Card(state) => apply_card(store, entry, state, report)?,
+            Change::DeleteCard { uuid } => delete_card(store, entry, uuid, report)?,
+            Change::Review(_) => {}
+        }
+    }
+
+    let mut reviewed = HashSet::new();
+    for entry in entries {
+        let Change::Review(state) = &entry.change else {
+            continue;
+        };
+        // Reviews of deleted cards are dropped with them.
+        let Some(card) = store.get_card_by_uuid(&state.card_uuid)? else {
+            continue;
+        };
+        let review = Review {
+            _id: 0,
+            card_id: card.id,
+            interval: state.interval,
+            last_interval: state.last_interval,
+            review_time: state.review_time,
+            stability: state.stability,
+            difficulty: state.difficulty,
+            uuid: state.uuid.clone(),
+        };
+        if store.add_synced_review(&review)? {
+            report.reviews += 1;
+            reviewed.insert(card.id);
+        }
+    }
+    for id in reviewed {
+        reschedule(store, id)?;
+    }
+    store.remove_orphan_decks()?;
+    Ok(())
+}
+
+/// Makes the card due after its latest review.
+fn reschedule<S: Store>(store: &S, id: ID) -> ReviseResult<()> {
+    if let Some(last) = store.get_last_review(id)? {
+        let next_show_date = last.review_time + Duration::days(last.interval.into());
+        store.update_card(id, next_show_date)?;
+    }
+    Ok(())
+}
+
+fn apply_card<S: Store>(
+    store: &S,
+    entry: &Entry,
+    state: &CardState,
+    report: &mut Report,
+) -> ReviseResult<()> {
+    if let Some(deleted) = store.get_sync_state(&format!("deleted:{}", state.uuid))? {
+        let deleted = DateTime::parse_from_rfc3339(&deleted).map(|time| time.to_utc());
+        if deleted.is_ok_and(|deleted| state.modified_at > deleted) {
+            report.conflicts.push(format!(
+                "{}: edited on {} after it was deleted, it stays deleted",
+                state.title, entry.device
+            ));
+        }
+        return Ok(());
+    }
+
+    let key = format!("card:{}", state.uuid);
+    let hash = state.hash();
+    match store.get_card_by_uuid(&state.uuid)? {
+        None => {
+            let mut card = Card {
+                id: 0,
+                deck_id: 0,
+                deck: String::new(),
+                title: String::new(),
+                desc: String::new(),
+                tags: Vec::new(),
+                next_show_date: state.created_at,
+                created_at: state.created_at,
+                suspended: false,
+                fields: Vec::new(),
+                source: String::new(),
+                source_hash: String::new(),
+                note_id: String::new(),
+                uuid: state.uuid.clone(),
+                modified_at: state.modified_at,
+            };
+            save_card(store, &mut card, state)?;
+            report.added.push(state.title.clone());
+        }
+        Some(mut card) => {
+            let local = CardState::of(&card).hash();
+            if local != hash {
+                let edited_here = store.get_sync_state(&key)?.as_deref() != Some(local.as_str());
+                if state.modified_at <= card.modified_at {
+                    // The edit made here is sent on, unless the one received is just old.
+                    if edited_here {
+                        report.conflicts.push(format!(
+                            "{}: edited here and on {}, kept the edit made here",
+                            card.title, entry.device
+                        ));
+                    }
+                    return Ok(());
+                }
+                if edited_here {
+                    report.conflicts.push(format!(
+                        "{}: edited here and on {}, kept the edit made there",
+                        card.title, entry.device
+                    ));
+                }
+                save_card(store, &mut card, state)?;
+                report.updated.push(state.title.clone());
+            }
+        }
+    }
+    store.set_sync_state(&key, Some(&hash))
+}
+
+/// Copies `state` onto `card` and saves it.
+fn save_card<S: Store>(store: &S, card: &mut Card, state: &CardState) -> ReviseResult<()> {
+    let find = |decks: Vec<Deck>| {
+        decks
+            .iter()
+            .find(|deck| deck.uuid == state.deck_uuid)
+            .or_else(|| decks.iter().find(|deck| deck.name == state.deck))
+            .map(|deck| deck.id)
+    };
+    card.deck_id = match find(store.list_decks()?) {
+        Some(id) => id,
+        None => {
+            store.add_deck(&state.deck)?;
+            find(store.list_decks()?).expect("the deck was just added")
+        }
+    };
+    card.deck = state.deck.clone();
+    card.title = state.title.clone();
+    card.desc = state.desc.clone();
+    card.tags = state.tags.clone();
+    card.fields = state.fields.clone();
+    if card.source != state.source {
+        card.source = state.source.clone();
+        card.source_hash = Source::parse(&card.source)
+            .ok()
+            .and_then(|source| source.hash())
+            .unwrap_or_default();
+    }
+    card.note_id = state.note_id.clone();
+    card.suspended = state.suspended;
+    card.created_at = state.created_at;
+    card.modified_at = state.modified_at;
+    card.id = store.save_synced_card(card)?;
+    Ok(())
+}
+
+fn delete_card<S: Store>(
+    store: &S,
+    entry: &Entry,
+    uuid: &str,
+    report: &mut Report,
+) -> ReviseResult<()> {
+    let key = format!("card:{uuid}");
+    let synced = store.get_sync_state(&key)?;
+    store.set_sync_state(&key, None)?;
+    store.set_sync_state(&format!("deleted:{uuid}"), Some(&entry.time.to_rfc3339()))?;
+    let Some(card) = store.get_card_by_uuid(uuid)? else {
+        return Ok(());
+    };
+    if synced != Some(CardState::of(&card).hash()) {
+        report.conflicts.push(format!(
+            "{}: deleted on {}, the edit made here is lost",
+            card.title, entry.device
+        ));
+    }
+    store.remove_card(card.id)?;
+    report.deleted.push(card.title);
+    Ok(())
+}
+
+fn to_line(entry: &Entry) -> String {
+    format!(
+        "{}\n",
+        serde_json::to_string(entry).expect("an entry serializes")
+    )
+}
+
+fn parse_line(line: &str) -> Result<Option<Entry>, String> {
+    parse_entry(serde_json::from_str(line).map_err(|err| err.to_string())?)
+}
+
+/// `Ok(None)` if the entry is of a newer version than this one.
+fn parse_entry(value: Value) -> Result<Option<Entry>, String> {
+    if value["v"].as_u64().is_some_and(|v| v > VERSION.into()) {
+        return Ok(None);
+    }
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|err| err.to_string())
+}
+
+/// The logs in `dir`, this machine's included.
+fn logs(dir: &Path) -> ReviseResult<Vec<PathBuf>> {
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "jsonl") && path.is_file() {
+            logs.push(path);
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+/// Reads the entries of a log after its first `skip` lines. Returns them with the number of
+/// lines read in all. A last line without a newline may still be being copied, it is read the
+/// next time.
+fn read_log(
+    path: &Path,
+    skip: usize,
+    warnings: &mut Vec<String>,
+) -> ReviseResult<(Vec<Entry>, usize)> {
+    let text = fs::read_to_string(path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut count = skip;
+    if skip > lines.len() {
+        warnings.push(format!(
+            "{name} is shorter than the last time, reading it again"
+        ));
+        count = 0;
+    }
+    let mut entries = Vec::new();
+    for line in &lines[count..] {
+        if !line.ends_with('\n') {
+            break;
+        }
+        count += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {
+                warnings.push(format!(
+                    "{name} has changes of a newer revise, update to read them"
+                ));
+                count -= 1;
+                break;
+            }
+            Err(err) => warnings.push(format!("{name}:{count}: {err}")),
+        }
+    }
+    Ok((entries, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+
+    fn review(store: &SqliteStore, id: ID, interval: u32) {
+        let now = Utc::now();
+        store
+            .add_review(Review {
+                _id: 0,
+                card_id: id,
+                interval,
+                last_interval: 0,
+                review_time: now,
+                stability: 1.0,
+                difficulty: 5.0,
+                uuid: String::new(),
+            })
+            .unwrap();
+        store
+            .update_card(id, now + Duration::days(interval.into()))
+            .unwrap();
+    }
+
+    fn find(store: &SqliteStore, title: &str) -> Option<Card> {
+        let cards = store.list_cards().unwrap();
+        cards.into_iter().find(|card| card.title == title)
+    }
+
+    #[test]
+    fn test_sync_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("shared");
+        fs::create_dir(&dir).unwrap();
+        let laptop = SqliteStore::open(&tmp.path().join("laptop.sqlite")).unwrap();
+        let desktop = SqliteStore::open(&tmp.path().join("desktop.sqlite")).unwrap();
+
+        laptop.add_deck("papers").unwrap();
+        let deck = laptop.list_decks().unwrap()[0].id;
+        let id = laptop
+            .add_card(deck, "Raft", "Leader election", &[])
+            .unwrap();
+        review(&laptop, id, 3);
+        let report = sync_dir(&laptop, &dir, "laptop").unwrap();
+        assert_eq!(report.sent, 2);
+
+        let report = sync_dir(&desktop, &dir, "desktop").unwrap();
+        assert_eq!(report.added, vec!["Raft"]);
+        assert_eq!(report.reviews, 1);
+        assert_eq!(report.sent, 0);
+        let raft = find(&desktop, "Raft").unwrap();
+        assert_eq!(raft.deck, "papers");
+        assert_eq!(raft.uuid, laptop.get_card(id).unwrap().uuid);
+        assert_eq!(
+            raft.next_show_date,
+            laptop.get_card(id).unwrap().next_show_date
+        );
+
+        // Both edit the card, the desktop last, and review it.
+        let mut card = laptop.get_card(id).unwrap();
+        card.desc = "Edited on the laptop".to_string();
+        laptop.update_card_details(&card).unwrap();
+        let mut card = raft.clone();
+        card.desc = "Edited on the desktop".to_string();
+        desktop.update_card_details(&card).unwrap();
+        review(&desktop, raft.id, 10);
+
+        let report = sync_dir(&laptop, &dir, "laptop").unwrap();
+        assert_eq!(report.sent, 1);
+        let report = sync_dir(&desktop, &dir, "desktop").unwrap();
+        assert_eq!(
+            report.conflicts,
+            vec!["Raft: edited here and on laptop, kept the edit made here"]
+        );
+        assert_eq!(report.sent, 2);
+        // The laptop sent its edit already, only the desktop had both.
+        let report = sync_dir(&laptop, &dir, "laptop").unwrap();
+        assert_eq!(report.conflicts, Vec::<String>::new());
+        assert_eq!(report.updated, vec!["Raft"]);
+        assert_eq!(report.reviews, 1);
+        assert_eq!(report.sent, 0);
+
+        let (raft, card) = (
+            find(&desktop, "Raft").unwrap(),
+            laptop.get_card(id).unwrap(),
+        );
+        assert_eq!(card.desc, "Edited on the desktop");
+        assert_eq!(card.next_show_date, raft.next_show_date);
+        assert_eq!(laptop.get_reviews(id).unwrap().len(), 2);
+        assert_eq!(desktop.get_reviews(raft.id).unwrap().len(), 2);
+
+        // Nothing changed, nothing to do.
+        assert_eq!(
+            sync_dir(&desktop, &dir, "desktop").unwrap(),
+            Report::default()
+        );
+
+        laptop.remove_card(id).unwrap();
+        laptop.remove_orphan_decks().unwrap();
+        assert_eq!(sync_dir(&laptop, &dir, "laptop").unwrap().sent, 1);
+        let report = sync_dir(&desktop, &dir, "desktop").unwrap();
+        assert_eq!(report.deleted, vec!["Raft"]);
+        assert!(desktop.list_cards().unwrap().is_empty());
+        assert!(desktop.list_decks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_log() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("laptop.jsonl");
+        let entry = Entry {
+            v: VERSION,
+            device: "laptop".to_string(),
+            time: Utc::now(),
+            change: Change::DeleteCard {
+                uuid: "1".to_string(),
+            },
+        };
+        let newer = r#"{"v":2,"device":"laptop","type":"merge_decks"}"#;
+        let text = format!("{}not json\n{newer}\n{}", to_line(&entry), to_line(&entry));
+        // The last line is still being copied.
+        fs::write(&path, &text[..text.len() - 3]).unwrap();
+
+        let mut warnings = Vec::new();
+        let (entries, lines) = read_log(&path, 0, &mut warnings).unwrap();
+        assert_eq!(entries, vec![entry.clone()]);
+        assert_eq!(lines, 2);
+        assert_eq!(
+            warnings,
+            vec![
+                "laptop.jsonl:2: expected ident at line 1 column 2",
+                "laptop.jsonl has changes of a newer revise, update to read them",
+            ]
+        );
+
+        fs::write(&path, to_line(&entry)).unwrap();
+        let mut warnings = Vec::new();
+        let (entries, lines) = read_log(&path, 3, &mut warnings).unwrap();
+        assert_eq!((entries.len(), lines), (1, 1));
+        assert_eq!(
+            warnings,
+            vec!["laptop.jsonl is shorter than the last time, reading it again"]
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! `sync-server`: keeps the change logs of [`crate::sync`] for several users, so their machines
+//! sync over HTTP instead of through a shared folder. Each user has a collection of their own,
+//! reached with their own token.
+//!
+//! The protocol is versioned by path, this is version [`PROTOCOL`]. `GET /version` needs no
+//! token, the other requests send `Authorization: Bearer <token>`:
+//!
+//! - `GET /v1/changes?since=N`: the changes after the first `N` of the user's collection, as
+//!   [`Pull`]. Pages hold at most [`PAGE`] changes, `more` says whether to ask again. A cursor
+//!   below `N` means the collection was reset and the client should send everything again.
+//! - `POST /v1/changes` with a [`Push`]: appends the changes if the client has seen all `since`
+//!   changes there are, and answers the new cursor. Otherwise it answers 409 with the current
+//!   cursor, the client pulls what it missed and pushes again.
+//!
+//! Changes are the [`Entry`](crate::sync::Entry) lines of the folder logs. The server keeps them
+//! as they are, so it passes on changes of newer versions than its own.
+
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{DefaultBodyLimit, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::ReviseResult;
+
+/// Version of the protocol, the prefix of its paths.
+pub const PROTOCOL: u32 = 1;
+
+/// Most changes in one pull.
+pub const PAGE: usize = 1000;
+
+/// Pushes are sent in parts of this many changes, well below the body limit.
+pub const PUSH_SIZE: usize = 500;
+
+const BODY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Answer to `GET /version`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Version {
+    pub protocol: u32,
+    pub revise: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pull {
+    /// Number of changes the client has seen after this page.
+    pub cursor: usize,
+    pub changes: Vec<Value>,
+    pub more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Push {
+    /// Number of changes the client has seen.
+    pub since: usize,
+    pub changes: Vec<Value>,
+}
+
+/// Answer to a push, and to a push that came too late with status 409.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    pub cursor: usize,
+}
+
+/// The users and their changes, in `server.sqlite` in the server's dir.
+pub struct ServerStore {
+    conn: Connection,
+}
+
+impl ServerStore {
+    pub fn open(dir: &Path) -> ReviseResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        let conn = Connection::open(dir.join("server.sqlite"))?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.execute(
+            "
+        CREATE TABLE if not exists users (
+            name text primary key,
+            token_hash text NOT NULL UNIQUE,
+            created_at text NOT NULL
+        )",
+            [],
+        )?;
+        conn.execute(
+            "
+        CREATE TABLE if not exists changes (
+            id integer primary key autoincrement,
+            user text NOT NULL,
+            body text NOT NULL,
+            received_at text NOT NULL
+        )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS changes_user_id ON changes(user, id)",
+            [],
+        )?;
+        Ok(ServerStore { conn })
+    }
+
+    /// Makes a new token for `name`, adding the user if needed. The old token stops working.
+    pub fn add_user(&self, name: &str) -> ReviseResult<String> {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        self.conn.execute(
+            "INSERT INTO users (name, token_hash, created_at) VALUES ($1, $2, $3)
+            ON CONFLICT(name) DO UPDATE SET token_hash = excluded.token_hash",
+            params![name, hash_token(&token), Utc::now()],
+        )?;
+        Ok(token)
+    }
+
+    /// Removes the user and their collection. Returns whether there was such a user.
+    pub fn remove_user(&self, name: &str) -> ReviseResult<bool> {
+        self.conn
+            .execute("DELETE FROM changes WHERE user = $1", [name])?;
+        let removed = self
+            .conn
+            .execute("DELETE FROM users WHERE name = $1", [name])?;
+        Ok(removed > 0)
+    }
+
+    pub fn find_user(&self, token: &str) -> ReviseResult<Option<String>> {
+        let user = self
+            .conn
+            .query_row(
+                "SELECT name FROM users WHERE token_hash = $1",
+                [hash_token(token)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(user)
+    }
+
+    fn count(&self, user: &str) -> ReviseResult<usize> {
+        let count = self.conn.query_row(
+            "SELECT count(*) FROM changes WHERE user = $1",
+            [user],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    /// The changes after the first `since`. A client that has seen more than there are, e.g.
+    /// because the user was removed and added again, gets a cursor below its own.
+    pub fn pull(&self, user: &str, since: usize) -> ReviseResult<Pull> {
+        let count = self.count(user)?;
+        let since = since.min(count);
+        let mut stmt = self
+            .conn
+            .prepare("SELECT body FROM changes WHERE user = $1 ORDER BY id LIMIT $2 OFFSET $3")?;
+        let changes = stmt
+            .query_map(params![user, PAGE, since], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let changes: Vec<Value> = changes
+            .iter()
+            .filter_map(|body| serde_json::from_str(body).ok())
+            .collect();
+        let cursor = since + changes.len();
+        Ok(Pull {
+            cursor,
+            more: cursor < count,
+            changes,
+        })
+    }
+
+    /// Appends `changes` if there are `since` changes, else returns the number there are.
+    pub fn push(
+        &self,
+        user: &str,
+        since: usize,
+        changes: &[Value],
+    ) -> ReviseResult<Result<usize, usize>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let count = self.count(user)?;
+        if count != since {
+            return Ok(Err(count));
+        }
+        let now = Utc::now();
+        for change in changes {
+            tx.execute(
+                "INSERT INTO changes (user, body, received_at) VALUES ($1, $2, $3)",
+                params![user, change.to_string(), now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(Ok(count + changes.len()))
+    }
+}
+
+/// Tokens are random, a plain hash keeps them out of the database.
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Serves the collections kept in `dir` on `addr` until Ctrl-C.
+pub async fn serve(dir: &Path, addr: SocketAddr) -> Result<()> {
+    let store = ServerStore::open(dir)?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    println!("Sync server listening on http://{addr}, press Ctrl-C to stop");
+    println!("Collections in {}", dir.display());
+    if !addr.ip().is_loopback() {
+        println!("Requests are plain HTTP, put a reverse proxy with TLS in front of the server");
+    }
+    axum::serve(listener, router(store))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+struct AppState {
+    store: Arc<Mutex<ServerStore>>,
+}
+
+#[derive(Clone)]
+struct User(String);
+
+fn router(store: ServerStore) -> Router {
+    let state = AppState {
+        store: Arc::new(Mutex::new(store)),
+    };
+    let v1 = Router::new()
+        .route("/changes", get(pull).post(push))
+        .layer(DefaultBodyLimit::max(BODY_LIMIT))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize));
+    Router::new()
+        .route("/version", get(version))
+        .nest(&format!("/v{PROTOCOL}"), v1)
+        .with_state(state)
+}
+
+struct ServerError(StatusCode, Value);
+
+impl ServerError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ServerError(status, json!({ "error": message.into() }))
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (self.0, Json(self.1)).into_response()
+    }
+}
+
+type ServerResult<T> = std::result::Result<T, ServerError>;
+
+/// Runs `f` on the store off the async threads.
+async fn with_store<T: Send + 'static>(
+    state: &AppState,
+    f: impl FnOnce(&ServerStore) -> ReviseResult<T> + Send + 'static,
+) -> ServerResult<T> {
+    let store = state.store.clone();
+    tokio::task::spawn_blocking(move || {
+        let store = store.lock().unwrap_or_else(|err| err.into_inner());
+        f(&store)
+    })
+    .await
+    .map_err(|err| ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+    .map_err(|err| ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+async fn authorize(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> ServerResult<Response> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let user = match token {
+        Some(token) => with_store(&state, move |store| store.find_user(&token)).await?,
+        None => None,
+    };
+    let Some(user) = user else {
+        return Err(ServerError::new(
+            StatusCode::UNAUTHORIZED,
+            "missing or unknown token",
+        ));
+    };
+    request.extensions_mut().insert(User(user));
+    Ok(next.run(request).await)
+}
+
+async fn version() -> Json<Version> {
+    Json(Version {
+        protocol: PROTOCOL,
+        revise: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+#[derive(Deserialize)]
+struct Since {
+    #[serde(default)]
+    since: usize,
+}
+
+async fn pull(
+    State(state): State<AppState>,
+    Extension(User(user)): Extension<User>,
+    Query(Since { since }): Query<Since>,
+) -> ServerResult<Json<Pull>> {
+    with_store(&state, move |store| store.pull(&user, since))
+        .await
+        .map(Json)
+}
+
+async fn push(
+    State(state): State<AppState>,
+    Extension(User(user)): Extension<User>,
+    Json(push): Json<Push>,
+) -> ServerResult<Json<Cursor>> {
+    // Other fields are up to the version of the change, these are needed to read any of them.
+    for (ind, change) in push.changes.iter().enumerate() {
+        if !(change["v"].is_u64() && change["device"].is_string()) {
+            return Err(ServerError::new(
+                StatusCode::BAD_REQUEST,
+                format!("change {ind} has no `v` or `device`"),
+            ));
+        }
+    }
+    let pushed = with_store(&state, move |store| {
+        store.push(&user, push.since, &push.changes)
+    })
+    .await?;
+    match pushed {
+        Ok(cursor) => Ok(Json(Cursor { cursor })),
+        Err(cursor) => Err(ServerError(
+            StatusCode::CONFLICT,
+            json!({ "error": "pull the changes pushed since first", "cursor": cursor }),
+        )),
+    }
+}
+
+/// Checks the name of a new user, it is shown in the server's output and nowhere else.
+pub fn check_user_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(eyre!("a user name must not be empty or have spaces"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ServerStore::open(dir.path()).unwrap();
+        let old = store.add_user("alice").unwrap();
+        let token = store.add_user("alice").unwrap();
+        let bob = store.add_user("bob").unwrap();
+        assert_eq!(store.find_user(&old).unwrap(), None);
+        assert_eq!(store.find_user(&token).unwrap().as_deref(), Some("alice"));
+
+        let change = json!({ "v": 1, "device": "laptop" });
+        let changes = std::slice::from_ref(&change);
+        assert_eq!(store.push("alice", 0, changes).unwrap(), Ok(1));
+        assert_eq!(store.push("alice", 0, changes).unwrap(), Err(1));
+        assert_eq!(store.push("alice", 1, changes).unwrap(), Ok(2));
+        let pull = store.pull("alice", 1).unwrap();
+        assert_eq!(
+            (pull.cursor, pull.changes, pull.more),
+            (2, vec![change], false)
+        );
+        assert_eq!(store.pull("bob", 0).unwrap().changes, Vec::<Value>::new());
+
+        assert!(store.remove_user("alice").unwrap());
+        assert_eq!(store.find_user(&token).unwrap(), None);
+        assert_eq!(store.find_user(&bob).unwrap().as_deref(), Some("bob"));
+        assert_eq!(store.pull("alice", 0).unwrap().cursor, 0);
+    }
+}
@@ -0,0 +1,86 @@
+//! Templates for new cards, Markdown files in the `templates` folder of the config dir.
+//!
+//! A template is picked by the `--template` flag, else by the name of the deck the card is added
+//! to, else `default.md` is used if it exists. `{{deck}}` and `{{date}}` are replaced when a card
+//! is added.
+
+use std::{fs, io, path::PathBuf};
+
+use chrono::Local;
+
+use crate::config;
+
+const DEFAULT: &str = "default";
+
+pub fn templates_dir() -> PathBuf {
+    config::get_config_dir().join("templates")
+}
+
+/// The file of the template called `name` in the templates dir.
+fn named(name: &str) -> Option<PathBuf> {
+    // Deck names may contain anything, they only name files directly in the templates dir.
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    let file = templates_dir().join(format!("{name}.md"));
+    file.is_file().then_some(file)
+}
+
+/// The file of the template given with `--template`, a name or a path to a file.
+pub fn path(name: &str) -> Option<PathBuf> {
+    let file = PathBuf::from(name);
+    if file.is_file() && file.components().count() > 1 {
+        return Some(file);
+    }
+    named(name)
+}
+
+/// Loads the template for a card in `deck`, `name` takes precedence over the deck's own template.
+/// Returns `None` when there is no template and the built-in one should be used.
+pub fn load(name: Option<&str>, deck: Option<&str>) -> io::Result<Option<String>> {
+    let file = name
+        .and_then(path)
+        .or_else(|| deck.and_then(named))
+        .or_else(|| named(DEFAULT));
+    let Some(file) = file else {
+        return Ok(None);
+    };
+    let text = fs::read_to_string(file)?;
+    Ok(Some(expand(&text, deck.unwrap_or_default())))
+}
+
+/// Replaces `{{deck}}` and `{{date}}`. Unknown placeholders are left as they are.
+fn expand(text: &str, deck: &str) -> String {
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 2];
+        result.push_str(&rest[..start]);
+        match placeholder[2..placeholder.len() - 2].trim() {
+            "deck" => result.push_str(deck),
+            "date" => result.push_str(&date),
+            _ => result.push_str(placeholder),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            expand("deck: {{deck}}\nread: {{ date }}\n{{other}} {{", "papers"),
+            format!("deck: papers\nread: {date}\n{{{{other}}}} {{{{")
+        );
+    }
+}
@@ -26,7 +26,6 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Event {
     Init,
@@ -49,7 +48,6 @@ pub struct Tui {
     pub cancellation_token: CancellationToken,
     pub event_rx: UnboundedReceiver<Event>,
     pub event_tx: UnboundedSender<Event>,
-    pub frame_rate: f64,
     pub tick_rate: f64,
     pub mouse: bool,
     pub paste: bool,
@@ -64,7 +62,6 @@ impl Tui {
             cancellation_token: CancellationToken::new(),
             event_rx,
             event_tx,
-            frame_rate: 60.0,
             tick_rate: 4.0,
             mouse: false,
             paste: false,
@@ -76,11 +73,6 @@ impl Tui {
         self
     }
 
-    pub fn frame_rate(mut self, frame_rate: f64) -> Self {
-        self.frame_rate = frame_rate;
-        self
-    }
-
     pub fn mouse(mut self, mouse: bool) -> Self {
         self.mouse = mouse;
         self
@@ -98,22 +90,23 @@ impl Tui {
             self.event_tx.clone(),
             self.cancellation_token.clone(),
             self.tick_rate,
-            self.frame_rate,
         );
         self.task = tokio::spawn(async {
             event_loop.await;
         });
     }
 
+    /// Forwards terminal input and ticks to `event_tx`.
+    ///
+    /// Rendering is not driven from here: the app redraws on its own when its state changed, so
+    /// an idle terminal only wakes up for ticks.
     async fn event_loop(
         event_tx: UnboundedSender<Event>,
         cancellation_token: CancellationToken,
         tick_rate: f64,
-        frame_rate: f64,
     ) {
         let mut event_stream = EventStream::new();
         let mut tick_interval = interval(Duration::from_secs_f64(1.0 / tick_rate));
-        let mut render_interval = interval(Duration::from_secs_f64(1.0 / frame_rate));
 
         // if this fails, then it's likely a bug in the calling code
         event_tx
@@ -125,7 +118,6 @@ impl Tui {
                     break;
                 }
                 _ = tick_interval.tick() => Event::Tick,
-                _ = render_interval.tick() => Event::Render,
                 crossterm_event = event_stream.next().fuse() => match crossterm_event {
                     Some(Ok(event)) => match event {
                         CrosstermEvent::Key(key) if key.kind == KeyEventKind::Press => Event::Key(key),
@@ -182,14 +174,7 @@ impl Tui {
         self.stop()?;
         if crossterm::terminal::is_raw_mode_enabled()? {
             self.flush()?;
-            if self.paste {
-                crossterm::execute!(stdout(), DisableBracketedPaste)?;
-            }
-            if self.mouse {
-                crossterm::execute!(stdout(), DisableMouseCapture)?;
-            }
-            crossterm::execute!(stdout(), LeaveAlternateScreen, cursor::Show)?;
-            crossterm::terminal::disable_raw_mode()?;
+            restore()?;
         }
         Ok(())
     }
@@ -231,6 +216,24 @@ impl DerefMut for Tui {
 
 impl Drop for Tui {
     fn drop(&mut self) {
-        self.exit().unwrap();
+        if let Err(err) = self.exit() {
+            error!("Unable to exit terminal: {err:?}");
+        }
     }
 }
+
+/// Puts the terminal back into a usable state for the shell.
+///
+/// This does not need a `Tui` instance, so it can be called from the panic hook and signal
+/// handlers. Every step is safe to repeat, even when the terminal was never entered.
+pub fn restore() -> Result<()> {
+    crossterm::execute!(
+        stdout(),
+        DisableBracketedPaste,
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        cursor::Show
+    )?;
+    crossterm::terminal::disable_raw_mode()?;
+    Ok(())
+}
@@ -1,68 +1,162 @@
-use crate::store::{SqliteStore, Store, ID};
-use chrono::{DateTime, Duration, Utc};
+use crate::bookmarks;
+use crate::check::{self, BrokenLink};
+use crate::error::{ReviseError, ReviseResult};
+use crate::frontmatter::{self, Frontmatter};
+use crate::markdown;
+use crate::source::{self, Openers, Source};
+use crate::store::{self, SqliteStore, Store, ID};
+use crate::sync;
+use crate::template;
+use crate::vault;
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
 use colored::*;
 use fsrs::{MemoryState, FSRS};
-use std::process::Command;
-use std::{fmt, fs};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::{fmt, fs, io};
 
 pub struct Usecase<S: Store> {
     store: S,
     editor: Option<String>,
+    /// Template for new cards given on the command line, see [`template`].
+    template: Option<String>,
+}
+
+impl Default for Usecase<SqliteStore> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Usecase<SqliteStore> {
     pub fn new() -> Self {
-        let store = SqliteStore::new();
-        Usecase { 
-            store,
-            editor: None,
-        }
+        Self::new_with_editor(None)
     }
 
     pub fn new_with_editor(editor: Option<String>) -> Self {
-        let store = SqliteStore::new();
-        Usecase { 
-            store,
+        Usecase {
             editor,
+            ..Self::with_store(SqliteStore::new())
         }
     }
 
+    /// A usecase on another database than the user's, e.g. in tests.
+    pub fn with_store(store: SqliteStore) -> Self {
+        Usecase {
+            store,
+            editor: None,
+            template: None,
+        }
+    }
+
+    pub fn with_template(mut self, template: Option<String>) -> Self {
+        self.template = template;
+        self
+    }
+
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
     fn get_editor(&self) -> String {
         match &self.editor {
             Some(cmd) => cmd.clone(),
-            None => std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string())
+            None => std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string()),
         }
     }
 
-    fn spawn_editor(&self, path: &str) {
+    /// Runs the editor on `path`. Returns false if it exited with an error, e.g. after `:cq` in
+    /// vim or a crash.
+    fn spawn_editor(&self, path: &Path) -> ReviseResult<bool> {
         let editor_cmd = self.get_editor();
-        let mut chars = editor_cmd.chars().peekable();
-        let mut args = Vec::new();
-        let mut current = String::new();
-        let mut in_quotes = false;
-
-        while let Some(c) = chars.next() {
-            match c {
-                '\'' => in_quotes = !in_quotes,
-                ' ' if !in_quotes => {
-                    if !current.is_empty() {
-                        args.push(current.clone());
-                        current.clear();
-                    }
-                },
-                _ => current.push(c),
+        let args = source::split_command(&editor_cmd);
+        let Some(program) = args.first() else {
+            return Err(ReviseError::EditorError(
+                editor_cmd,
+                io::Error::new(io::ErrorKind::InvalidInput, "editor command is empty"),
+            ));
+        };
+        let mut command = Command::new(program);
+        command.args(&args[1..]);
+        command.arg(path);
+
+        let status = command
+            .status()
+            .map_err(|err| ReviseError::EditorError(editor_cmd.clone(), err))?;
+        Ok(status.success())
+    }
+
+    /// Opens the editor on `text` until `parse` accepts it, `parse` returns `None` to cancel.
+    /// Errors are written above the buffer so the user can fix them. Quitting without saving or
+    /// with an error status, e.g. `:cq` in vim, abandons the edit.
+    ///
+    /// The buffer is a temp file only the current user can read, with a `.md` suffix so editors
+    /// pick Markdown. It is removed once the editor is done.
+    fn edit_buffer<T>(
+        &self,
+        text: &str,
+        parse: impl Fn(&str) -> Result<Option<T>, String>,
+    ) -> ReviseResult<Edited<T>> {
+        let mut file = tempfile::Builder::new()
+            .prefix("revise-card-")
+            .suffix(".md")
+            .tempfile()?;
+        file.write_all(text.as_bytes())?;
+        file.flush()?;
+        let path = file.path();
+        let _open = OpenBuffer::register(path, text);
+
+        let mut shown = text.to_string();
+        loop {
+            let success = self.spawn_editor(path)?;
+            let edited = fs::read_to_string(path)?;
+            let text = frontmatter::strip_error(&edited);
+            if !success || edited == shown {
+                return Ok(Edited::Abandoned(text.to_string()));
+            }
+            if text.trim().is_empty() {
+                return Ok(Edited::Cancelled);
+            }
+            match parse(text) {
+                Ok(Some(result)) => return Ok(Edited::Done(result)),
+                Ok(None) => return Ok(Edited::Cancelled),
+                Err(error) => {
+                    shown = frontmatter::with_error(text, &error);
+                    fs::write(path, &shown)?;
+                }
             }
         }
-        if !current.is_empty() {
-            args.push(current);
-        }
+    }
 
-        let program = args.first().expect("Editor command cannot be empty");
+    /// Opens `source` with the configured opener. Files are opened in the terminal, which must be
+    /// released first, URLs are handed to another app without waiting for it.
+    pub fn open_source(&self, source: &Source, openers: &Openers) -> ReviseResult<()> {
+        let args = source.command(openers, &self.get_editor());
+        let Some(program) = args.first() else {
+            return Err(ReviseError::OpenerError(
+                source.to_string(),
+                io::Error::new(io::ErrorKind::InvalidInput, "opener command is empty"),
+            ));
+        };
         let mut command = Command::new(program);
         command.args(&args[1..]);
-        command.arg(path);
-        
-        command.status().expect("Failed to launch editor");
+        let error = |err| ReviseError::OpenerError(source.to_string(), err);
+        if source.is_file() {
+            // Like the editor, a failing exit status just means the user is done.
+            command.status().map_err(error)?;
+        } else {
+            let mut child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(error)?;
+            std::thread::spawn(move || child.wait());
+        }
+        Ok(())
     }
 
     pub fn add_deck(&self, name: &str) {
@@ -70,123 +164,311 @@ impl Usecase<SqliteStore> {
     }
 
     pub fn list_decks(&self) -> Vec<Deck> {
-        let decks = self.store.list_decks().unwrap();
-        return decks;
-        // decks.iter().for_each(|deck| {
-        //     println!("{}.\t{}", deck.id, deck.name);
-        // });
+        self.store.list_decks().unwrap()
     }
 
-    // If no desc get the desc from neovim file
-    pub fn add_card(&self, current_deck: Option<&str>) {
-        const TMP_FILE_PATH: &str = "/tmp/revise_card.md";
-        let content = format!("---\ntitle:\ndeck: {}\n---\n", current_deck.unwrap_or_default());
-        fs::write(TMP_FILE_PATH, content).unwrap();
-
-        // Try to get desc from frontmatter
-        self.spawn_editor(TMP_FILE_PATH);
-        let desc = std::fs::read_to_string(TMP_FILE_PATH).unwrap();
-        let fm = parse_yaml_frontmatter(&desc);
-
-        let mut title = fm.get("title").map(|s| s.trim().to_string());
-        let mut deck = fm.get("deck").map(|s| s.trim().to_string());
-
-        // If title field is empty, cancel card creation
-        if title.as_ref().map_or(true, |s| s.is_empty()) {
-            std::fs::remove_file(TMP_FILE_PATH).unwrap();
-            return;
-        }
-
-        // Otherwise, keep retrying until both fields are filled
-        let (title, deck_name, desc) = loop {
-            if let (Some(t), Some(d)) = (&title, &deck) {
-                if !t.is_empty() && !d.is_empty() {
-                    break (t.clone(), d.clone(), desc.clone());
-                }
-            }
-            
-            self.spawn_editor(TMP_FILE_PATH);
-            let desc = std::fs::read_to_string(TMP_FILE_PATH).unwrap();
-            let fm = parse_yaml_frontmatter(&desc);
-            
-            title = fm.get("title").map(|s| s.trim().to_string());
-            deck = fm.get("deck").map(|s| s.trim().to_string());
-        };
-
-        std::fs::remove_file(TMP_FILE_PATH).unwrap();
-
-        // Find or create the deck
-        let deck_id = match self.list_decks().into_iter().find(|d| d.name == deck_name) {
+    /// Returns the id of the deck named `name`, creating it if needed.
+    fn find_or_create_deck(&self, name: &str) -> ID {
+        match self.list_decks().into_iter().find(|d| d.name == name) {
             Some(deck) => deck.id,
             None => {
-                self.add_deck(&deck_name);
+                self.add_deck(name);
                 self.list_decks()
                     .into_iter()
-                    .find(|d| d.name == deck_name)
+                    .find(|d| d.name == name)
                     .unwrap()
                     .id
             }
+        }
+    }
+
+    // If no desc get the desc from neovim file
+    pub fn add_card(&self, current_deck: Option<&str>) -> ReviseResult<EditOutcome> {
+        let text = match template::load(self.template(), current_deck)? {
+            Some(text) => text,
+            None => {
+                let template = Frontmatter {
+                    deck: current_deck.unwrap_or_default().to_string(),
+                    ..Default::default()
+                };
+                frontmatter::render(&template, "")
+            }
         };
+        // An empty title cancels card creation.
+        match self.edit_buffer(&text, |text| parse_card(text, true))? {
+            Edited::Done((fm, body)) => {
+                self.create_card(&fm, &body);
+                Ok(EditOutcome::saved(&fm))
+            }
+            Edited::Cancelled => Ok(EditOutcome::Cancelled),
+            Edited::Abandoned(buffer) => keep_changes(&buffer, &text),
+        }
+    }
 
-        self.store.add_card(deck_id, &title, &desc).unwrap();
+    fn create_card(&self, fm: &Frontmatter, body: &str) -> ID {
+        let deck_id = self.find_or_create_deck(&fm.deck);
+        let id = self
+            .store
+            .add_card(deck_id, &fm.title, body, &fm.tags)
+            .unwrap();
+
+        // Due date, suspension, source and custom fields may be set right away.
+        let has_details = fm.due.is_some() || fm.suspended.is_some();
+        if has_details || !fm.source.is_empty() || !fm.fields.is_empty() {
+            let mut card = self.store.get_card(id).unwrap();
+            apply_frontmatter(&mut card, fm);
+            self.store.update_card_details(&card).unwrap();
+        }
+        id
     }
 
-    pub fn list_card_summaries(&self, deck_id: Option<ID>, all: bool, is_suspended: bool) -> Vec<CardSummary> {
-        return self.store.list_card_summaries(deck_id, all, is_suspended).unwrap();
+    pub fn list_card_summaries(
+        &self,
+        deck_id: Option<ID>,
+        all: bool,
+        is_suspended: bool,
+    ) -> Vec<CardSummary> {
+        self.store
+            .list_card_summaries(deck_id, all, is_suspended)
+            .unwrap()
     }
 
-    pub fn edit_card(&self, id: ID) {
-        const TMP_FILE_PATH: &str = "/tmp/revise_card.md";
+    pub fn edit_card(&self, id: ID) -> ReviseResult<EditOutcome> {
         let card = self.store.get_card(id).unwrap();
-        fs::write(TMP_FILE_PATH, card.desc).unwrap();
-
-        let (title, deck_name, desc) = loop {
-            self.spawn_editor(TMP_FILE_PATH);
-            let desc = std::fs::read_to_string(TMP_FILE_PATH).unwrap();
-            let fm = parse_yaml_frontmatter(&desc);
-            if let Some(title) = fm.get("title") {
-                if !title.trim().is_empty() {
-                    if let Some(deck) = fm.get("deck") {
-                        if !deck.trim().is_empty() {
-                            break (
-                                title.trim().to_string(),
-                                deck.trim().to_string(),
-                                desc.to_string(),
-                            );
-                        }
-                    }
-                }
+        let text = frontmatter::render(&card_frontmatter(&card), &card.desc);
+        match self.edit_buffer(&text, |text| parse_card(text, false))? {
+            Edited::Done((fm, _)) if fm.delete => {
+                self.remove_card(id);
+                self.store.remove_orphan_decks().unwrap();
+                Ok(EditOutcome::Saved)
             }
-        };
+            Edited::Done((fm, body)) => {
+                let outcome = EditOutcome::saved(&fm);
+                self.save_card(card, &fm, body);
+                Ok(outcome)
+            }
+            Edited::Cancelled => Ok(EditOutcome::Cancelled),
+            Edited::Abandoned(buffer) => keep_changes(&buffer, &text),
+        }
+    }
 
-        std::fs::remove_file(TMP_FILE_PATH).unwrap();
+    fn save_card(&self, mut card: Card, fm: &Frontmatter, body: String) {
+        if card.deck != fm.deck {
+            card.deck_id = self.find_or_create_deck(&fm.deck);
+        }
+        card.desc = body;
+        apply_frontmatter(&mut card, fm);
 
-        let deck_id = if card.deck != deck_name {
-            match self.list_decks().into_iter().find(|d| d.name == deck_name) {
-                Some(deck) => deck.id,
-                None => {
-                    self.add_deck(&deck_name);
-                    self.list_decks()
-                        .into_iter()
-                        .find(|d| d.name == deck_name)
-                        .unwrap()
-                        .id
+        self.store.update_card_details(&card).unwrap();
+        self.store.remove_orphan_decks().unwrap();
+    }
+
+    /// Saves a card from the form in the TUI, adding it if `fm` has no id. Returns its id.
+    pub fn save_form(&self, fm: &Frontmatter, body: String) -> ID {
+        match fm.id {
+            Some(id) => {
+                self.save_card(self.store.get_card(id).unwrap(), fm, body);
+                id
+            }
+            None => self.create_card(fm, &body),
+        }
+    }
+
+    /// Opens the editor once on all cards in `ids`. Returns the changes to confirm, or how the
+    /// editor closed if the edit was cancelled, changed nothing or was kept as a draft.
+    pub fn bulk_edit(&self, ids: &[ID]) -> ReviseResult<BulkEdited> {
+        let mut text = format!(
+            "{BULK_HEADER} {} cards. Each card starts at a `---` line followed by its fields.\n\
+             # Set `delete: true` to delete a card, add a block without an id for a new card.\n\
+             # Removing a block leaves its card as it is. Save an empty file to cancel.\n",
+            ids.len()
+        );
+        for &id in ids {
+            let card = self.store.get_card(id).unwrap();
+            text.push('\n');
+            text.push_str(&frontmatter::render(&card_frontmatter(&card), &card.desc));
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+        self.edit_bulk_text(&text)
+    }
+
+    /// Opens the editor again on the document of `edit`, e.g. after reading its summary.
+    pub fn edit_bulk_again(&self, edit: &BulkEdit) -> ReviseResult<BulkEdited> {
+        self.edit_bulk_text(&edit.text)
+    }
+
+    fn edit_bulk_text(&self, text: &str) -> ReviseResult<BulkEdited> {
+        match self.edit_bulk_buffer(text)? {
+            Edited::Done(edit) => Ok(BulkEdited::Ready(edit)),
+            Edited::Cancelled => Ok(BulkEdited::Closed(EditOutcome::Cancelled)),
+            Edited::Abandoned(buffer) => Ok(BulkEdited::Closed(keep_changes(&buffer, text)?)),
+        }
+    }
+
+    fn edit_bulk_buffer(&self, text: &str) -> ReviseResult<Edited<BulkEdit>> {
+        self.edit_buffer(text, |text: &str| {
+            let edit = self.plan_bulk_edit(text)?;
+            Ok((!edit.changes.is_empty()).then_some(edit))
+        })
+    }
+
+    /// Compares the cards of a bulk edit document with the store.
+    fn plan_bulk_edit(&self, text: &str) -> Result<BulkEdit, String> {
+        let mut changes = Vec::new();
+        let mut seen = Vec::new();
+        for (fm, body) in frontmatter::parse_cards(text)? {
+            // Cards are separated by a blank line that is not part of the body.
+            let body = match body.trim_end() {
+                "" => String::new(),
+                body => format!("{body}\n"),
+            };
+            let Some(id) = fm.id else {
+                if !fm.delete {
+                    fm.validate()
+                        .map_err(|err| format!("new card `{}`: {err}", fm.title))?;
+                    changes.push(BulkChange::Add(fm, body));
                 }
+                continue;
+            };
+            if seen.contains(&id) {
+                return Err(format!("card {id} appears more than once"));
             }
-        } else {
-            card.deck_id
-        };
+            seen.push(id);
+            let card = self
+                .store
+                .get_card(id)
+                .map_err(|_| format!("there is no card with id {id}"))?;
+            if fm.delete {
+                changes.push(BulkChange::Delete(card));
+                continue;
+            }
+            fm.validate().map_err(|err| format!("card {id}: {err}"))?;
+            let fields = changed_fields(&card, &fm, &body);
+            if !fields.is_empty() {
+                changes.push(BulkChange::Update {
+                    card,
+                    fm,
+                    body,
+                    fields,
+                });
+            }
+        }
+        Ok(BulkEdit {
+            text: text.to_string(),
+            changes,
+        })
+    }
 
+    pub fn apply_bulk_edit(&self, edit: BulkEdit) {
+        for change in edit.changes {
+            match change {
+                BulkChange::Add(fm, body) => {
+                    self.create_card(&fm, &body);
+                }
+                BulkChange::Update { card, fm, body, .. } => self.save_card(card, &fm, body),
+                BulkChange::Delete(card) => self.remove_card(card.id),
+            }
+        }
         self.store.remove_orphan_decks().unwrap();
+    }
 
-        self.store
-            .update_card_details(id, &title, deck_id, &desc)
-            .unwrap();
+    /// Drafts of edits that were never saved, oldest first.
+    pub fn list_drafts(&self) -> Vec<Draft> {
+        let Ok(entries) = fs::read_dir(drafts_dir()) else {
+            return Vec::new();
+        };
+        let mut drafts: Vec<_> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "md" {
+                    return None;
+                }
+                let saved_at = fs::metadata(&path).ok()?.modified().ok()?.into();
+                let text = fs::read_to_string(&path).ok()?;
+                let text = frontmatter::strip_error(&text);
+                let bulk = text.starts_with(BULK_HEADER);
+                let title = if bulk {
+                    // "# Bulk edit of 3 cards. Each card…"
+                    text[2..].split('.').next().unwrap_or_default().to_string()
+                } else {
+                    frontmatter::parse(text)
+                        .map(|(fm, _)| fm.title)
+                        .unwrap_or_default()
+                };
+                Some(Draft {
+                    path,
+                    title,
+                    saved_at,
+                    bulk,
+                })
+            })
+            .collect();
+        drafts.sort_by_key(|draft| draft.saved_at);
+        drafts
     }
 
-    pub fn get_card(&self, id: ID) -> Card {
-        let card = self.store.get_card(id).unwrap();
-        return card;
+    /// Opens the editor on a draft. Saving it updates the card it was made for, or adds a new
+    /// card if it has no id or the card was deleted since. Emptying the file discards the draft,
+    /// it is kept if the edit is abandoned again.
+    pub fn recover_draft(&self, path: &Path) -> ReviseResult<EditOutcome> {
+        let text = fs::read_to_string(path)?;
+        match self.edit_buffer(&text, |text| parse_card(text, false))? {
+            Edited::Done((fm, body)) => {
+                match fm.id.and_then(|id| self.store.get_card(id).ok()) {
+                    Some(card) if fm.delete => {
+                        self.remove_card(card.id);
+                        self.store.remove_orphan_decks().unwrap();
+                    }
+                    Some(card) => self.save_card(card, &fm, body),
+                    None if fm.delete => {}
+                    None => {
+                        self.create_card(&fm, &body);
+                    }
+                }
+                fs::remove_file(path)?;
+                Ok(EditOutcome::saved(&fm))
+            }
+            Edited::Cancelled => {
+                fs::remove_file(path)?;
+                Ok(EditOutcome::Cancelled)
+            }
+            Edited::Abandoned(buffer) => {
+                fs::write(path, buffer)?;
+                Ok(EditOutcome::DraftKept)
+            }
+        }
+    }
+
+    /// Opens the editor on a draft of a bulk edit, see [`Usecase::recover_draft`]. The draft is
+    /// gone once its changes wait for confirmation.
+    pub fn recover_bulk_draft(&self, path: &Path) -> ReviseResult<BulkEdited> {
+        let text = fs::read_to_string(path)?;
+        match self.edit_bulk_buffer(&text)? {
+            Edited::Done(edit) => {
+                fs::remove_file(path)?;
+                Ok(BulkEdited::Ready(edit))
+            }
+            Edited::Cancelled => {
+                fs::remove_file(path)?;
+                Ok(BulkEdited::Closed(EditOutcome::Cancelled))
+            }
+            Edited::Abandoned(buffer) => {
+                fs::write(path, buffer)?;
+                Ok(BulkEdited::Closed(EditOutcome::DraftKept))
+            }
+        }
+    }
+
+    pub fn discard_draft(&self, path: &Path) -> ReviseResult<()> {
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// The card with `id`, [`ReviseError::NotFoundError`] if there is none.
+    pub fn find_card(&self, id: ID) -> ReviseResult<Card> {
+        self.store.get_card(id)
     }
 
     pub fn remove_card(&self, id: ID) {
@@ -198,6 +480,249 @@ impl Usecase<SqliteStore> {
         self.store.get_reviews(id).unwrap()
     }
 
+    /// Checks the links of all cards that are not suspended, see [`check::check`]. Line ranges
+    /// set before their content was hashed get their hash now, so later changes are noticed.
+    pub fn check_links(&self) -> ReviseResult<Vec<BrokenLink>> {
+        let mut cards = self.store.list_cards()?;
+        cards.retain(|card| !card.suspended);
+        for card in cards.iter_mut().filter(|card| card.source_hash.is_empty()) {
+            let hash = Source::parse(&card.source)
+                .ok()
+                .and_then(|source| source.hash());
+            if let Some(hash) = hash {
+                self.store.set_source_hash(card.id, &hash)?;
+                card.source_hash = hash;
+            }
+        }
+        Ok(check::check(&cards))
+    }
+
+    /// Adds and updates the cards of the notes in `dir`, see [`vault`]. Cards whose note is gone
+    /// or no longer wanted are suspended, so their reviews are kept.
+    pub fn sync_vault(&self, dir: &Path, tag: Option<&str>) -> ReviseResult<SyncReport> {
+        let dir = dir.canonicalize()?;
+        let (notes, warnings) = vault::scan(&dir, tag);
+        let mut sync = SyncReport {
+            warnings,
+            ..Default::default()
+        };
+        let cards = self.store.list_cards()?;
+        let mut seen = HashSet::new();
+        for note in notes {
+            let mut fm = Frontmatter {
+                title: note.title.clone(),
+                deck: note.deck.clone(),
+                tags: note.tags.clone(),
+                source: note.path.display().to_string(),
+                ..Default::default()
+            };
+            // A copied note carries the id of the original, it gets its own card and id.
+            let id = note.id.clone().filter(|id| !seen.contains(id));
+            let card = id
+                .as_ref()
+                .and_then(|id| cards.iter().find(|card| &card.note_id == id));
+            match card {
+                Some(card) => {
+                    // Fields are not in the note, the ones added to the card are kept. A card
+                    // suspended when its note was gone is back with the note, one the user
+                    // suspended stays so.
+                    fm.fields = card.fields.clone();
+                    let key = format!("{}{}", vault::SUSPENDED_PREFIX, card.note_id);
+                    if self.store.get_sync_state(&key)?.is_some() {
+                        fm.suspended = Some(false);
+                        self.store.set_sync_state(&key, None)?;
+                    }
+                    if !changed_fields(card, &fm, &note.body).is_empty() {
+                        self.save_card(card.clone(), &fm, note.body.clone());
+                        sync.updated.push(note.title.clone());
+                    }
+                }
+                None => {
+                    let card_id = self.create_card(&fm, &note.body);
+                    let mut card = self.store.get_card(card_id)?;
+                    card.note_id = id
+                        .clone()
+                        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                    self.store.update_card_details(&card)?;
+                    if id.is_none() {
+                        vault::write_id(&note.path, &card.note_id)?;
+                    }
+                    sync.added.push(note.title.clone());
+                    seen.insert(card.note_id);
+                    continue;
+                }
+            }
+            seen.extend(id);
+        }
+
+        for card in cards {
+            let in_vault = matches!(
+                Source::parse(&card.source),
+                Ok(Source::File { path, .. }) if path.starts_with(&dir)
+            );
+            if in_vault
+                && !card.note_id.is_empty()
+                && !card.suspended
+                && !seen.contains(&card.note_id)
+            {
+                self.store.suspend_card(card.id)?;
+                let key = format!("{}{}", vault::SUSPENDED_PREFIX, card.note_id);
+                self.store
+                    .set_sync_state(&key, Some(&card.id.to_string()))?;
+                sync.suspended.push(card.title);
+            }
+        }
+        Ok(sync)
+    }
+
+    /// Makes a card of every heading of `level` in the Markdown file at `path`, with the section
+    /// as its description and `path#heading` as its source. Cards imported before are updated,
+    /// and suspended if their heading is gone. The deck defaults to the name of the file.
+    pub fn import_headings(
+        &self,
+        path: &Path,
+        level: usize,
+        deck: Option<&str>,
+    ) -> ReviseResult<SyncReport> {
+        let path = path.canonicalize()?;
+        let text = fs::read_to_string(&path)?;
+        let deck = deck.map(str::to_string).unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        let sections = markdown::sections(&text);
+        let mut report = SyncReport::default();
+        let cards = self.store.list_cards()?;
+        let mut seen = HashSet::new();
+        for section in sections.iter().filter(|section| section.level == level) {
+            if section.title.is_empty() {
+                continue;
+            }
+            // The title is all that ties a card to its section, a second one can't be told apart.
+            if !seen.insert(section.title.as_str()) {
+                report.warnings.push(format!(
+                    "`{}` is in the file more than once, only the first is imported",
+                    section.title
+                ));
+                continue;
+            }
+            let source = format!("{}#{}", path.display(), section.title);
+            let mut fm = Frontmatter {
+                title: section.title.clone(),
+                deck: deck.clone(),
+                source: source.clone(),
+                ..Default::default()
+            };
+            match cards.iter().find(|card| card.source == source) {
+                Some(card) => {
+                    // Tags and fields are not in the file, the ones added since are kept.
+                    fm.tags = card.tags.clone();
+                    fm.fields = card.fields.clone();
+                    if !changed_fields(card, &fm, &section.body).is_empty() {
+                        self.save_card(card.clone(), &fm, section.body.clone());
+                        report.updated.push(section.title.clone());
+                    }
+                }
+                None => {
+                    self.create_card(&fm, &section.body);
+                    report.added.push(section.title.clone());
+                }
+            }
+        }
+
+        for card in cards.iter().filter(|card| !card.suspended) {
+            let Ok(Source::File {
+                path: card_path,
+                heading: Some(heading),
+                ..
+            }) = Source::parse(&card.source)
+            else {
+                continue;
+            };
+            if card_path == path && !sections.iter().any(|section| section.title == heading) {
+                self.store.suspend_card(card.id)?;
+                report.suspended.push(card.title.clone());
+            }
+        }
+        Ok(report)
+    }
+
+    /// Makes a card of every link in a browser's bookmark export that is in one of `folders`,
+    /// or of every link if none are given. The link becomes the source and the folders the deck.
+    /// Links that already are the source of a card are skipped.
+    pub fn import_bookmarks(&self, path: &Path, folders: &[String]) -> ReviseResult<SyncReport> {
+        let html = fs::read_to_string(path)?;
+        let mut report = SyncReport::default();
+        let mut sources: HashSet<String> = self
+            .store
+            .list_cards()?
+            .into_iter()
+            .map(|card| card.source)
+            .collect();
+        let bookmarks = bookmarks::parse(&html);
+        for folder in folders {
+            if !bookmarks
+                .iter()
+                .any(|b| bookmarks::in_folder(b, folder).is_some())
+            {
+                report
+                    .warnings
+                    .push(format!("No links in a folder named `{folder}`"));
+            }
+        }
+
+        for bookmark in &bookmarks {
+            let path = if folders.is_empty() {
+                Some(&bookmark.folders[..])
+            } else {
+                folders
+                    .iter()
+                    .find_map(|folder| bookmarks::in_folder(bookmark, folder))
+            };
+            let Some(path) = path else {
+                continue;
+            };
+            if !sources.insert(bookmark.url.clone()) {
+                continue;
+            }
+            let title = if bookmark.title.is_empty() {
+                bookmark.url.clone()
+            } else {
+                bookmark.title.clone()
+            };
+            let fm = Frontmatter {
+                title: title.clone(),
+                deck: if path.is_empty() {
+                    "bookmarks".to_string()
+                } else {
+                    path.join("/")
+                },
+                source: bookmark.url.clone(),
+                ..Default::default()
+            };
+            let id = self.create_card(&fm, "");
+            if let Some(added) = bookmark.added {
+                let mut card = self.store.get_card(id)?;
+                card.created_at = added;
+                self.store.update_card_details(&card)?;
+            }
+            report.added.push(title);
+        }
+        Ok(report)
+    }
+
+    /// Merges the cards and reviews with the other machines syncing through `dir`, see [`sync`].
+    pub fn sync(&self, dir: &Path) -> ReviseResult<sync::Report> {
+        sync::sync_dir(&self.store, dir, &sync::device_id()?)
+    }
+
+    /// Merges the cards and reviews with the collection of the user of `token` on a sync
+    /// server, see [`crate::sync_server`].
+    pub fn sync_with_server(&self, url: &str, token: &str) -> ReviseResult<sync::Report> {
+        sync::sync_with_server(&self.store, url, token, &sync::device_id()?)
+    }
+
     pub fn suspend_card(&self, id: ID) {
         self.store.suspend_card(id).unwrap();
     }
@@ -240,7 +765,7 @@ impl Usecase<SqliteStore> {
             ("good", next_states.good.interval.round()),
             ("easy", next_states.easy.interval.round()),
         ];
-        return dates;
+        dates
     }
 
     pub fn revise_card(&self, card_id: ID, n: usize) {
@@ -269,7 +794,7 @@ impl Usecase<SqliteStore> {
             .unwrap();
 
         if n > 4 {
-            panic!("invalid input {}", n); 
+            panic!("invalid input {}", n);
         }
 
         let next_state = match n {
@@ -288,6 +813,7 @@ impl Usecase<SqliteStore> {
             interval: next_state.interval as u32,
             last_interval: days_elapsed,
             review_time: now,
+            uuid: String::new(),
         };
 
         self.store.add_review(revision).unwrap();
@@ -296,8 +822,224 @@ impl Usecase<SqliteStore> {
     }
 }
 
+/// Reads the buffer of a single card. A new card with an empty title or `delete: true` is
+/// cancelled when `empty_title_cancels` is set.
+fn parse_card(
+    text: &str,
+    empty_title_cancels: bool,
+) -> Result<Option<(Frontmatter, String)>, String> {
+    let (fm, body) = frontmatter::parse(text)?;
+    if empty_title_cancels && (fm.title.is_empty() || fm.delete) {
+        return Ok(None);
+    }
+    fm.validate()?;
+    Ok(Some((fm, body)))
+}
+
+/// How an editor session ended.
+enum Edited<T> {
+    Done(T),
+    /// The user emptied the buffer, or the title of a new card.
+    Cancelled,
+    /// The editor failed or quit without saving, this is the buffer it left behind.
+    Abandoned(String),
+}
+
+/// First words of a bulk edit document, which tell its drafts from those of a single card.
+const BULK_HEADER: &str = "# Bulk edit of";
+
+/// How an editor session on several cards ended.
+#[derive(Debug, Clone)]
+pub enum BulkEdited {
+    /// Changes waiting for the user to confirm them.
+    Ready(BulkEdit),
+    Closed(EditOutcome),
+}
+
+/// Changes made to several cards in one editor session, applied once the user confirmed them.
+#[derive(Debug, Clone)]
+pub struct BulkEdit {
+    /// The document as saved, to edit it again.
+    text: String,
+    changes: Vec<BulkChange>,
+}
+
+#[derive(Debug, Clone)]
+enum BulkChange {
+    Add(Frontmatter, String),
+    Update {
+        card: Card,
+        fm: Frontmatter,
+        body: String,
+        fields: Vec<&'static str>,
+    },
+    Delete(Card),
+}
+
+impl BulkEdit {
+    /// One line per change, after a line with the totals.
+    pub fn summary(&self) -> Vec<String> {
+        let count = |f: fn(&BulkChange) -> bool| self.changes.iter().filter(|c| f(c)).count();
+        let mut lines = vec![format!(
+            "{} changed, {} added, {} deleted",
+            count(|c| matches!(c, BulkChange::Update { .. })),
+            count(|c| matches!(c, BulkChange::Add(..))),
+            count(|c| matches!(c, BulkChange::Delete(_))),
+        )];
+        lines.extend(self.changes.iter().map(|change| match change {
+            BulkChange::Update { card, fields, .. } => {
+                format!("~ {} {}: {}", card.id, card.title, fields.join(", "))
+            }
+            BulkChange::Add(fm, _) => format!("+ {} ({})", fm.title, fm.deck),
+            BulkChange::Delete(card) => format!("- {} {}", card.id, card.title),
+        }));
+        lines
+    }
+}
+
+/// What a vault sync or an import did, by card title.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// Cards whose note or heading is gone.
+    pub suspended: Vec<String>,
+    /// Notes or sections that could not be imported.
+    pub warnings: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.suspended.is_empty()
+            && self.warnings.is_empty()
+    }
+}
+
+/// Names of the fields `fm` and `body` would change on `card`.
+fn changed_fields(card: &Card, fm: &Frontmatter, body: &str) -> Vec<&'static str> {
+    let current_due = card.next_show_date.with_timezone(&Local).date_naive();
+    [
+        ("title", card.title != fm.title),
+        ("deck", card.deck != fm.deck),
+        ("tags", card.tags != fm.tags),
+        ("source", card.source != fm.source),
+        ("fields", card.fields != fm.fields),
+        ("due", fm.due.is_some_and(|due| due != current_due)),
+        (
+            "suspended",
+            fm.suspended.is_some_and(|s| s != card.suspended),
+        ),
+        ("body", card.desc.trim_end() != body.trim_end()),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect()
+}
+
+/// What became of an add or edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOutcome {
+    Saved,
+    /// Saved, with fields the user may not have meant, see [`Frontmatter::typos`].
+    SavedWithWarnings(Vec<String>),
+    Cancelled,
+    /// Cancelled, but the buffer had changes which were kept as a draft.
+    DraftKept,
+}
+
+impl EditOutcome {
+    fn saved(fm: &Frontmatter) -> Self {
+        let warnings = fm.typos();
+        if warnings.is_empty() {
+            EditOutcome::Saved
+        } else {
+            EditOutcome::SavedWithWarnings(warnings)
+        }
+    }
+}
+
+/// Keeps an abandoned `buffer` as a draft unless it is still the `original` text.
+fn keep_changes(buffer: &str, original: &str) -> ReviseResult<EditOutcome> {
+    if buffer == original {
+        return Ok(EditOutcome::Cancelled);
+    }
+    save_draft(buffer)?;
+    Ok(EditOutcome::DraftKept)
+}
 
-#[derive(Debug)] 
+/// Editor buffers open right now, with the text they started with.
+static OPEN_BUFFERS: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+/// Entry in [`OPEN_BUFFERS`] while the editor runs on a buffer.
+struct OpenBuffer(PathBuf);
+
+impl OpenBuffer {
+    fn register(path: &Path, original: &str) -> Self {
+        let mut buffers = OPEN_BUFFERS.lock().unwrap_or_else(|err| err.into_inner());
+        buffers.push((path.to_path_buf(), original.to_string()));
+        OpenBuffer(path.to_path_buf())
+    }
+}
+
+impl Drop for OpenBuffer {
+    fn drop(&mut self) {
+        let mut buffers = OPEN_BUFFERS.lock().unwrap_or_else(|err| err.into_inner());
+        buffers.retain(|(path, _)| *path != self.0);
+    }
+}
+
+/// Keeps the changes saved so far in open editor buffers as drafts, for when the process is
+/// terminated while the editor runs, e.g. because its terminal was closed.
+pub fn save_open_buffers() {
+    let mut buffers = OPEN_BUFFERS.lock().unwrap_or_else(|err| err.into_inner());
+    for (path, original) in buffers.drain(..) {
+        let Ok(edited) = fs::read_to_string(&path) else {
+            continue;
+        };
+        match keep_changes(frontmatter::strip_error(&edited), &original) {
+            // The temp file is not removed on exit.
+            Ok(_) => _ = fs::remove_file(&path),
+            Err(err) => eprintln!(
+                "Unable to keep the edit in {} as a draft: {err}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// An editor buffer that could not be saved, kept in the data dir until it is recovered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Draft {
+    pub path: PathBuf,
+    pub title: String,
+    pub saved_at: DateTime<Local>,
+    /// A draft of a bulk edit rather than of one card.
+    pub bulk: bool,
+}
+
+fn drafts_dir() -> PathBuf {
+    store::data_path()
+        .parent()
+        .map_or_else(store::data_dir, Path::to_path_buf)
+        .join("drafts")
+}
+
+/// Keeps `buffer` as a draft that is offered for recovery on the next start.
+fn save_draft(buffer: &str) -> ReviseResult<()> {
+    let dir = drafts_dir();
+    fs::create_dir_all(&dir)?;
+    let mut file = tempfile::Builder::new()
+        .prefix("draft-")
+        .suffix(".md")
+        .tempfile_in(dir)?;
+    file.write_all(buffer.as_bytes())?;
+    file.keep().map_err(|err| err.error)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CardSummary {
     pub id: ID,
     pub deck: String,
@@ -306,23 +1048,40 @@ pub struct CardSummary {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Card {
     pub id: ID,
     pub deck_id: ID,
     pub deck: String,
     pub title: String,
+    /// Markdown body, the other fields are only added to the frontmatter when editing.
     pub desc: String,
+    pub tags: Vec<String>,
     pub next_show_date: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    pub suspended: bool,
+    /// Custom frontmatter fields, e.g. from a template.
+    pub fields: Vec<(String, String)>,
+    /// Where the material lives, empty if unknown. See [`crate::source::Source`].
+    pub source: String,
+    /// Hash of the lines `source` pointed at when it was set, see [`Source::hash`].
+    pub source_hash: String,
+    /// `revise-id` of the vault note this card is synced from, empty for other cards.
+    pub note_id: String,
+    /// Same on every machine the card is synced to, see [`crate::sync`].
+    pub uuid: String,
+    /// When it was last changed on any machine, decides which change wins in a sync.
+    pub modified_at: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Deck {
     pub id: ID,
     pub name: String,
+    pub uuid: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Review {
     pub _id: ID,
     pub card_id: ID,
@@ -331,6 +1090,8 @@ pub struct Review {
     pub review_time: DateTime<Utc>,
     pub stability: f32,
     pub difficulty: f32,
+    /// Empty for a new review, it gets one when it is added.
+    pub uuid: String,
 }
 
 impl fmt::Display for Card {
@@ -350,22 +1111,45 @@ impl fmt::Display for Card {
     }
 }
 
-fn parse_yaml_frontmatter(s: &str) -> std::collections::HashMap<String, String> {
-    let mut frontmatter = std::collections::HashMap::new();
-    let mut in_frontmatter = false;
-
-    for line in s.lines() {
-        if line.trim() == "---" {
-            in_frontmatter = !in_frontmatter;
-            continue;
-        }
+/// The frontmatter shown when editing `card`.
+pub fn card_frontmatter(card: &Card) -> Frontmatter {
+    Frontmatter {
+        id: Some(card.id),
+        title: card.title.clone(),
+        deck: card.deck.clone(),
+        tags: card.tags.clone(),
+        due: Some(card.next_show_date.with_timezone(&Local).date_naive()),
+        suspended: Some(card.suspended),
+        delete: false,
+        fields: card.fields.clone(),
+        source: card.source.clone(),
+    }
+}
 
-        if in_frontmatter {
-            if let Some((key, value)) = line.split_once(':') {
-                frontmatter.insert(key.trim().to_string(), value.trim().to_string());
-            }
+/// Copies the edited fields onto `card`, except the deck which needs a lookup.
+fn apply_frontmatter(card: &mut Card, fm: &Frontmatter) {
+    card.title = fm.title.clone();
+    card.tags = fm.tags.clone();
+    card.fields = fm.fields.clone();
+    if card.source != fm.source {
+        card.source = fm.source.clone();
+        card.source_hash = Source::parse(&card.source)
+            .ok()
+            .and_then(|source| source.hash())
+            .unwrap_or_default();
+    }
+    if let Some(suspended) = fm.suspended {
+        card.suspended = suspended;
+    }
+    // Only a changed date moves the card, it becomes due at the start of that day.
+    let current_due = card.next_show_date.with_timezone(&Local).date_naive();
+    if let Some(due) = fm.due.filter(|due| *due != current_due) {
+        if let Some(start) = due
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+        {
+            card.next_show_date = start.with_timezone(&Utc);
         }
     }
-
-    frontmatter
 }
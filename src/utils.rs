@@ -10,12 +10,12 @@ pub fn date_to_relative_string(date: DateTime<Utc>) -> String {
     let days_diff = (Utc::now() - date).num_days();
 
     let days_diff_str = if days_diff > 0 {
-        format!("{} days ago", days_diff.to_string())
+        format!("{} days ago", days_diff)
     } else if days_diff == 0 {
-        format!("today")
+        "today".to_string()
     } else {
-        format!("in {} days", (-days_diff).to_string())
+        format!("in {} days", (-days_diff))
     };
 
-    return date_str + "  " + &days_diff_str.to_string();
+    date_str + "  " + &days_diff_str.to_string()
 }
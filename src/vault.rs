@@ -0,0 +1,235 @@
+//! Cards from a folder of Markdown notes, such as an Obsidian vault. A note becomes a card when
+//! its frontmatter has `revise: true` or a configured tag. Each synced note gets a `revise-id`
+//! in its frontmatter, so the card follows the note when it is renamed or moved.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde_yaml::{Mapping, Value};
+use walkdir::WalkDir;
+
+use crate::frontmatter;
+
+/// Frontmatter key of the id that ties a note to its card.
+pub const ID_KEY: &str = "revise-id";
+
+/// Prefix of the sync state keys, by note id, of the cards suspended because their note was
+/// gone. Only these come back when the note does.
+pub const SUSPENDED_PREFIX: &str = "vault-suspended:";
+
+/// A note that should be a card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    pub path: PathBuf,
+    /// `None` until the note was synced for the first time.
+    pub id: Option<String>,
+    pub title: String,
+    pub deck: String,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+/// Reads the notes under `dir` that should be cards. Notes that can't be read are skipped with a
+/// warning. Hidden folders such as `.obsidian` and `.git` are not searched.
+pub fn scan(dir: &Path, tag: Option<&str>) -> (Vec<Note>, Vec<String>) {
+    let mut notes = Vec::new();
+    let mut warnings = Vec::new();
+    for path in markdown_files(dir) {
+        match read_note(dir, &path, tag) {
+            Ok(Some(note)) => notes.push(note),
+            Ok(None) => {}
+            Err(err) => warnings.push(format!("{}: {err}", path.display())),
+        }
+    }
+    (notes, warnings)
+}
+
+fn markdown_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+}
+
+fn read_note(dir: &Path, path: &Path, tag: Option<&str>) -> Result<Option<Note>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let (Some(block), body) = frontmatter::split(&text) else {
+        return Ok(None);
+    };
+    // Notes have all sorts of keys, unlike cards only the ones used here are looked at.
+    let meta: Mapping = if block.trim().is_empty() {
+        Mapping::new()
+    } else {
+        serde_yaml::from_str(block).map_err(|err| format!("invalid frontmatter: {err}"))?
+    };
+    let tags = tags(meta.get("tags"));
+    let wanted = meta.get("revise").and_then(Value::as_bool) == Some(true)
+        || tag.is_some_and(|tag| tags.iter().any(|t| t == tag.trim_start_matches('#')));
+    if !wanted {
+        return Ok(None);
+    }
+
+    let text_value = |key: &str| {
+        meta.get(key).and_then(|value| match value {
+            Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    };
+    let title = text_value("title").unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    // Notes in the top folder go to a deck named after the vault.
+    let folder = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(dir).ok());
+    let deck = text_value("deck").unwrap_or_else(|| match folder {
+        Some(folder) if !folder.as_os_str().is_empty() => folder.to_string_lossy().into_owned(),
+        _ => dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "vault".to_string()),
+    });
+    Ok(Some(Note {
+        path: path.to_path_buf(),
+        id: text_value(ID_KEY),
+        title,
+        deck,
+        tags,
+        body: body.trim_start_matches('\n').to_string(),
+    }))
+}
+
+/// Tags as a list or a string separated by commas or spaces, with or without `#`.
+fn tags(value: Option<&Value>) -> Vec<String> {
+    let words: Vec<String> = match value {
+        Some(Value::Sequence(values)) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(text)) => text.split([',', ' ']).map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    words
+        .iter()
+        .map(|word| word.trim().trim_start_matches('#').to_string())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Sets `revise-id: <id>` in the note's frontmatter, replacing the id a copied note carries or
+/// adding it as the last line, and leaves the rest of the file as it was.
+pub fn write_id(path: &Path, id: &str) -> io::Result<()> {
+    let text = fs::read_to_string(path)?;
+    let (Some(block), _) = frontmatter::split(&text) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "note has no frontmatter",
+        ));
+    };
+    // The block starts after the opening `---` line.
+    let start = text.find('\n').map_or(text.len(), |ind| ind + 1);
+    let end = start + block.len();
+    let line = format!("{ID_KEY}: {id}");
+    let mut result = String::with_capacity(text.len() + line.len() + 1);
+    result.push_str(&text[..start]);
+    let mut replaced = false;
+    for old in block.split_inclusive('\n') {
+        if old.split(':').next().unwrap_or_default().trim_end() != ID_KEY {
+            result.push_str(old);
+        } else if !replaced {
+            // A second `revise-id` would make the frontmatter invalid.
+            result.push_str(&line);
+            result.push_str(if old.ends_with('\n') { "\n" } else { "" });
+            replaced = true;
+        }
+    }
+    if !replaced {
+        if !block.is_empty() && !block.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push_str(&line);
+        result.push('\n');
+    }
+    result.push_str(&text[end..]);
+    fs::write(path, result)
+}
+
+/// Paths, sizes and modification times of the notes under `dir`, to notice changes in watch
+/// mode.
+pub fn fingerprint(dir: &Path) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    markdown_files(dir)
+        .map(|path| {
+            let meta = fs::metadata(&path).ok();
+            let len = meta.as_ref().map_or(0, |meta| meta.len());
+            let modified = meta.and_then(|meta| meta.modified().ok());
+            (path, len, modified)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_and_write_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let papers = dir.path().join("papers");
+        fs::create_dir(&papers).unwrap();
+        fs::create_dir(dir.path().join(".obsidian")).unwrap();
+        fs::write(
+            papers.join("raft.md"),
+            "---\nrevise: true\ntags: [distsys]\n---\n\n# Raft\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("todo.md"),
+            "---\ntags: \"#review, misc\"\n---\nbody\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("skip.md"), "---\naliases: [x]\n---\n").unwrap();
+        fs::write(
+            dir.path().join(".obsidian/x.md"),
+            "---\nrevise: true\n---\n",
+        )
+        .unwrap();
+
+        let (notes, warnings) = scan(dir.path(), Some("#review"));
+        assert!(warnings.is_empty());
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].title, "raft");
+        assert_eq!(notes[0].deck, "papers");
+        assert_eq!(notes[0].tags, vec!["distsys"]);
+        assert_eq!(notes[0].body, "# Raft\n");
+        assert_eq!(notes[1].title, "todo");
+        assert_eq!(notes[1].tags, vec!["review", "misc"]);
+
+        write_id(&notes[0].path, "abc").unwrap();
+        assert_eq!(
+            fs::read_to_string(&notes[0].path).unwrap(),
+            "---\nrevise: true\ntags: [distsys]\nrevise-id: abc\n---\n\n# Raft\n"
+        );
+        let (notes, _) = scan(dir.path(), None);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id.as_deref(), Some("abc"));
+
+        // A copy gets an id of its own in place of the original's.
+        write_id(&notes[0].path, "def").unwrap();
+        assert_eq!(
+            fs::read_to_string(&notes[0].path).unwrap(),
+            "---\nrevise: true\ntags: [distsys]\nrevise-id: def\n---\n\n# Raft\n"
+        );
+    }
+}
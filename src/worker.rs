@@ -0,0 +1,253 @@
+//! Database access for the TUI.
+//!
+//! The UI loop never talks to SQLite directly. It sends [`Request`]s to a worker thread that owns
+//! the [`Usecase`] and answers with [`Action`]s on the regular action channel, so a slow query
+//! only delays the data it loads and never a keypress.
+
+use std::{path::PathBuf, sync::mpsc, thread};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::error;
+
+use crate::{
+    action::Action,
+    app::{CardInfo, ReviseCardDetails},
+    error::{ReviseError, ReviseResult},
+    frontmatter::Frontmatter,
+    source::{Openers, Source},
+    store::{SqliteStore, ID},
+    usecase::{BulkEdit, BulkEdited, CardSummary, Draft, Usecase},
+};
+
+/// A list of cards as shown in the card table, selected from the sidebar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardList {
+    /// Cards that are due, across all decks.
+    Review,
+    Suspended,
+    All,
+    /// Cards that are due in one deck.
+    Deck(ID),
+}
+
+#[derive(Debug)]
+pub enum Request {
+    LoadDecks,
+    /// Answered with [`Action::CardsLoaded`] carrying the same `generation`, so the app can drop
+    /// answers to lists it is no longer showing.
+    LoadCards {
+        list: CardList,
+        generation: u64,
+    },
+    LoadCardInfo(ID),
+    LoadNextDates(CardSummary),
+    ReviseCard {
+        id: ID,
+        rating: usize,
+    },
+    SuspendCard(ID),
+    UnsuspendCard(ID),
+    RemoveCard(ID),
+    DeleteDeck(ID),
+    /// Opens the editor, the terminal must be released before sending this.
+    AddCard {
+        deck: Option<String>,
+    },
+    /// Opens the editor, the terminal must be released before sending this.
+    EditCard(ID),
+    /// Opens the editor on all these cards, the terminal must be released before sending this.
+    /// Answered with [`Action::BulkEditReady`] unless the edit was cancelled.
+    BulkEdit(Vec<ID>),
+    /// Opens the editor again on the pending bulk edit, same as [`Request::BulkEdit`].
+    EditBulkAgain,
+    ApplyBulkEdit,
+    DiscardBulkEdit,
+    /// Adds or updates a card from the form in the TUI.
+    SaveCard {
+        fm: Frontmatter,
+        body: String,
+    },
+    LoadDrafts,
+    /// Opens the editor, the terminal must be released before sending this.
+    RecoverDraft(Draft),
+    DiscardDraft(PathBuf),
+    /// Opens the source of a card. For files the terminal must be released before sending this.
+    OpenSource {
+        source: Source,
+        openers: Openers,
+    },
+    /// Answered with [`Action::LinksChecked`].
+    CheckLinks,
+}
+
+/// Handle to the worker thread. The thread stops once this is dropped.
+pub struct StoreWorker {
+    tx: mpsc::Sender<Request>,
+}
+
+impl StoreWorker {
+    pub fn spawn(usecase: Usecase<SqliteStore>, action_tx: UnboundedSender<Action>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker = Worker {
+            usecase,
+            action_tx,
+            bulk_edit: None,
+        };
+        thread::spawn(move || worker.run(rx));
+        Self { tx }
+    }
+
+    pub fn send(&self, request: Request) -> Result<()> {
+        self.tx
+            .send(request)
+            .map_err(|_| eyre!("store worker has stopped"))
+    }
+}
+
+struct Worker {
+    usecase: Usecase<SqliteStore>,
+    action_tx: UnboundedSender<Action>,
+    /// Bulk edit waiting for the user to confirm its summary.
+    bulk_edit: Option<BulkEdit>,
+}
+
+impl Worker {
+    fn run(mut self, rx: mpsc::Receiver<Request>) {
+        for request in rx {
+            if let Err(err) = self.handle(request) {
+                error!("Store worker stopped: {err:?}");
+                break;
+            }
+        }
+    }
+
+    fn handle(&mut self, request: Request) -> Result<()> {
+        let action = match request {
+            Request::LoadDecks => Action::DecksLoaded(self.usecase.list_decks()),
+            Request::LoadCards { list, generation } => {
+                let cards = match list {
+                    CardList::Review => self.usecase.list_card_summaries(None, false, false),
+                    CardList::Suspended => self.usecase.list_card_summaries(None, true, true),
+                    CardList::All => self.usecase.list_card_summaries(None, true, false),
+                    CardList::Deck(id) => self.usecase.list_card_summaries(Some(id), false, false),
+                };
+                Action::CardsLoaded { generation, cards }
+            }
+            Request::LoadCardInfo(id) => match self.usecase.find_card(id) {
+                Ok(card) => {
+                    let reviews = self.usecase.get_reviews(id);
+                    let excerpt = Source::parse(&card.source)
+                        .ok()
+                        .and_then(|source| source.excerpt());
+                    Action::CardInfoLoaded(Box::new(CardInfo {
+                        card,
+                        reviews,
+                        excerpt,
+                    }))
+                }
+                // Another process such as a server or a sync removed it since it was listed.
+                Err(ReviseError::NotFoundError(_)) => Action::CardRemoved(id),
+                Err(err) => {
+                    error!("Failed to load card {id}: {err}");
+                    return Ok(());
+                }
+            },
+            Request::LoadNextDates(card) => Action::ReviseCardLoaded(ReviseCardDetails {
+                id: card.id,
+                next_dates: self.usecase.get_next_dates(&card),
+            }),
+            Request::ReviseCard { id, rating } => {
+                self.usecase.revise_card(id, rating);
+                return Ok(());
+            }
+            Request::SuspendCard(id) => {
+                self.usecase.suspend_card(id);
+                return Ok(());
+            }
+            Request::UnsuspendCard(id) => {
+                self.usecase.unsuspend_card(id);
+                return Ok(());
+            }
+            Request::RemoveCard(id) => {
+                self.usecase.remove_card(id);
+                return Ok(());
+            }
+            Request::DeleteDeck(id) => {
+                self.usecase.delete_deck(id);
+                return Ok(());
+            }
+            Request::AddCard { deck } => {
+                let result = self.usecase.add_card(deck.as_deref());
+                Action::EditorClosed(result.map_err(|err| err.to_string()))
+            }
+            Request::EditCard(id) => {
+                let result = self.usecase.edit_card(id);
+                Action::EditorClosed(result.map_err(|err| err.to_string()))
+            }
+            Request::BulkEdit(ids) => {
+                let result = self.usecase.bulk_edit(&ids);
+                self.bulk_edit_closed(result)
+            }
+            Request::EditBulkAgain => {
+                let Some(edit) = self.bulk_edit.take() else {
+                    return Ok(());
+                };
+                let result = self.usecase.edit_bulk_again(&edit);
+                self.bulk_edit_closed(result)
+            }
+            Request::ApplyBulkEdit => {
+                if let Some(edit) = self.bulk_edit.take() {
+                    self.usecase.apply_bulk_edit(edit);
+                }
+                return Ok(());
+            }
+            Request::DiscardBulkEdit => {
+                self.bulk_edit = None;
+                return Ok(());
+            }
+            Request::SaveCard { fm, body } => {
+                self.usecase.save_form(&fm, body);
+                return Ok(());
+            }
+            Request::LoadDrafts => Action::DraftsLoaded(self.usecase.list_drafts()),
+            Request::RecoverDraft(draft) if draft.bulk => {
+                let result = self.usecase.recover_bulk_draft(&draft.path);
+                self.bulk_edit_closed(result)
+            }
+            Request::RecoverDraft(draft) => {
+                let result = self.usecase.recover_draft(&draft.path);
+                Action::EditorClosed(result.map_err(|err| err.to_string()))
+            }
+            Request::OpenSource { source, openers } => Action::SourceOpened(
+                self.usecase
+                    .open_source(&source, &openers)
+                    .map_err(|err| err.to_string()),
+            ),
+            Request::CheckLinks => {
+                Action::LinksChecked(self.usecase.check_links().map_err(|err| err.to_string()))
+            }
+            Request::DiscardDraft(path) => {
+                if let Err(err) = self.usecase.discard_draft(&path) {
+                    error!("Failed to discard draft {path:?}: {err}");
+                }
+                return Ok(());
+            }
+        };
+        self.action_tx.send(action)?;
+        Ok(())
+    }
+
+    /// Keeps a bulk edit for confirmation, or reports that the editor closed without one.
+    fn bulk_edit_closed(&mut self, result: ReviseResult<BulkEdited>) -> Action {
+        match result {
+            Ok(BulkEdited::Ready(edit)) => {
+                let summary = edit.summary();
+                self.bulk_edit = Some(edit);
+                Action::BulkEditReady(summary)
+            }
+            Ok(BulkEdited::Closed(outcome)) => Action::EditorClosed(Ok(outcome)),
+            Err(err) => Action::EditorClosed(Err(err.to_string())),
+        }
+    }
+}
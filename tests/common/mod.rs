@@ -0,0 +1,56 @@
+//! Runs the `evise` binary on a database and config of its own.
+
+// Every test crate uses a part of the helpers.
+#![allow(dead_code)]
+
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+/// A server process, killed when the test ends.
+pub struct Server {
+    child: Child,
+    pub url: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// `evise` with its database and config in `dir`.
+pub fn evise(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_evise"));
+    command
+        .env("REVISE_DB_PATH", dir.join("data.sqlite"))
+        .env("HOME", dir)
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .env("XDG_CONFIG_HOME", dir.join("config"));
+    command
+}
+
+/// Starts a server that prints its URL on the first line, e.g.
+/// "Listening on http://127.0.0.1:PORT, press Ctrl-C to stop".
+pub fn start(mut command: Command) -> Server {
+    let mut child = command.stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    // The server keeps printing, it must not find the pipe closed.
+    std::thread::spawn(move || std::io::copy(&mut stdout, &mut std::io::sink()));
+    let url = line
+        .split_whitespace()
+        .find(|word| word.starts_with("http://"))
+        .unwrap_or_else(|| panic!("no address in {line:?}"))
+        .trim_end_matches(',')
+        .to_string();
+    Server { child, url }
+}
+
+pub fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().redirects(0).build()
+}
@@ -0,0 +1,172 @@
+//! Runs `evise serve --http` on a fresh database and talks to it over HTTP.
+
+use std::{path::Path, process::Command};
+
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{agent, Server};
+
+const TOKEN: &str = "secret";
+
+fn evise(dir: &Path) -> Command {
+    let mut command = common::evise(dir);
+    command.env("REVISE_HTTP_TOKEN", TOKEN);
+    command
+}
+
+fn start(dir: &Path) -> Server {
+    let mut command = evise(dir);
+    command.args(["serve", "--http", "127.0.0.1:0"]);
+    common::start(command)
+}
+
+/// Status and JSON body of an API request, errors included.
+fn api(method: &str, url: &str, body: Option<Value>) -> (u16, Value) {
+    let request = agent()
+        .request(method, url)
+        .set("Authorization", &format!("Bearer {TOKEN}"));
+    let response = match body {
+        Some(body) => request.send_json(body),
+        None => request.call(),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => panic!("{err}"),
+    };
+    let status = response.status();
+    let body = response.into_json().unwrap_or(Value::Null);
+    (status, body)
+}
+
+#[test]
+fn test_api() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = start(dir.path());
+    let url = |path: &str| format!("{}{path}", server.url);
+
+    let unauthorized = agent().get(&url("/api/decks")).call();
+    assert!(matches!(unauthorized, Err(ureq::Error::Status(401, _))));
+
+    let (status, card) = api(
+        "POST",
+        &url("/api/cards"),
+        Some(json!({ "title": "Raft", "deck": "papers", "desc": "Leader **election**" })),
+    );
+    assert_eq!(status, 201);
+    let id = card["id"].as_i64().unwrap();
+
+    let (status, _) = api(
+        "POST",
+        &url("/api/cards"),
+        Some(json!({ "title": "", "deck": "papers" })),
+    );
+    assert_eq!(status, 400);
+
+    let (status, card) = api(
+        "PATCH",
+        &url(&format!("/api/cards/{id}")),
+        Some(json!({ "tags": ["distsys"] })),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(card["tags"], json!(["distsys"]));
+    assert_eq!(card["desc"], "Leader **election**");
+
+    let (_, decks) = api("GET", &url("/api/decks"), None);
+    assert_eq!(decks[0]["name"], "papers");
+    let (_, cards) = api("GET", &url("/api/cards?deck=papers"), None);
+    assert_eq!(cards.as_array().unwrap().len(), 1);
+
+    let (status, _) = api(
+        "POST",
+        &url(&format!("/api/cards/{id}/rate")),
+        Some(json!({ "rating": 3 })),
+    );
+    assert_eq!(status, 200);
+    let (_, reviews) = api("GET", &url(&format!("/api/cards/{id}/reviews")), None);
+    assert_eq!(reviews.as_array().unwrap().len(), 1);
+    let (_, due) = api("GET", &url("/api/due"), None);
+    assert_eq!(due, json!([]));
+
+    let (status, _) = api("DELETE", &url(&format!("/api/cards/{id}")), None);
+    assert_eq!(status, 204);
+    let (status, body) = api("GET", &url(&format!("/api/cards/{id}")), None);
+    assert_eq!(status, 404);
+    assert_eq!(body["error"], format!("card {id} not found"));
+}
+
+#[test]
+fn test_review_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = start(dir.path());
+    let url = |path: &str| format!("{}{path}", server.url);
+    let (_, card) = api(
+        "POST",
+        &url("/api/cards"),
+        Some(json!({
+            "title": "Raft <b>",
+            "deck": "papers",
+            "desc": "Leader **election**, [paper](https://raft.github.io/raft.pdf)\n\n\
+                     [x](javascript:alert(1)) [y](JavaScript:alert(1)) ![z](data:text/html,hi)",
+        })),
+    );
+    let id = card["id"].as_i64().unwrap();
+
+    let wrong = agent().get(&url("/login?token=nope")).call();
+    assert!(matches!(wrong, Err(ureq::Error::Status(401, _))));
+    let login = agent()
+        .get(&url(&format!("/login?token={TOKEN}")))
+        .call()
+        .unwrap();
+    assert_eq!(login.status(), 303);
+    let cookie = login.header("set-cookie").unwrap();
+    let cookie = cookie.split(';').next().unwrap().to_string();
+
+    // New cards are due in the next second.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let page = agent()
+        .get(&url("/review"))
+        .set("Cookie", &cookie)
+        .call()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    assert!(page.contains("<h1>Raft &lt;b&gt;</h1>"), "{page}");
+    assert!(page.contains("<strong>election</strong>"));
+    assert!(page.contains(r#"<a href="https://raft.github.io/raft.pdf">paper</a>"#));
+    assert!(page.contains(r#"<a href="">x</a>"#), "{page}");
+    assert!(page.contains(r#"<img src="" alt="z" />"#), "{page}");
+    assert!(
+        !page.to_lowercase().contains("script:") && !page.contains("data:"),
+        "{page}"
+    );
+    assert!(page.contains(&format!(r#"action="/review/{id}""#)));
+
+    let rated = agent()
+        .post(&url(&format!("/review/{id}")))
+        .set("Cookie", &cookie)
+        .send_form(&[("rating", "easy")])
+        .unwrap();
+    assert_eq!(rated.status(), 303);
+    let page = agent()
+        .get(&url("/review"))
+        .set("Cookie", &cookie)
+        .call()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    assert!(page.contains("Nothing to review"));
+}
+
+#[test]
+fn test_localhost_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = evise(dir.path())
+        .args(["serve", "--http", "0.0.0.0:0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("only listens on localhost"));
+}
@@ -0,0 +1,243 @@
+//! Runs `evise sync-server` on localhost and syncs separate machines, each a database of its
+//! own, with it.
+
+use std::{fs, path::Path};
+
+use serde_json::{json, Value};
+
+mod common;
+
+use common::{agent, evise, Server};
+
+fn start(dir: &Path) -> Server {
+    let mut command = evise(dir);
+    command.args(["sync-server", "--addr", "127.0.0.1:0", "--dir"]);
+    command.arg(dir.join("server"));
+    common::start(command)
+}
+
+/// "Token for alice: TOKEN"
+fn add_user(dir: &Path, name: &str) -> String {
+    let output = evise(dir)
+        .args(["sync-server", "--dir"])
+        .arg(dir.join("server"))
+        .args(["add-user", name])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout.lines().next().unwrap();
+    line.split_whitespace().last().unwrap().to_string()
+}
+
+/// Runs `evise` on `machine` and returns its output, which must succeed.
+fn run(machine: &Path, args: &[&str]) -> String {
+    let output = evise(machine).args(args).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn query(machine: &Path, sql: &str) -> Vec<String> {
+    let conn = rusqlite::Connection::open(machine.join("data.sqlite")).unwrap();
+    let mut stmt = conn.prepare(sql).unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<rusqlite::Result<_>>().unwrap()
+}
+
+/// Status and JSON body of a protocol request, errors included.
+fn call(request: ureq::Request, body: Option<Value>) -> (u16, Value) {
+    let response = match body {
+        Some(body) => request.send_json(body),
+        None => request.call(),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => panic!("{err}"),
+    };
+    let status = response.status();
+    (status, response.into_json().unwrap_or(Value::Null))
+}
+
+#[test]
+fn test_sync_machines() {
+    let dir = tempfile::tempdir().unwrap();
+    let alice = add_user(dir.path(), "alice");
+    let bob = add_user(dir.path(), "bob");
+    let server = start(dir.path());
+    let url = server.url.as_str();
+    let (laptop, desktop, other) = (
+        dir.path().join("laptop"),
+        dir.path().join("desktop"),
+        dir.path().join("other"),
+    );
+    for machine in [&laptop, &desktop, &other] {
+        fs::create_dir(machine).unwrap();
+    }
+
+    let notes = dir.path().join("notes.md");
+    fs::write(&notes, "## Raft\nLeader election\n\n## Paxos\nSynod\n").unwrap();
+    let notes = notes.to_str().unwrap();
+    run(&laptop, &["import", "headings", notes, "--deck", "papers"]);
+    let output = run(&laptop, &["sync", url, "--token", &alice]);
+    assert!(
+        output.contains("0 reviews received, 2 changes sent"),
+        "{output}"
+    );
+
+    let output = run(&desktop, &["sync", url, "--token", &alice]);
+    assert!(
+        output.contains("Raft") && output.contains("Paxos"),
+        "{output}"
+    );
+    let sql = "SELECT title FROM cards ORDER BY title";
+    assert_eq!(query(&desktop, sql), vec!["Paxos", "Raft"]);
+
+    // An edit on the laptop, and a review another machine pushed for the desktop's Raft card.
+    fs::write(
+        notes,
+        "## Raft\nLeader election and log replication\n\n## Paxos\nSynod\n",
+    )
+    .unwrap();
+    run(&laptop, &["import", "headings", notes, "--deck", "papers"]);
+    run(&laptop, &["sync", url, "--token", &alice]);
+    let raft = query(&desktop, "SELECT uuid FROM cards WHERE title = 'Raft'").remove(0);
+    let review = json!({
+        "v": 1, "device": "phone", "time": "2024-05-01T10:00:00Z", "type": "review",
+        "uuid": "5b0c8c38-8f61-4a4f-a0f5-8a4d1d7a6f51", "card_uuid": raft,
+        "review_time": "2024-05-01T10:00:00Z", "interval": 7, "last_interval": 0,
+        "stability": 7.0, "difficulty": 5.0,
+    });
+    let changes = format!("{url}/v1/changes");
+    let (_, pull) = call(
+        agent()
+            .get(&changes)
+            .set("Authorization", &format!("Bearer {alice}")),
+        None,
+    );
+    let (status, _) = call(
+        agent()
+            .post(&changes)
+            .set("Authorization", &format!("Bearer {alice}")),
+        Some(json!({ "since": pull["cursor"], "changes": [review] })),
+    );
+    assert_eq!(status, 200);
+
+    let output = run(&desktop, &["sync", url, "--token", &alice]);
+    assert!(output.contains("1 reviews received"), "{output}");
+    let sql = "SELECT desc FROM cards WHERE title = 'Raft'";
+    assert_eq!(
+        query(&desktop, sql),
+        vec!["Leader election and log replication"]
+    );
+    let sql = "SELECT next_show_date FROM cards WHERE title = 'Raft'";
+    assert!(query(&desktop, sql)[0].starts_with("2024-05-08"));
+
+    // Bob's collection is his own.
+    let output = run(&other, &["sync", url, "--token", &bob]);
+    assert!(
+        output.contains("0 reviews received, 0 changes sent"),
+        "{output}"
+    );
+    assert!(query(&other, "SELECT title FROM cards").is_empty());
+
+    let output = evise(&other)
+        .args(["sync", url, "--token", "nope"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not know the token"));
+}
+
+#[test]
+fn test_protocol() {
+    let dir = tempfile::tempdir().unwrap();
+    let token = add_user(dir.path(), "alice");
+    let server = start(dir.path());
+    let url = |path: &str| format!("{}{path}", server.url);
+    let auth = format!("Bearer {token}");
+
+    let (status, version) = call(agent().get(&url("/version")), None);
+    assert_eq!(status, 200);
+    assert_eq!(version["protocol"], 1);
+
+    let (status, _) = call(agent().get(&url("/v1/changes")), None);
+    assert_eq!(status, 401);
+    let wrong = agent()
+        .get(&url("/v1/changes"))
+        .set("Authorization", "Bearer nope");
+    assert_eq!(call(wrong, None).0, 401);
+    let (status, _) = call(
+        agent().get(&url("/v2/changes")).set("Authorization", &auth),
+        None,
+    );
+    assert_eq!(status, 404);
+
+    let change = json!({ "v": 1, "device": "laptop", "type": "delete_card", "uuid": "1" });
+    let push = |body: Value| {
+        call(
+            agent()
+                .post(&url("/v1/changes"))
+                .set("Authorization", &auth),
+            Some(body),
+        )
+    };
+    let (status, body) = push(json!({ "since": 0, "changes": [change, change] }));
+    assert_eq!((status, body), (200, json!({ "cursor": 2 })));
+    // Another machine pushed in between.
+    let (status, body) = push(json!({ "since": 1, "changes": [change] }));
+    assert_eq!(status, 409);
+    assert_eq!(body["cursor"], 2);
+    let (status, body) = push(json!({ "since": 2, "changes": [{ "type": "card" }] }));
+    assert_eq!(status, 400);
+    assert_eq!(body["error"], "change 0 has no `v` or `device`");
+
+    let pull = agent()
+        .get(&url("/v1/changes"))
+        .query("since", "1")
+        .set("Authorization", &auth);
+    let (status, body) = call(pull, None);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        json!({ "cursor": 2, "changes": [change], "more": false })
+    );
+    // A client that saw more than there is, the collection was reset.
+    let pull = agent()
+        .get(&url("/v1/changes"))
+        .query("since", "5")
+        .set("Authorization", &auth);
+    assert_eq!(call(pull, None).1["cursor"], 2);
+}
+
+#[test]
+fn test_collection_reset() {
+    let dir = tempfile::tempdir().unwrap();
+    let token = add_user(dir.path(), "alice");
+    let server = start(dir.path());
+    let url = server.url.as_str();
+    let (laptop, desktop) = (dir.path().join("laptop"), dir.path().join("desktop"));
+    for machine in [&laptop, &desktop] {
+        fs::create_dir(machine).unwrap();
+    }
+    let notes = dir.path().join("notes.md");
+    fs::write(&notes, "## Raft\nLeader election\n\n## Paxos\nSynod\n").unwrap();
+    let notes = notes.to_str().unwrap();
+    run(&laptop, &["import", "headings", notes, "--deck", "papers"]);
+    run(&laptop, &["sync", url, "--token", &token]);
+
+    // The server is restored from a backup made before the laptop's first sync.
+    let conn = rusqlite::Connection::open(dir.path().join("server/server.sqlite")).unwrap();
+    conn.execute("DELETE FROM changes", []).unwrap();
+    let output = run(&laptop, &["sync", url, "--token", &token]);
+    assert!(output.contains("2 changes sent"), "{output}");
+    assert!(
+        output.contains("collection on the server was reset"),
+        "{output}"
+    );
+
+    run(&desktop, &["sync", url, "--token", &token]);
+    let sql = "SELECT title FROM cards ORDER BY title";
+    assert_eq!(query(&desktop, sql), vec!["Paxos", "Raft"]);
+}
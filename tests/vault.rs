@@ -0,0 +1,85 @@
+//! Runs `evise vault sync` on a folder of notes.
+
+use std::{fs, path::Path};
+
+mod common;
+
+use common::evise;
+
+fn sync(dir: &Path, vault: &Path) -> String {
+    let output = evise(dir)
+        .args(["vault", "sync"])
+        .arg(vault)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn query(dir: &Path, sql: &str) -> Vec<String> {
+    let conn = rusqlite::Connection::open(dir.join("data.sqlite")).unwrap();
+    let mut stmt = conn.prepare(sql).unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<rusqlite::Result<_>>().unwrap()
+}
+
+fn note_id(path: &Path) -> String {
+    let text = fs::read_to_string(path).unwrap();
+    let ids: Vec<&str> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("revise-id: "))
+        .collect();
+    assert_eq!(ids.len(), 1, "{text}");
+    ids[0].to_string()
+}
+
+#[test]
+fn test_copied_note() {
+    let dir = tempfile::tempdir().unwrap();
+    let vault = dir.path().join("vault");
+    fs::create_dir(&vault).unwrap();
+    let raft = vault.join("raft.md");
+    fs::write(&raft, "---\nrevise: true\n---\nLeader election\n").unwrap();
+    sync(dir.path(), &vault);
+    let id = note_id(&raft);
+
+    // The copy sorts after the original, and before it the next time.
+    let copy = vault.join("raft2.md");
+    fs::copy(&raft, &copy).unwrap();
+    let output = sync(dir.path(), &vault);
+    assert!(output.contains("+ raft2"), "{output}");
+    assert_eq!(note_id(&raft), id);
+    assert_ne!(note_id(&copy), id);
+    fs::rename(&copy, vault.join("paxos.md")).unwrap();
+    let output = sync(dir.path(), &vault);
+    assert_eq!(output.trim(), "~ paxos");
+    assert_eq!(query(dir.path(), "SELECT title FROM cards").len(), 2);
+
+    // Fields set on the card stay, and a note that is back brings back its card.
+    let conn = rusqlite::Connection::open(dir.path().join("data.sqlite")).unwrap();
+    conn.execute(
+        "UPDATE cards SET fields = '[[\"year\",\"2014\"]]' WHERE title = 'raft'",
+        [],
+    )
+    .unwrap();
+    let text = fs::read_to_string(&raft).unwrap();
+    fs::remove_file(&raft).unwrap();
+    let output = sync(dir.path(), &vault);
+    assert!(output.contains("- raft (note deleted"), "{output}");
+    fs::write(&raft, text.replace("Leader election", "Log replication")).unwrap();
+    let output = sync(dir.path(), &vault);
+    assert!(output.contains("~ raft"), "{output}");
+    let sql = "SELECT fields || ' ' || suspended FROM cards WHERE title = 'raft'";
+    assert_eq!(query(dir.path(), sql), vec!["[[\"year\",\"2014\"]] 0"]);
+
+    // A card the user suspended stays so while its note changes.
+    conn.execute("UPDATE cards SET suspended = 1 WHERE title = 'raft'", [])
+        .unwrap();
+    let text = fs::read_to_string(&raft).unwrap();
+    fs::write(&raft, text.replace("Log replication", "Safety")).unwrap();
+    let output = sync(dir.path(), &vault);
+    assert!(output.contains("~ raft"), "{output}");
+    let sql = "SELECT title || ' ' || suspended FROM cards WHERE title = 'raft'";
+    assert_eq!(query(dir.path(), sql), vec!["raft 1"]);
+}